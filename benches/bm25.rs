@@ -0,0 +1,115 @@
+//! BM25 indexing and search. Run with `cargo bench --bench bm25`, and with
+//! `--features rayon` for the parallel scoring and ingestion numbers.
+
+mod common;
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use edgerag::Bm25Index;
+use std::hint::black_box;
+
+const VOCABULARY: u64 = 20_000;
+
+fn index(docs: &[String]) -> Bm25Index {
+    let mut index = Bm25Index::new();
+    let docs: Vec<(usize, String)> = docs.iter().cloned().enumerate().collect();
+    index.add_documents_parallel(&docs);
+    index
+}
+
+/// Warm IDF cache against a fresh index, whose first search builds the cache
+fn idf_cache(c: &mut Criterion) {
+    let fresh = index(&common::corpus(10_000, VOCABULARY));
+    let warm = fresh.clone();
+    warm.search("w1 w20 w300", 10);
+
+    let mut group = c.benchmark_group("bm25_idf_cache");
+    group.bench_function("cached", |b| b.iter(|| warm.search(black_box("w1 w20 w300"), 10)));
+    group.bench_function("first_search", |b| {
+        // Returning the index keeps dropping it out of the measurement
+        b.iter_batched(
+            || fresh.clone(),
+            |index| {
+                black_box(index.search(black_box("w1 w20 w300"), 10));
+                index
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+/// Scoring only the documents in the query terms' postings, against scoring
+/// every document as search did before candidate gathering
+fn candidates(c: &mut Criterion) {
+    let docs = common::corpus(10_000, VOCABULARY);
+    let index = index(&docs);
+
+    let mut group = c.benchmark_group("bm25_candidates");
+    for query in ["w15000 w18000", "w2 w50"] {
+        group.bench_with_input(BenchmarkId::new("postings", query), query, |b, query| {
+            b.iter(|| index.search(black_box(query), 10))
+        });
+        group.bench_with_input(BenchmarkId::new("full_scan", query), query, |b, query| {
+            b.iter(|| {
+                let mut scores: Vec<(usize, f32)> =
+                    (0..docs.len()).map(|doc_id| (doc_id, index.explain(query, doc_id).score)).collect();
+                scores.sort_by(|a, b| b.1.total_cmp(&a.1));
+                scores.truncate(10);
+                scores
+            })
+        });
+    }
+    group.finish();
+}
+
+/// Scoring a common-word query across thread counts
+#[cfg(feature = "rayon")]
+fn parallel_scoring(c: &mut Criterion) {
+    let index = index(&common::corpus(50_000, VOCABULARY));
+    let max_threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+
+    let mut group = c.benchmark_group("bm25_parallel_scoring");
+    let mut threads = 1;
+    while threads <= max_threads {
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+        group.bench_with_input(BenchmarkId::from_parameter(threads), &threads, |b, _| {
+            b.iter(|| pool.install(|| index.search(black_box("w0 w1 w2 w3"), 10)))
+        });
+        threads *= 2;
+    }
+    group.finish();
+}
+
+#[cfg(not(feature = "rayon"))]
+fn parallel_scoring(c: &mut Criterion) {
+    let index = index(&common::corpus(50_000, VOCABULARY));
+    c.bench_function("bm25_serial_scoring", |b| b.iter(|| index.search(black_box("w0 w1 w2 w3"), 10)));
+}
+
+/// `add_document` one at a time against one `add_documents_parallel` batch
+fn ingestion(c: &mut Criterion) {
+    let docs: Vec<(usize, String)> = common::corpus(10_000, VOCABULARY).into_iter().enumerate().collect();
+
+    let mut group = c.benchmark_group("bm25_ingestion");
+    group.sample_size(10);
+    group.bench_function("sequential", |b| {
+        b.iter(|| {
+            let mut index = Bm25Index::new();
+            for (doc_id, text) in &docs {
+                index.add_document(*doc_id, text);
+            }
+            index
+        })
+    });
+    group.bench_function("batch", |b| {
+        b.iter(|| {
+            let mut index = Bm25Index::new();
+            index.add_documents_parallel(&docs);
+            index
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, idf_cache, candidates, parallel_scoring, ingestion);
+criterion_main!(benches);
@@ -0,0 +1,46 @@
+//! Deterministic synthetic data shared by the benchmarks
+
+#![allow(dead_code)]
+
+use edgerag::mock_embed;
+
+/// SplitMix64, so every run benchmarks the same corpus
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+/// `n` documents of 50 words drawn from a `vocabulary`-word vocabulary with
+/// a skew towards low word ids, so low ids behave like common words and
+/// high ids like rare ones
+pub fn corpus(n: usize, vocabulary: u64) -> Vec<String> {
+    let mut rng = Rng::new(42);
+    (0..n)
+        .map(|_| {
+            (0..50)
+                .map(|_| {
+                    let a = rng.next_u64() % vocabulary;
+                    let b = rng.next_u64() % vocabulary;
+                    format!("w{}", a * b / vocabulary)
+                })
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .collect()
+}
+
+/// `n` unit-length embeddings of dimension `dim`
+pub fn embeddings(n: usize, dim: usize) -> Vec<Vec<f32>> {
+    (0..n).map(|i| mock_embed(&format!("doc {}", i), dim)).collect()
+}
@@ -0,0 +1,91 @@
+//! Vector similarity and search. Run with `cargo bench --bench vector`, and
+//! with `--features simd` or `--features rayon` to compare those paths.
+
+mod common;
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use edgerag::{mock_embed, VectorStore};
+use std::hint::black_box;
+
+/// The plain loop `cosine_similarity` used before the SIMD path
+fn scalar_cosine(a: &[f32], b: &[f32]) -> f32 {
+    let mut dot = 0.0;
+    let mut norm_a = 0.0;
+    let mut norm_b = 0.0;
+    for (x, y) in a.iter().zip(b) {
+        dot += x * y;
+        norm_a += x * x;
+        norm_b += y * y;
+    }
+    dot / (norm_a.sqrt() * norm_b.sqrt()).max(1e-9)
+}
+
+fn cosine(c: &mut Criterion) {
+    let mut group = c.benchmark_group("cosine_similarity");
+    for dim in [768, 1536] {
+        let vectors = common::embeddings(2, dim);
+        let (a, b) = (&vectors[0], &vectors[1]);
+        group.bench_with_input(BenchmarkId::new("crate", dim), &dim, |bench, _| {
+            bench.iter(|| VectorStore::cosine_similarity(black_box(a), black_box(b)))
+        });
+        group.bench_with_input(BenchmarkId::new("scalar", dim), &dim, |bench, _| {
+            bench.iter(|| scalar_cosine(black_box(a), black_box(b)))
+        });
+    }
+    group.finish();
+}
+
+/// Cosine search over raw embeddings against a `new_normalized` store and
+/// `search_dot`, which both reduce to dot products
+fn normalized(c: &mut Criterion) {
+    // Scaled copies, so the plain store really has to compute norms
+    let embeddings: Vec<Vec<f32>> = common::embeddings(10_000, 384)
+        .into_iter()
+        .enumerate()
+        .map(|(i, emb)| emb.into_iter().map(|x| x * (1 + i % 5) as f32).collect())
+        .collect();
+    let query = mock_embed("query", 384);
+    let plain = VectorStore::new(embeddings.clone()).unwrap();
+    let normalized = VectorStore::new_normalized(embeddings).unwrap();
+
+    let mut group = c.benchmark_group("vector_normalized_search");
+    group.bench_function("search", |b| b.iter(|| plain.search(black_box(&query), 10).unwrap()));
+    group.bench_function("new_normalized_search", |b| {
+        b.iter(|| normalized.search(black_box(&query), 10).unwrap())
+    });
+    group.bench_function("search_dot", |b| b.iter(|| normalized.search_dot(black_box(&query), 10).unwrap()));
+    group.finish();
+}
+
+/// Bounded top-k selection against sorting every similarity, over 1M vectors
+fn top_k(c: &mut Criterion) {
+    let store = VectorStore::new_normalized(common::embeddings(1_000_000, 16)).unwrap();
+    let query = mock_embed("query", 16);
+
+    let mut group = c.benchmark_group("vector_top_k_1m");
+    group.sample_size(10);
+    group.bench_function("limit_10", |b| b.iter(|| store.search(black_box(&query), 10).unwrap()));
+    group.bench_function("full_sort", |b| {
+        b.iter(|| {
+            let mut results = store.search(black_box(&query), 0).unwrap();
+            results.truncate(10);
+            results
+        })
+    });
+    group.finish();
+}
+
+/// Dimension and finiteness validation in `VectorStore::new`
+fn construction(c: &mut Criterion) {
+    let embeddings = common::embeddings(200_000, 64);
+
+    let mut group = c.benchmark_group("vector_store_new");
+    group.sample_size(10);
+    group.bench_function("200k_x_64", |b| {
+        b.iter_batched(|| embeddings.clone(), |e| VectorStore::new(e).unwrap(), BatchSize::LargeInput)
+    });
+    group.finish();
+}
+
+criterion_group!(benches, cosine, normalized, top_k, construction);
+criterion_main!(benches);
@@ -1,5 +1,16 @@
+use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
-use std::collections::{HashSet, HashMap};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
+
+#[cfg(feature = "lru")]
+use crate::cache::QueryCache;
+use crate::ranking::{cmp_score, ranked, top_k};
+use crate::tokenizer::{stem_tokens, DefaultTokenizer, Language, StopwordFilter, Tokenizer};
+use crate::trace::span;
+use crate::types::Chunk;
 
 /// BM25 scoring parameters
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -14,9 +25,123 @@ pub struct Bm25Params {
     #[serde(default = "default_b")]
     pub b: f32,
 
-    /// Epsilon value to prevent log(0)
+    /// Lower bound for the IDF of a term, so very common terms still
+    /// contribute a small positive score
     #[serde(default = "default_epsilon")]
     pub epsilon: f32,
+
+    /// Reduce terms to their English stem at index and query time,
+    /// so "running" matches "run"
+    #[serde(default)]
+    pub stemming: bool,
+
+    /// Boost per field for documents added with `add_document_fields`,
+    /// e.g. `title: 3.0, body: 1.0`. Fields not listed get 1.0.
+    #[serde(default)]
+    pub field_weights: HashMap<String, f32>,
+
+    /// Query terms missing from the index also match indexed terms within
+    /// this many edits (sharing the first character), at a reduced weight.
+    /// 0 disables fuzzy matching.
+    #[serde(default)]
+    pub max_edit_distance: usize,
+
+    /// IDF formula, floored at `epsilon` whichever is chosen
+    #[serde(default)]
+    pub idf: IdfVariant,
+}
+
+/// IDF formulas for `Bm25Params::idf`, with `N` live documents of which
+/// `df` contain the term
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IdfVariant {
+    /// `ln((N - df + 0.5) / (df + 0.5) + 1)`, as in Lucene. Always positive
+    #[default]
+    Lucene,
+    /// `ln(N / df)`, with `df` of at least 1 so unseen terms stay finite
+    Classic,
+    /// Robertson-Sparck Jones `ln((N - df + 0.5) / (df + 0.5))`, negative
+    /// for terms in more than half the documents before the `epsilon` floor
+    Probabilistic,
+}
+
+impl Bm25Params {
+    /// Start from the defaults, with validation on `build`
+    pub fn builder() -> Bm25ParamsBuilder {
+        Bm25ParamsBuilder::default()
+    }
+
+    pub fn field_weight(&self, field: &str) -> f32 {
+        self.field_weights.get(field).copied().unwrap_or(1.0)
+    }
+
+    /// Check the parameters are in range: k1 > 0, b in [0, 1], epsilon >= 0
+    /// and field weights >= 0, all finite
+    pub fn validate(&self) -> Result<()> {
+        if !(self.k1.is_finite() && self.k1 > 0.0) {
+            return Err(anyhow::anyhow!("k1 must be a positive number, got {}", self.k1));
+        }
+        if !(0.0..=1.0).contains(&self.b) {
+            return Err(anyhow::anyhow!("b must be between 0 and 1, got {}", self.b));
+        }
+        if !(self.epsilon.is_finite() && self.epsilon >= 0.0) {
+            return Err(anyhow::anyhow!("epsilon must be a non-negative number, got {}", self.epsilon));
+        }
+        if let Some((field, weight)) = self.field_weights.iter().find(|(_, w)| !(w.is_finite() && **w >= 0.0)) {
+            return Err(anyhow::anyhow!("Weight of field {} must be a non-negative number, got {}", field, weight));
+        }
+        Ok(())
+    }
+}
+
+/// Builds validated `Bm25Params`, see `Bm25Params::builder`
+#[derive(Debug, Clone, Default)]
+pub struct Bm25ParamsBuilder {
+    params: Bm25Params,
+}
+
+impl Bm25ParamsBuilder {
+    pub fn k1(mut self, k1: f32) -> Self {
+        self.params.k1 = k1;
+        self
+    }
+
+    pub fn b(mut self, b: f32) -> Self {
+        self.params.b = b;
+        self
+    }
+
+    pub fn epsilon(mut self, epsilon: f32) -> Self {
+        self.params.epsilon = epsilon;
+        self
+    }
+
+    pub fn stemming(mut self, stemming: bool) -> Self {
+        self.params.stemming = stemming;
+        self
+    }
+
+    pub fn field_weight(mut self, field: &str, weight: f32) -> Self {
+        self.params.field_weights.insert(field.to_string(), weight);
+        self
+    }
+
+    pub fn max_edit_distance(mut self, max_edit_distance: usize) -> Self {
+        self.params.max_edit_distance = max_edit_distance;
+        self
+    }
+
+    pub fn idf(mut self, idf: IdfVariant) -> Self {
+        self.params.idf = idf;
+        self
+    }
+
+    /// The params, or an error naming the first out-of-range value
+    pub fn build(self) -> Result<Bm25Params> {
+        self.params.validate()?;
+        Ok(self.params)
+    }
 }
 
 fn default_k1() -> f32 {
@@ -35,12 +160,98 @@ impl Default for Bm25Params {
             k1: default_k1(),
             b: default_b(),
             epsilon: default_epsilon(),
+            stemming: false,
+            field_weights: HashMap::new(),
+            max_edit_distance: 0,
+            idf: IdfVariant::Lucene,
         }
     }
 }
 
-/// BM25 index for efficient keyword search
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+/// Upper bound on indexed terms a single misspelled query term expands to
+const MAX_FUZZY_EXPANSIONS: usize = 8;
+
+/// Leading bytes of a saved index file
+const FILE_MAGIC: &[u8; 4] = b"EBM2";
+
+/// Leading bytes of a file written by `Bm25Index::save_compressed`
+const COMPRESSED_FILE_MAGIC: &[u8; 4] = b"EBMZ";
+
+/// Bumped whenever the on-disk layout of `Bm25Index` changes
+const FILE_FORMAT_VERSION: u32 = 8;
+
+/// Number of terms listed in `IndexStats::top_terms`
+const STATS_TOP_TERMS: usize = 10;
+
+/// How far past the end of the index a new doc_id may land. Every skipped
+/// slot is kept as a tombstone, so an unbounded gap could allocate gigabytes
+const MAX_DOC_ID_GAP: usize = 1 << 20;
+
+/// Summary of a `Bm25Index`, returned by `Bm25Index::stats`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexStats {
+    /// Live documents
+    pub total_docs: usize,
+
+    /// Average document length in tokens
+    pub avg_doc_length: f32,
+
+    /// Distinct indexed terms
+    pub vocabulary_size: usize,
+
+    /// Total (term, document) pairs across the inverted index
+    pub total_postings: usize,
+
+    /// Terms in the most documents, with their document frequency,
+    /// most frequent first (ties alphabetical)
+    pub top_terms: Vec<(String, usize)>,
+
+    /// `search` calls answered from the query cache (always 0 without the `lru` feature)
+    pub cache_hits: u64,
+
+    /// `search` calls that had to be scored while the query cache was enabled
+    pub cache_misses: u64,
+}
+
+/// Breakdown of a document's BM25 score, returned by `Bm25Index::explain`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScoreExplanation {
+    pub doc_id: usize,
+
+    /// Sum of all term contributions, equal to the score `search` ranks by
+    pub score: f32,
+
+    /// One entry per matching query term or quoted phrase
+    pub terms: Vec<TermExplanation>,
+}
+
+/// Contribution of a single query term (or quoted phrase) to a document score
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TermExplanation {
+    /// The analyzed term, or the phrase in double quotes
+    pub term: String,
+
+    /// Query-time boost from `term^weight`
+    pub boost: f32,
+
+    /// Occurrences of the term (or phrase) in the document
+    pub term_frequency: f32,
+
+    pub idf: f32,
+
+    /// `1 - b + b * doc_length / avg_doc_length`. For fielded documents this
+    /// is the effective factor implied by the BM25F weighted term frequency.
+    pub length_norm: f32,
+
+    /// `boost * idf * tf * (k1 + 1) / (tf + k1 * length_norm)`
+    pub contribution: f32,
+}
+
+/// BM25 index for efficient keyword search.
+///
+/// `Send + Sync`: searches take `&self`, so one index can serve many threads.
+/// Wrap it in `SharedIndex` to also allow writes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Bm25Index {
     /// Document frequencies for each term
     doc_frequencies: HashMap<String, usize>,
@@ -54,105 +265,2646 @@ pub struct Bm25Index {
     /// Total number of documents
     total_docs: usize,
 
+    /// Sum of `doc_lengths`, so the average updates in O(1) per change. Not
+    /// serialized: rebuilt from `doc_lengths` on first use after loading
+    #[serde(skip)]
+    total_tokens: Option<usize>,
+
     /// Inverted index: term -> list of (doc_id, term_frequency)
     inverted_index: HashMap<String, Vec<(usize, usize)>>,
 
+    /// Forward index: doc_id -> its terms, so removing a document only
+    /// touches its own posting lists. Not serialized: rebuilt from
+    /// `inverted_index` on first use after loading
+    #[serde(skip)]
+    doc_terms: Option<HashMap<usize, Vec<String>>>,
+
     /// BM25 parameters
     params: Bm25Params,
 
     /// Token to index mapping for faster lookups
     token_to_docs: HashMap<String, HashSet<usize>>,
+
+    /// Token positions for phrase matching: term -> doc_id -> sorted positions
+    #[serde(default)]
+    term_positions: HashMap<String, HashMap<usize, Vec<usize>>>,
+
+    /// Per-field statistics for documents added with `add_document_fields`
+    #[serde(default)]
+    fielded_docs: HashMap<usize, FieldedDoc>,
+
+    /// Total token count per field across all fielded documents
+    #[serde(default)]
+    field_length_totals: HashMap<String, usize>,
+
+    /// Doc slots that hold no live document (removed, or skipped over by a
+    /// larger doc_id). Keeping them as tombstones keeps doc_ids stable.
+    #[serde(default)]
+    tombstones: HashSet<usize>,
+
+    /// Words dropped at both index and query time
+    #[serde(default)]
+    stopwords: StopwordFilter,
+
+    /// Caller-supplied string id per document: doc_id -> external id
+    #[serde(default)]
+    external_ids: HashMap<usize, String>,
+
+    /// Reverse of `external_ids`
+    #[serde(default)]
+    doc_ids_by_external: HashMap<String, usize>,
+
+    /// IDF per term, filled on the first query after the index changes
+    #[serde(skip)]
+    idf_cache: OnceLock<HashMap<String, f32>>,
+
+    /// Indexed terms grouped by first character, for fuzzy candidate lookup
+    #[serde(skip)]
+    fuzzy_index: OnceLock<HashMap<char, Vec<String>>>,
+
+    /// Recent `search` results, see `enable_query_cache`
+    #[cfg(feature = "lru")]
+    #[serde(skip)]
+    query_cache: QueryCache<ParsedQuery>,
+
+    /// Not serialized: a custom tokenizer must be set again after loading
+    #[serde(skip, default = "default_tokenizer")]
+    tokenizer: Arc<dyn Tokenizer>,
+}
+
+fn default_tokenizer() -> Arc<dyn Tokenizer> {
+    Arc::new(DefaultTokenizer::default())
+}
+
+impl Default for Bm25Index {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Bm25Index {
     pub fn new() -> Self {
+        Self::with_params(Bm25Params::default())
+    }
+
+    /// Create an empty index with custom scoring parameters
+    pub fn with_params(params: Bm25Params) -> Self {
         Self {
             doc_frequencies: HashMap::new(),
             doc_lengths: Vec::new(),
             avg_doc_length: 0.0,
             total_docs: 0,
+            total_tokens: Some(0),
             inverted_index: HashMap::new(),
-            params: Bm25Params::default(),
-            token_to_docs: HashMap::new()
+            doc_terms: Some(HashMap::new()),
+            params,
+            token_to_docs: HashMap::new(),
+            term_positions: HashMap::new(),
+            fielded_docs: HashMap::new(),
+            field_length_totals: HashMap::new(),
+            tombstones: HashSet::new(),
+            stopwords: StopwordFilter::default(),
+            external_ids: HashMap::new(),
+            doc_ids_by_external: HashMap::new(),
+            idf_cache: OnceLock::new(),
+            fuzzy_index: OnceLock::new(),
+            #[cfg(feature = "lru")]
+            query_cache: QueryCache::new(),
+            tokenizer: default_tokenizer(),
         }
     }
 
-    pub fn search(&self, query: &str, limit: usize) -> Vec<(usize, f32)> {
-        if self.total_docs == 0 {
-            return Vec::new();
+    pub fn params(&self) -> &Bm25Params {
+        &self.params
+    }
+
+    /// Replace the scoring parameters. Errors if they are out of range (see
+    /// `Bm25Params::validate`). Stemming can't be toggled once documents are
+    /// indexed, since indexed terms would no longer match query terms.
+    pub fn set_params(&mut self, params: Bm25Params) -> Result<()> {
+        params.validate()?;
+        if self.total_docs > 0 && params.stemming != self.params.stemming {
+            return Err(anyhow::anyhow!(
+                "Index was built with stemming {}, cannot switch it {}",
+                if self.params.stemming { "on" } else { "off" },
+                if params.stemming { "on" } else { "off" }
+            ));
         }
+        self.params = params;
+        self.idf_cache = OnceLock::new();
+        self.clear_query_cache();
+        Ok(())
+    }
 
-        let query_tokens = tokenize(query);
-        if query_tokens.is_empty() {
-            return Vec::new();
+    /// Drop `words` from indexed documents and queries.
+    /// Set this before indexing; documents already indexed keep their stopwords.
+    pub fn set_stopwords(&mut self, words: HashSet<String>) {
+        self.set_stopword_filter(StopwordFilter::new(words));
+    }
+
+    /// Drop the built-in stopwords of `languages` (see `StopwordFilter::for_languages`).
+    /// A new index keeps every token, so stopwords only apply once set here or with
+    /// `set_stopwords`. Like `set_stopwords`, set this before indexing
+    pub fn set_stopword_languages(&mut self, languages: &[Language]) {
+        self.set_stopword_filter(StopwordFilter::for_languages(languages));
+    }
+
+    pub fn set_stopword_filter(&mut self, filter: StopwordFilter) {
+        self.stopwords = filter;
+        self.clear_query_cache();
+    }
+
+    /// Cache the results of up to `capacity` distinct `search` calls, keyed
+    /// by the parsed query and limit, so queries that analyze to the same
+    /// terms share an entry. Any change to the index clears it. A capacity of
+    /// 0 turns caching off.
+    #[cfg(feature = "lru")]
+    pub fn enable_query_cache(&mut self, capacity: usize) {
+        self.query_cache.set_capacity(capacity);
+    }
+
+    fn clear_query_cache(&self) {
+        #[cfg(feature = "lru")]
+        self.query_cache.clear();
+    }
+
+    /// Write the index to `path` as bincode, prefixed with a format header.
+    /// A custom tokenizer is not saved and must be set again after `load`.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let mut bytes = Vec::with_capacity(8);
+        bytes.extend_from_slice(FILE_MAGIC);
+        bytes.extend_from_slice(&FILE_FORMAT_VERSION.to_le_bytes());
+        bincode::serialize_into(&mut bytes, self).context("Failed to serialize BM25 index")?;
+        std::fs::write(path, bytes)
+            .with_context(|| format!("Failed to write BM25 index to {}", path.display()))
+    }
+
+    /// Read an index written by `save`
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path)
+            .with_context(|| format!("Failed to read BM25 index from {}", path.display()))?;
+
+        if bytes.len() < 8 || &bytes[..4] != FILE_MAGIC {
+            return Err(anyhow::anyhow!("{} is not a BM25 index file", path.display()));
         }
+        let version = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+        if version != FILE_FORMAT_VERSION {
+            return Err(anyhow::anyhow!(
+                "BM25 index file version {} is not supported (expected {})",
+                version, FILE_FORMAT_VERSION
+            ));
+        }
+
+        let mut index: Self = bincode::deserialize(&bytes[8..])
+            .with_context(|| format!("BM25 index file {} is corrupt or truncated", path.display()))?;
+        index
+            .check_slots()
+            .with_context(|| format!("BM25 index file {} is corrupt", path.display()))?;
+        Ok(index)
+    }
+
+    /// Like `save`, but with posting lists sorted by doc_id, delta-encoded and
+    /// written as variable-byte integers, along with the term positions.
+    /// Lookup structures derivable from the postings are rebuilt on
+    /// `load_compressed` instead of being saved, so files are typically
+    /// several times smaller than with `save`.
+    pub fn save_compressed<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(COMPRESSED_FILE_MAGIC);
+        bytes.extend_from_slice(&FILE_FORMAT_VERSION.to_le_bytes());
 
-        let mut scores: HashMap<usize, f32> = HashMap::new();
+        let rest = (
+            &self.doc_lengths,
+            self.avg_doc_length,
+            self.total_docs,
+            &self.params,
+            &self.fielded_docs,
+            &self.field_length_totals,
+            &self.tombstones,
+            &self.stopwords,
+            &self.external_ids,
+            &self.doc_ids_by_external,
+        );
+        let rest = bincode::serialize(&rest).context("Failed to serialize BM25 index")?;
+        write_varint(&mut bytes, rest.len() as u64);
+        bytes.extend_from_slice(&rest);
 
-        for doc_id in 0..self.total_docs {
-            let score = self.calculate_score(&query_tokens, doc_id);
-            if score > 0.0 {
-                scores.insert(doc_id, score);
+        let mut terms: Vec<&String> = self.inverted_index.keys().collect();
+        terms.sort_unstable();
+        write_varint(&mut bytes, terms.len() as u64);
+        for term in terms {
+            write_varint(&mut bytes, term.len() as u64);
+            bytes.extend_from_slice(term.as_bytes());
+
+            let mut postings = self.inverted_index[term].clone();
+            postings.sort_unstable();
+            let positions = self.term_positions.get(term);
+            write_varint(&mut bytes, postings.len() as u64);
+
+            let mut previous_doc = 0;
+            for (doc_id, frequency) in postings {
+                write_varint(&mut bytes, (doc_id - previous_doc) as u64);
+                write_varint(&mut bytes, frequency as u64);
+                previous_doc = doc_id;
+
+                let doc_positions = positions.and_then(|p| p.get(&doc_id)).map_or(&[][..], Vec::as_slice);
+                write_varint(&mut bytes, doc_positions.len() as u64);
+                let mut previous_position = 0;
+                for &position in doc_positions {
+                    write_varint(&mut bytes, (position - previous_position) as u64);
+                    previous_position = position;
+                }
             }
         }
 
-        let mut results: Vec<(usize, f32)> = scores.into_iter().collect();
-        results.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
-        results.truncate(limit);
-        results
+        std::fs::write(path, bytes)
+            .with_context(|| format!("Failed to write BM25 index to {}", path.display()))
     }
 
-    /// Calculate BM25 score for a document given query terms
-    fn calculate_score(&self, query_tokens: &[String], doc_id: usize) -> f32 {
-        let mut score = 0.0;
-        let doc_length = self.doc_lengths[doc_id] as f32;
+    /// Read an index written by `save_compressed`
+    pub fn load_compressed<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path)
+            .with_context(|| format!("Failed to read BM25 index from {}", path.display()))?;
 
-        // Length normalization factor
-        let length_norm = 1.0 - self.params.b + self.params.b * (doc_length / self.avg_doc_length);
+        if bytes.len() < 8 || &bytes[..4] != COMPRESSED_FILE_MAGIC {
+            return Err(anyhow::anyhow!("{} is not a compressed BM25 index file", path.display()));
+        }
+        let version = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+        if version != FILE_FORMAT_VERSION {
+            return Err(anyhow::anyhow!(
+                "BM25 index file version {} is not supported (expected {})",
+                version, FILE_FORMAT_VERSION
+            ));
+        }
 
-        for term in query_tokens {
-            // Get term frequency in this document
-            let tf = self.get_term_frequency(term, doc_id) as f32;
-            if tf == 0.0 {
-                continue;
+        Self::decode_compressed(&bytes[8..])
+            .with_context(|| format!("BM25 index file {} is corrupt or truncated", path.display()))
+    }
+
+    fn decode_compressed(bytes: &[u8]) -> Result<Self> {
+        type Rest = (
+            Vec<usize>,
+            f32,
+            usize,
+            Bm25Params,
+            HashMap<usize, FieldedDoc>,
+            HashMap<String, usize>,
+            HashSet<usize>,
+            StopwordFilter,
+            HashMap<usize, String>,
+            HashMap<String, usize>,
+        );
+
+        let mut reader = VarintReader { bytes, pos: 0 };
+        let rest_len = reader.varint()? as usize;
+        let rest: Rest = bincode::deserialize(reader.take(rest_len)?)?;
+        let (
+            doc_lengths,
+            avg_doc_length,
+            total_docs,
+            params,
+            fielded_docs,
+            field_length_totals,
+            tombstones,
+            stopwords,
+            external_ids,
+            doc_ids_by_external,
+        ) = rest;
+
+        let mut index = Self::with_params(params);
+        index.doc_lengths = doc_lengths;
+        index.avg_doc_length = avg_doc_length;
+        index.total_docs = total_docs;
+        index.total_tokens = None;
+        index.doc_terms = None;
+        index.fielded_docs = fielded_docs;
+        index.field_length_totals = field_length_totals;
+        index.tombstones = tombstones;
+        index.stopwords = stopwords;
+        index.external_ids = external_ids;
+        index.doc_ids_by_external = doc_ids_by_external;
+
+        for _ in 0..reader.varint()? {
+            let term_len = reader.varint()? as usize;
+            let term = String::from_utf8(reader.take(term_len)?.to_vec())?;
+
+            let count = reader.varint()? as usize;
+            let mut postings = Vec::with_capacity(count.min(index.doc_lengths.len()));
+            let mut docs = HashSet::new();
+            let mut positions = HashMap::new();
+            let mut doc_id: usize = 0;
+            for i in 0..count {
+                let gap = reader.varint()? as usize;
+                if i > 0 && gap == 0 {
+                    return Err(anyhow::anyhow!("Duplicate posting for doc {} of term {:?}", doc_id, term));
+                }
+                doc_id = doc_id
+                    .checked_add(gap)
+                    .filter(|&doc_id| doc_id < index.doc_lengths.len())
+                    .ok_or_else(|| anyhow::anyhow!("Posting of term {:?} is out of range", term))?;
+                postings.push((doc_id, reader.varint()? as usize));
+                docs.insert(doc_id);
+
+                let position_count = reader.varint()? as usize;
+                if position_count > 0 {
+                    let mut doc_positions = Vec::with_capacity(position_count.min(reader.remaining()));
+                    let mut position: usize = 0;
+                    for _ in 0..position_count {
+                        position = position
+                            .checked_add(reader.varint()? as usize)
+                            .ok_or_else(|| anyhow::anyhow!("Position of term {:?} is out of range", term))?;
+                        doc_positions.push(position);
+                    }
+                    positions.insert(doc_id, doc_positions);
+                }
+            }
+
+            index.doc_frequencies.insert(term.clone(), postings.len());
+            index.token_to_docs.insert(term.clone(), docs);
+            if !positions.is_empty() {
+                index.term_positions.insert(term.clone(), positions);
+            }
+            index.inverted_index.insert(term, postings);
+        }
+
+        if reader.remaining() > 0 {
+            return Err(anyhow::anyhow!("{} unexpected bytes after the postings", reader.remaining()));
+        }
+        index.check_slots()?;
+        Ok(index)
+    }
+
+    /// Check that every tombstone of a loaded index is a doc slot, and
+    /// recompute `total_docs` from the slots rather than trusting the file,
+    /// so removing documents can't underflow it
+    fn check_slots(&mut self) -> Result<()> {
+        let slots = self.doc_lengths.len();
+        if let Some(doc_id) = self.tombstones.iter().find(|&&doc_id| doc_id >= slots) {
+            return Err(anyhow::anyhow!("Tombstone {} is out of range ({} doc slots)", doc_id, slots));
+        }
+        self.total_docs = slots - self.tombstones.len();
+        Ok(())
+    }
+
+    /// Replace the tokenizer used at index and query time.
+    /// Set this before indexing, and again after deserializing an index.
+    pub fn set_tokenizer<T: Tokenizer + 'static>(&mut self, tokenizer: T) {
+        self.tokenizer = Arc::new(tokenizer);
+        self.clear_query_cache();
+    }
+
+    /// Run text through the same pipeline used for documents and queries
+    /// (tokenizer, stopwords, stemming), to see exactly which terms it produces.
+    /// Query syntax such as quotes and `^` boosts is not interpreted.
+    pub fn analyze(&self, text: &str) -> Vec<String> {
+        let mut tokens = self.tokenizer.tokenize(text);
+        if !self.stopwords.is_empty() {
+            tokens.retain(|t| !self.stopwords.is_stopword(t));
+        }
+        if self.params.stemming {
+            tokens = stem_tokens(tokens);
+        }
+        tokens
+    }
+
+    /// Build an index from a slice of chunks, using each chunk's position as
+    /// its doc_id and the chunk id as its external id
+    pub fn build_from_chunks(chunks: &[Chunk]) -> Self {
+        let mut index = Self::new();
+        for (doc_id, chunk) in chunks.iter().enumerate() {
+            index.add_document(doc_id, &chunk.as_text());
+            index.set_external_id(doc_id, &chunk.id);
+        }
+        index
+    }
+
+    /// Tokenize `text` and add it to the index under `doc_id`.
+    /// If the doc_id is already indexed, the old document is replaced.
+    ///
+    /// Panics if `doc_id` is more than 2^20 past the highest doc_id so far,
+    /// since every doc_id skipped over is stored as a tombstone.
+    pub fn add_document(&mut self, doc_id: usize, text: &str) {
+        self.check_doc_ids([doc_id]);
+        self.unindex_document(doc_id);
+        let (length, term_positions) = self.analyze_positions(text);
+        self.index_terms(doc_id, length, term_positions);
+        self.refresh_corpus_stats();
+    }
+
+    /// Add many `(doc_id, text)` documents, with the same result as calling
+    /// `add_document` for each in order. With the `rayon` feature the texts
+    /// are tokenized in parallel before being merged into the index, and
+    /// corpus statistics are recomputed once for the whole batch.
+    /// Panics like `add_document`, before indexing anything.
+    pub fn add_documents_parallel(&mut self, docs: &[(usize, String)]) {
+        self.check_doc_ids(docs.iter().map(|&(doc_id, _)| doc_id));
+        #[cfg(feature = "rayon")]
+        let analyzed: Vec<(usize, HashMap<String, Vec<usize>>)> = {
+            use rayon::prelude::*;
+            docs.par_iter().map(|(_, text)| self.analyze_positions(text)).collect()
+        };
+        #[cfg(not(feature = "rayon"))]
+        let analyzed: Vec<(usize, HashMap<String, Vec<usize>>)> =
+            docs.iter().map(|(_, text)| self.analyze_positions(text)).collect();
+
+        for (&(doc_id, _), (length, term_positions)) in docs.iter().zip(analyzed) {
+            self.unindex_document(doc_id);
+            self.index_terms(doc_id, length, term_positions);
+        }
+        self.refresh_corpus_stats();
+    }
+
+    /// Token count of `text` and the positions of each analyzed term
+    fn analyze_positions(&self, text: &str) -> (usize, HashMap<String, Vec<usize>>) {
+        let tokens = self.analyze(text);
+        let length = tokens.len();
+
+        let mut term_positions: HashMap<String, Vec<usize>> = HashMap::new();
+        for (position, token) in tokens.into_iter().enumerate() {
+            term_positions.entry(token).or_default().push(position);
+        }
+        (length, term_positions)
+    }
+
+    /// Add a document made of named fields (e.g. title and body) scored with
+    /// BM25F: each field is length-normalized against that field's average
+    /// length and weighted by `Bm25Params::field_weights`.
+    /// If the doc_id is already indexed, the old document is replaced.
+    /// Panics like `add_document`.
+    pub fn add_document_fields(&mut self, doc_id: usize, fields: HashMap<String, String>) {
+        self.check_doc_ids([doc_id]);
+        self.unindex_document(doc_id);
+
+        let mut names: Vec<&String> = fields.keys().collect();
+        names.sort();
+
+        let mut doc = FieldedDoc::default();
+        let mut term_positions: HashMap<String, Vec<usize>> = HashMap::new();
+        let mut position = 0;
+
+        for name in names {
+            let tokens = self.analyze(&fields[name]);
+            doc.lengths.insert(name.clone(), tokens.len());
+            *self.field_length_totals.entry(name.clone()).or_insert(0) += tokens.len();
+
+            for token in tokens {
+                *doc.term_frequencies
+                    .entry(token.clone())
+                    .or_default()
+                    .entry(name.clone())
+                    .or_insert(0) += 1;
+                term_positions.entry(token).or_default().push(position);
+                position += 1;
             }
+            // Leave a gap so phrases never match across field boundaries
+            position += 1;
+        }
+
+        let length = doc.lengths.values().sum();
+        self.fielded_docs.insert(doc_id, doc);
+        self.index_terms(doc_id, length, term_positions);
+        self.refresh_corpus_stats();
+    }
+
+    /// Panics if adding `doc_ids` in order would at some point skip more than
+    /// `MAX_DOC_ID_GAP` slots past the end of the index
+    fn check_doc_ids(&self, doc_ids: impl IntoIterator<Item = usize>) {
+        let mut len = self.doc_lengths.len();
+        for doc_id in doc_ids {
+            assert!(
+                doc_id.saturating_sub(len) <= MAX_DOC_ID_GAP,
+                "doc_id {} is more than {} past the end of the index ({} slots)",
+                doc_id, MAX_DOC_ID_GAP, len
+            );
+            len = len.max(doc_id + 1);
+        }
+    }
+
+    /// Add a document's postings. Callers refresh corpus statistics afterwards
+    fn index_terms(&mut self, doc_id: usize, length: usize, term_positions: HashMap<String, Vec<usize>>) {
+        if doc_id >= self.doc_lengths.len() {
+            self.tombstones.extend(self.doc_lengths.len()..doc_id);
+            self.doc_lengths.resize(doc_id + 1, 0);
+        }
+        self.tombstones.remove(&doc_id);
+        let total_tokens = self.total_tokens() - self.doc_lengths[doc_id] + length;
+        self.total_tokens = Some(total_tokens);
+        self.doc_lengths[doc_id] = length;
+        let terms = term_positions.keys().cloned().collect();
+        self.doc_terms().insert(doc_id, terms);
+
+        for (term, positions) in term_positions {
+            *self.doc_frequencies.entry(term.clone()).or_insert(0) += 1;
+            self.inverted_index
+                .entry(term.clone())
+                .or_default()
+                .push((doc_id, positions.len()));
+            self.token_to_docs.entry(term.clone()).or_default().insert(doc_id);
+            self.term_positions.entry(term).or_default().insert(doc_id, positions);
+        }
+
+        self.total_docs += 1;
+    }
+
+    /// Drop terms found in fewer than `min_df` documents or in more than
+    /// `max_df_ratio` of them (e.g. `prune_vocabulary(2, 0.5)`), shrinking the
+    /// index. Returns the number of terms removed.
+    ///
+    /// Pruned terms no longer match any query. This changes scores: document
+    /// lengths still count the pruned tokens, and documents matched only
+    /// through pruned terms drop out of results. Terms of documents added
+    /// later are indexed as usual.
+    pub fn prune_vocabulary(&mut self, min_df: usize, max_df_ratio: f32) -> usize {
+        let max_df = max_df_ratio * self.total_docs as f32;
+        let pruned: Vec<String> = self
+            .doc_frequencies
+            .iter()
+            .filter(|&(_, &df)| df < min_df || df as f32 > max_df)
+            .map(|(term, _)| term.clone())
+            .collect();
+
+        for term in &pruned {
+            self.doc_frequencies.remove(term);
+            self.inverted_index.remove(term);
+            self.token_to_docs.remove(term);
+            self.term_positions.remove(term);
+        }
+        if !pruned.is_empty() {
+            self.doc_terms = None;
+            self.idf_cache = OnceLock::new();
+            self.fuzzy_index = OnceLock::new();
+            self.clear_query_cache();
+        }
+        pruned.len()
+    }
+
+    /// Remove a document from the index. Its slot is tombstoned rather than
+    /// compacted, so the doc_ids of the remaining documents stay valid.
+    /// Returns false if the doc_id was not indexed.
+    pub fn remove_document(&mut self, doc_id: usize) -> bool {
+        if !self.unindex_document(doc_id) {
+            return false;
+        }
+        if let Some(external_id) = self.external_ids.remove(&doc_id) {
+            self.doc_ids_by_external.remove(&external_id);
+        }
+        true
+    }
+
+    /// Add a document identified by a string id and return the doc_id it was
+    /// given. Reusing an id that is still indexed replaces that document in place.
+    pub fn add_document_with_id(&mut self, external_id: &str, text: &str) -> usize {
+        let doc_id = self
+            .doc_ids_by_external
+            .get(external_id)
+            .copied()
+            .unwrap_or(self.doc_lengths.len());
+        self.add_document(doc_id, text);
+        self.set_external_id(doc_id, external_id);
+        doc_id
+    }
+
+    /// The external id of `doc_id`, if it was given one
+    pub fn external_id(&self, doc_id: usize) -> Option<&str> {
+        self.external_ids.get(&doc_id).map(String::as_str)
+    }
+
+    /// The doc_id currently holding `external_id`
+    pub fn doc_id_for(&self, external_id: &str) -> Option<usize> {
+        self.doc_ids_by_external.get(external_id).copied()
+    }
+
+    /// Like `search`, but returns external ids. Documents without one are skipped.
+    pub fn search_by_id(&self, query: &str, limit: usize) -> Vec<(String, f32)> {
+        self.search_where(query, limit, |doc_id| self.external_ids.contains_key(&doc_id), |_, _| true)
+            .into_iter()
+            .filter_map(|(doc_id, score)| self.external_id(doc_id).map(|id| (id.to_string(), score)))
+            .collect()
+    }
+
+    fn set_external_id(&mut self, doc_id: usize, external_id: &str) {
+        if let Some(previous) = self.external_ids.insert(doc_id, external_id.to_string()) {
+            self.doc_ids_by_external.remove(&previous);
+        }
+        if let Some(stale) = self.doc_ids_by_external.insert(external_id.to_string(), doc_id)
+            && stale != doc_id
+        {
+            self.external_ids.remove(&stale);
+        }
+    }
+
+    /// Drop a document's postings and statistics, leaving a tombstone.
+    /// Returns false if the doc_id was not indexed.
+    fn unindex_document(&mut self, doc_id: usize) -> bool {
+        if !self.contains_document(doc_id) {
+            return false;
+        }
 
-            // Get document frequency
-            let df = self.doc_frequencies.get(term).copied().unwrap_or(0) as f32;
-            if df == 0.0 {
+        let terms = self.doc_terms().remove(&doc_id).unwrap_or_default();
+        for term in &terms {
+            let Some(postings) = self.inverted_index.get_mut(term) else {
                 continue;
+            };
+            let before = postings.len();
+            postings.retain(|(id, _)| *id != doc_id);
+            if postings.len() == before {
+                continue;
+            }
+            if postings.is_empty() {
+                self.inverted_index.remove(term);
+            }
+            if let Some(df) = self.doc_frequencies.get_mut(term) {
+                *df -= 1;
+                if *df == 0 {
+                    self.doc_frequencies.remove(term);
+                }
+            }
+            if let Some(docs) = self.token_to_docs.get_mut(term) {
+                docs.remove(&doc_id);
+                if docs.is_empty() {
+                    self.token_to_docs.remove(term);
+                }
+            }
+            if let Some(docs) = self.term_positions.get_mut(term) {
+                docs.remove(&doc_id);
+                if docs.is_empty() {
+                    self.term_positions.remove(term);
+                }
+            }
+        }
+
+        if let Some(doc) = self.fielded_docs.remove(&doc_id) {
+            for (field, length) in doc.lengths {
+                if let Some(total) = self.field_length_totals.get_mut(&field) {
+                    *total -= length;
+                    if *total == 0 {
+                        self.field_length_totals.remove(&field);
+                    }
+                }
             }
+        }
+
+        let total_tokens = self.total_tokens() - self.doc_lengths[doc_id];
+        self.total_tokens = Some(total_tokens);
+        self.doc_lengths[doc_id] = 0;
+        self.tombstones.insert(doc_id);
+        self.total_docs -= 1;
+        self.refresh_corpus_stats();
+        true
+    }
+
+    /// Whether `doc_id` currently holds a live (indexed, not removed) document
+    pub fn contains_document(&self, doc_id: usize) -> bool {
+        doc_id < self.doc_lengths.len() && !self.tombstones.contains(&doc_id)
+    }
+
+    /// Number of documents containing `term`. The word is analyzed like query
+    /// text (see `analyze`), so callers pass it unstemmed; if it yields several
+    /// terms only the first is looked up. 0 for unseen terms and stopwords
+    pub fn document_frequency(&self, term: &str) -> usize {
+        self.analyze(term)
+            .first()
+            .and_then(|term| self.doc_frequencies.get(term))
+            .copied()
+            .unwrap_or(0)
+    }
 
-            // Calculate IDF component
-            // Using log((N - df + 0.5) / (df + 0.5) + 1) for better scores
-            let idf = ((self.total_docs as f32 - df + 0.5) / (df + 0.5) + 1.0).ln();
+    /// `(doc_id, term_frequency)` postings of `term`, analyzed as in
+    /// `document_frequency`, in the order documents were added. None for
+    /// unseen terms and stopwords
+    pub fn postings(&self, term: &str) -> Option<&[(usize, usize)]> {
+        let term = self.analyze(term).into_iter().next()?;
+        self.inverted_index.get(&term).map(Vec::as_slice)
+    }
 
-            // Calculate normalized term frequency with saturation
-            let normalized_tf = (tf * (self.params.k1 + 1.0)) / (tf + self.params.k1 * length_norm);
+    /// IDF of `term`, analyzed as in `document_frequency`. An unseen term gets
+    /// the highest IDF the corpus allows, that of a document frequency of 0
+    pub fn idf(&self, term: &str) -> f32 {
+        self.idf_for_df(self.document_frequency(term) as f32)
+    }
 
-            score += idf * normalized_tf;
+    /// Corpus statistics for tuning and monitoring
+    pub fn stats(&self) -> IndexStats {
+        let mut terms: Vec<(&String, &usize)> = self.doc_frequencies.iter().collect();
+        let by_frequency = |a: &(&String, &usize), b: &(&String, &usize)| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0));
+        if terms.len() > STATS_TOP_TERMS {
+            terms.select_nth_unstable_by(STATS_TOP_TERMS, by_frequency);
+            terms.truncate(STATS_TOP_TERMS);
         }
+        terms.sort_unstable_by(by_frequency);
 
-        score
+        IndexStats {
+            total_docs: self.total_docs,
+            avg_doc_length: self.avg_doc_length,
+            vocabulary_size: self.doc_frequencies.len(),
+            total_postings: self.inverted_index.values().map(Vec::len).sum(),
+            top_terms: terms.into_iter().map(|(term, &df)| (term.clone(), df)).collect(),
+            #[cfg(feature = "lru")]
+            cache_hits: self.query_cache.hits(),
+            #[cfg(feature = "lru")]
+            cache_misses: self.query_cache.misses(),
+            #[cfg(not(feature = "lru"))]
+            cache_hits: 0,
+            #[cfg(not(feature = "lru"))]
+            cache_misses: 0,
+        }
     }
 
-    fn get_term_frequency(&self, term: &str, doc_id: usize) -> usize {
-        self.inverted_index
-            .get(term)
-            .and_then(|postings| {
-                postings
-                    .iter()
-                    .find(|(id, _)| *id == doc_id)
-                    .map(|(_, freq)| *freq)
+    /// Sum of `doc_lengths`, computed once if the index was deserialized
+    fn total_tokens(&mut self) -> usize {
+        *self.total_tokens.get_or_insert_with(|| self.doc_lengths.iter().sum())
+    }
+
+    /// The forward index, rebuilt from the postings if the index was deserialized
+    fn doc_terms(&mut self) -> &mut HashMap<usize, Vec<String>> {
+        let inverted_index = &self.inverted_index;
+        self.doc_terms.get_or_insert_with(|| {
+            let mut doc_terms: HashMap<usize, Vec<String>> = HashMap::new();
+            for (term, postings) in inverted_index {
+                for &(doc_id, _) in postings {
+                    doc_terms.entry(doc_id).or_default().push(term.clone());
+                }
+            }
+            doc_terms
+        })
+    }
+
+    /// Recompute corpus-level statistics after documents were added or removed
+    fn refresh_corpus_stats(&mut self) {
+        self.idf_cache = OnceLock::new();
+        self.clear_query_cache();
+        self.fuzzy_index = OnceLock::new();
+        self.avg_doc_length = if self.total_docs == 0 {
+            0.0
+        } else {
+            self.total_tokens() as f32 / self.total_docs as f32
+        };
+    }
+
+    /// Search for `query`. Text in double quotes is matched as an exact
+    /// phrase, e.g. `"machine learning" tutorial`; other terms score normally.
+    /// A `term^weight` suffix scales that term's contribution, e.g. `rust^2 async`.
+    /// A `limit` of 0 returns every matching document.
+    pub fn search(&self, query: &str, limit: usize) -> Vec<(usize, f32)> {
+        // Keyed on the parsed query, which a miss parses again while scoring;
+        // parsing is cheap next to scoring
+        #[cfg(feature = "lru")]
+        return self
+            .query_cache
+            .get_or_insert(self.parse_query(query), limit, || self.search_with_min_score(query, limit, None));
+        #[cfg(not(feature = "lru"))]
+        self.search_with_min_score(query, limit, None)
+    }
+
+    /// Like `search`, but drops documents scoring below `min_score` before
+    /// taking the top `limit`, so fewer than `limit` may come back.
+    /// `None` behaves like `search`.
+    pub fn search_with_min_score(&self, query: &str, limit: usize, min_score: Option<f32>) -> Vec<(usize, f32)> {
+        self.search_where(query, limit, |_| true, |_, score| min_score.is_none_or(|threshold| score >= threshold))
+    }
+
+    /// Like `search`, but also returns how many documents matched (had a
+    /// positive score) before truncating to `limit`, e.g. for "10 of N" displays
+    pub fn search_with_count(&self, query: &str, limit: usize) -> (Vec<(usize, f32)>, usize) {
+        let scores = self.score_query(query, |_| true);
+        let total = scores.len();
+        (top_k(scores, limit), total)
+    }
+
+    /// Like `search`, but with the query terms that matched each result, e.g.
+    /// for faceting. Terms are listed in query order as analyzed (lowercased,
+    /// and stemmed if enabled); a matching phrase contributes all its words.
+    pub fn search_with_matches(&self, query: &str, limit: usize) -> Vec<(usize, f32, Vec<String>)> {
+        let parsed = self.parse_query(query);
+        top_k(self.score_query(query, |_| true), limit)
+            .into_iter()
+            .map(|(doc_id, score)| {
+                let mut matched: Vec<String> = Vec::new();
+                self.visit_contributions(&parsed, doc_id, |part, _| {
+                    let words: Vec<&str> = match part {
+                        QueryPart::Term(term) => vec![term],
+                        QueryPart::Phrase(phrase) => phrase.iter().map(String::as_str).collect(),
+                    };
+                    for word in words {
+                        if !matched.iter().any(|m| m == word) {
+                            matched.push(word.to_string());
+                        }
+                    }
+                });
+                (doc_id, score, matched)
             })
-            .unwrap_or(0)
+            .collect()
     }
-}
 
-fn tokenize(text: &str) -> Vec<String> {
-    text.to_lowercase()
-        .split(|c: char| !c.is_alphanumeric())
-        .filter(|s| !s.is_empty() && s.len() >= 2)
-        .map(|s| s.to_string())
-        .collect()
-}
\ No newline at end of file
+    /// Documents similar to `doc_id`: its `num_terms` highest tf-idf terms
+    /// are searched as a query, and the document itself is left out of the
+    /// results. Scans the whole vocabulary to find the document's terms.
+    /// Empty if `doc_id` isn't a live document.
+    pub fn more_like_this(&self, doc_id: usize, num_terms: usize, limit: usize) -> Vec<(usize, f32)> {
+        if !self.contains_document(doc_id) || num_terms == 0 {
+            return Vec::new();
+        }
+
+        let mut weighted: Vec<(&String, f32)> = self
+            .token_to_docs
+            .iter()
+            .filter(|(_, docs)| docs.contains(&doc_id))
+            .filter_map(|(term, _)| {
+                let tf = self.get_term_frequency(term, doc_id) as f32;
+                Some((term, tf * self.term_idf(term)?))
+            })
+            .collect();
+        weighted.sort_by(|a, b| cmp_score(b.1, a.1).then_with(|| a.0.cmp(b.0)));
+        weighted.truncate(num_terms);
+
+        // Terms are already analyzed, so build the query directly rather than
+        // running them through `parse_query` again
+        let parsed = ParsedQuery {
+            terms: weighted.into_iter().map(|(term, _)| (term.clone(), 1.0)).collect(),
+            phrases: Vec::new(),
+        };
+        let candidates = self.candidate_docs(parsed.terms.iter().map(|(term, _)| term));
+        let scores = score_candidates(candidates, |candidate| self.calculate_score(&parsed, candidate));
+        top_k(scores.into_iter().filter(|&(candidate, _)| candidate != doc_id), limit)
+    }
+
+    /// Like `search`, but only scores documents whose doc_id is in `allowed`,
+    /// e.g. chunks matching a `MetadataFilter`: other candidates are dropped
+    /// before scoring. `limit` applies after filtering.
+    pub fn search_filtered(&self, query: &str, limit: usize, allowed: &HashSet<usize>) -> Vec<(usize, f32)> {
+        if allowed.is_empty() {
+            return Vec::new();
+        }
+        self.search_where(query, limit, |doc_id| allowed.contains(&doc_id), |_, _| true)
+    }
+
+    /// Search with flat boolean operators, e.g. `rust AND async NOT javascript`.
+    ///
+    /// Terms joined by `AND` must all appear in a document, terms after `NOT`
+    /// must not, and every other term is optional (`OR` is the default).
+    /// All non-negated terms contribute to the BM25 score. Operators must be
+    /// uppercase; parentheses aren't supported. Errors on malformed queries,
+    /// such as a dangling operator or a query with only negated terms.
+    pub fn search_boolean(&self, query: &str, limit: usize) -> Result<Vec<(usize, f32)>> {
+        let boolean = parse_boolean_query(query)?;
+
+        let analyze_words = |words: &[&str]| -> Vec<String> {
+            words.iter().flat_map(|word| self.analyze(parse_boost(word).0)).collect()
+        };
+        let required = analyze_words(&boolean.required);
+        let excluded = analyze_words(&boolean.excluded);
+        let has_term = |term: &String, doc_id: usize| {
+            self.token_to_docs.get(term).is_some_and(|docs| docs.contains(&doc_id))
+        };
+
+        // Candidates failing the AND/NOT constraints are pruned before scoring
+        let allow = |doc_id: usize| {
+            required.iter().all(|term| has_term(term, doc_id))
+                && !excluded.iter().any(|term| has_term(term, doc_id))
+        };
+        Ok(self.search_where(&boolean.scored.join(" "), limit, allow, |_, _| true))
+    }
+
+    /// All matching documents for `query`, best first, produced lazily.
+    ///
+    /// Every candidate is scored up front, but ordering is done with a heap
+    /// that is popped on demand, so consuming the first few items is cheap and
+    /// no limit needs to be chosen ahead of time. Holds all scored candidates
+    /// in memory until dropped; `search` is leaner when the limit is known.
+    pub fn search_iter(&self, query: &str) -> impl Iterator<Item = (usize, f32)> {
+        ranked(self.score_query(query, |_| true))
+    }
+
+    /// Score the candidates for `query` accepted by `allow`, and return the
+    /// best `limit` of them whose score passes `keep`
+    fn search_where(
+        &self,
+        query: &str,
+        limit: usize,
+        allow: impl Fn(usize) -> bool,
+        keep: impl Fn(usize, f32) -> bool,
+    ) -> Vec<(usize, f32)> {
+        let span = span!("bm25_search", matches, results);
+        let scores = self.score_query(query, allow);
+        span.record("matches", scores.len());
+
+        let results = {
+            let _span = span!("bm25_rank");
+            top_k(scores.into_iter().filter(|&(doc_id, score)| keep(doc_id, score)), limit)
+        };
+        span.record("results", results.len());
+        results
+    }
+
+    /// Every candidate document for `query` accepted by `allow` with a
+    /// positive score, unordered. Rejected candidates are never scored
+    fn score_query(&self, query: &str, allow: impl Fn(usize) -> bool) -> Vec<(usize, f32)> {
+        if self.total_docs == 0 {
+            return Vec::new();
+        }
+
+        let parsed = {
+            let _span = span!("bm25_parse");
+            self.parse_query(query)
+        };
+        if parsed.terms.is_empty() && parsed.phrases.is_empty() {
+            return Vec::new();
+        }
+
+        let candidates = {
+            let span = span!("bm25_candidates", candidates);
+            let mut candidates = self.candidate_docs(
+                parsed.terms.iter().map(|(term, _)| term).chain(parsed.phrases.iter().flatten()),
+            );
+            candidates.retain(|&doc_id| allow(doc_id));
+            span.record("candidates", candidates.len());
+            candidates
+        };
+        let _span = span!("bm25_score");
+        score_candidates(candidates, |doc_id| self.calculate_score(&parsed, doc_id))
+    }
+
+    /// Search for documents containing `phrase` as consecutive tokens
+    pub fn search_phrase(&self, phrase: &str, limit: usize) -> Vec<(usize, f32)> {
+        let tokens = self.analyze(phrase);
+        if self.total_docs == 0 || tokens.is_empty() {
+            return Vec::new();
+        }
+
+        let mut term_docs = Vec::with_capacity(tokens.len());
+        for term in &tokens {
+            match self.token_to_docs.get(term) {
+                Some(docs) => term_docs.push(docs),
+                None => return Vec::new(),
+            }
+        }
+        term_docs.sort_by_key(|docs| docs.len());
+        let candidates = term_docs[0]
+            .iter()
+            .filter(|doc_id| term_docs[1..].iter().all(|docs| docs.contains(doc_id)));
+
+        let scores = candidates
+            .filter_map(|&doc_id| {
+                self.phrase_score(&tokens, doc_id)
+                    .map(|score| (doc_id, score.contribution))
+            })
+            .filter(|(_, score)| *score > 0.0);
+
+        top_k(scores, limit)
+    }
+
+    /// Documents containing at least one of `terms`, gathered from the
+    /// inverted index so documents sharing no query term are never scored.
+    /// Sorted by doc_id, so scores come out in the same order on every run
+    fn candidate_docs<'a>(&self, terms: impl Iterator<Item = &'a String>) -> Vec<usize> {
+        let mut candidates = Vec::new();
+        for term in terms {
+            if let Some(docs) = self.token_to_docs.get(term) {
+                candidates.extend(docs.iter().copied());
+            }
+        }
+        candidates.sort_unstable();
+        candidates.dedup();
+        candidates
+    }
+
+    /// Split a query into free terms and quoted phrases. An unmatched quote
+    /// is ignored and the text after it is treated as free terms.
+    fn parse_query(&self, query: &str) -> ParsedQuery {
+        let mut parsed = ParsedQuery::default();
+        for (segment, quoted) in split_quoted(query) {
+            if quoted {
+                let tokens = self.analyze(segment);
+                if tokens.len() > 1 {
+                    parsed.phrases.push(tokens);
+                } else {
+                    parsed.terms.extend(tokens.into_iter().map(|t| (t, 1.0)));
+                }
+            } else if segment.contains('^') {
+                for word in segment.split_whitespace() {
+                    let (text, boost) = parse_boost(word);
+                    parsed.terms.extend(self.analyze(text).into_iter().map(|t| (t, boost)));
+                }
+            } else {
+                parsed.terms.extend(self.analyze(segment).into_iter().map(|t| (t, 1.0)));
+            }
+        }
+
+        if self.params.max_edit_distance > 0 {
+            let fuzzy: Vec<(String, f32)> = parsed
+                .terms
+                .iter()
+                .filter(|(term, _)| !self.doc_frequencies.contains_key(term))
+                .flat_map(|(term, boost)| {
+                    self.fuzzy_matches(term)
+                        .into_iter()
+                        .map(move |(matched, distance)| (matched, boost / (1.0 + distance as f32)))
+                })
+                .collect();
+            parsed.terms.extend(fuzzy);
+        }
+        parsed
+    }
+
+    /// Indexed terms within `max_edit_distance` of `term`, closest first
+    fn fuzzy_matches(&self, term: &str) -> Vec<(String, usize)> {
+        let max_distance = self.params.max_edit_distance;
+        let Some(first) = term.chars().next() else {
+            return Vec::new();
+        };
+
+        let index = self.fuzzy_index.get_or_init(|| {
+            let mut index: HashMap<char, Vec<String>> = HashMap::new();
+            for term in self.doc_frequencies.keys() {
+                if let Some(c) = term.chars().next() {
+                    index.entry(c).or_default().push(term.clone());
+                }
+            }
+            index
+        });
+
+        let term_len = term.chars().count();
+        let mut matches: Vec<(String, usize)> = index
+            .get(&first)
+            .into_iter()
+            .flatten()
+            .filter(|candidate| candidate.chars().count().abs_diff(term_len) <= max_distance)
+            .filter_map(|candidate| {
+                let distance = edit_distance(term, candidate);
+                (distance <= max_distance).then(|| (candidate.clone(), distance))
+            })
+            .collect();
+
+        matches.sort_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.cmp(&b.0)));
+        matches.truncate(MAX_FUZZY_EXPANSIONS);
+        matches
+    }
+
+    /// Count the positions in `doc_id` where `phrase` occurs as consecutive tokens
+    fn phrase_frequency(&self, phrase: &[String], doc_id: usize) -> usize {
+        let mut term_positions = Vec::with_capacity(phrase.len());
+        for term in phrase {
+            match self.term_positions.get(term).and_then(|docs| docs.get(&doc_id)) {
+                Some(positions) => term_positions.push(positions),
+                None => return 0,
+            }
+        }
+
+        term_positions[0]
+            .iter()
+            .filter(|&&start| {
+                term_positions[1..]
+                    .iter()
+                    .enumerate()
+                    .all(|(offset, positions)| positions.binary_search(&(start + offset + 1)).is_ok())
+            })
+            .count()
+    }
+
+    /// Explain how `doc_id` scores against `query`: the IDF, term frequency,
+    /// length normalization and contribution of every matching term. The
+    /// total is computed by the same code as `search`, so it always equals
+    /// the ranked score.
+    pub fn explain(&self, query: &str, doc_id: usize) -> ScoreExplanation {
+        let mut explanation = ScoreExplanation {
+            doc_id,
+            score: 0.0,
+            terms: Vec::new(),
+        };
+        if !self.contains_document(doc_id) {
+            return explanation;
+        }
+
+        let parsed = self.parse_query(query);
+        self.visit_contributions(&parsed, doc_id, |part, score| {
+            explanation.score += score.contribution;
+            explanation.terms.push(TermExplanation {
+                term: match part {
+                    QueryPart::Term(term) => term.to_string(),
+                    QueryPart::Phrase(phrase) => format!("\"{}\"", phrase.join(" ")),
+                },
+                boost: score.boost,
+                term_frequency: score.term_frequency,
+                idf: score.idf,
+                length_norm: score.length_norm,
+                contribution: score.contribution,
+            });
+        });
+        explanation
+    }
+
+    /// Calculate BM25 score for a document given a parsed query. 0 for
+    /// removed and unknown doc_ids, which `score_candidates` then skips
+    fn calculate_score(&self, query: &ParsedQuery, doc_id: usize) -> f32 {
+        if !self.contains_document(doc_id) {
+            return 0.0;
+        }
+        let mut score = 0.0;
+        self.visit_contributions(query, doc_id, |_, part| score += part.contribution);
+        score
+    }
+
+    /// Call `visit` with the contribution of every query term and phrase that
+    /// matches `doc_id`. Both ranking and `explain` go through here.
+    fn visit_contributions(
+        &self,
+        query: &ParsedQuery,
+        doc_id: usize,
+        mut visit: impl FnMut(QueryPart<'_>, TermScore),
+    ) {
+        for (term, boost) in &query.terms {
+            if let Some(score) = self.term_score(term, *boost, doc_id) {
+                visit(QueryPart::Term(term), score);
+            }
+        }
+        for phrase in &query.phrases {
+            if let Some(score) = self.phrase_score(phrase, doc_id) {
+                visit(QueryPart::Phrase(phrase), score);
+            }
+        }
+    }
+
+    fn term_score(&self, term: &str, boost: f32, doc_id: usize) -> Option<TermScore> {
+        if let Some(doc) = self.fielded_docs.get(&doc_id) {
+            return self.field_term_score(term, boost, doc);
+        }
+
+        // Get term frequency in this document
+        let tf = self.get_term_frequency(term, doc_id) as f32;
+        if tf == 0.0 {
+            return None;
+        }
+        let idf = self.term_idf(term)?;
+
+        Some(self.saturate(boost, tf, idf, self.length_norm(doc_id)))
+    }
+
+    /// BM25F: per-field term frequencies are normalized by field length,
+    /// weighted by field boost and summed before a single saturation step
+    fn field_term_score(&self, term: &str, boost: f32, doc: &FieldedDoc) -> Option<TermScore> {
+        let field_tfs = doc.term_frequencies.get(term)?;
+        let idf = self.term_idf(term)?;
+
+        let tf: usize = field_tfs.values().sum();
+        let weighted_tf: f32 = field_tfs
+            .iter()
+            .map(|(field, &tf)| {
+                let field_length = doc.lengths.get(field).copied().unwrap_or(0) as f32;
+                let length_norm = self.normalize_length(field_length, self.avg_field_length(field));
+                self.params.field_weight(field) * tf as f32 / length_norm
+            })
+            .sum();
+        if weighted_tf <= 0.0 {
+            return None;
+        }
+
+        // The effective length norm tf / weighted_tf makes the classic formula
+        // in `saturate` reproduce the BM25F contribution
+        Some(self.saturate(boost, tf as f32, idf, tf as f32 / weighted_tf))
+    }
+
+    /// Score a phrase as a single pseudo-term: the phrase frequency is
+    /// saturated like a term frequency and weighted by the summed IDF of
+    /// its terms
+    fn phrase_score(&self, phrase: &[String], doc_id: usize) -> Option<TermScore> {
+        let phrase_freq = self.phrase_frequency(phrase, doc_id) as f32;
+        if phrase_freq == 0.0 {
+            return None;
+        }
+        let idf: f32 = phrase.iter().filter_map(|term| self.term_idf(term)).sum();
+
+        Some(self.saturate(1.0, phrase_freq, idf, self.length_norm(doc_id)))
+    }
+
+    /// Length normalization factor for a document, treating unknown doc_ids
+    /// as empty
+    fn length_norm(&self, doc_id: usize) -> f32 {
+        let length = self.doc_lengths.get(doc_id).copied().unwrap_or(0);
+        self.normalize_length(length as f32, self.avg_doc_length)
+    }
+
+    /// `1 - b + b * length / avg_length`. With `b == 0` normalization is
+    /// skipped, and an average of zero (nothing but empty documents) counts
+    /// as no normalization rather than dividing by zero.
+    fn normalize_length(&self, length: f32, avg_length: f32) -> f32 {
+        if self.params.b == 0.0 || avg_length <= 0.0 {
+            return 1.0;
+        }
+        1.0 - self.params.b + self.params.b * (length / avg_length)
+    }
+
+    /// Calculate normalized term frequency with saturation and weight it by IDF
+    fn saturate(&self, boost: f32, tf: f32, idf: f32, length_norm: f32) -> TermScore {
+        let normalized_tf = (tf * (self.params.k1 + 1.0)) / (tf + self.params.k1 * length_norm);
+        TermScore {
+            boost,
+            term_frequency: tf,
+            idf,
+            length_norm,
+            contribution: boost * idf * normalized_tf,
+        }
+    }
+
+    /// Average length of `field` across all fielded documents
+    fn avg_field_length(&self, field: &str) -> f32 {
+        let total = self.field_length_totals.get(field).copied().unwrap_or(0) as f32;
+        total / self.fielded_docs.len().max(1) as f32
+    }
+
+    /// Cached IDF of an indexed term, or None if no document contains it
+    fn term_idf(&self, term: &str) -> Option<f32> {
+        self.idf_cache
+            .get_or_init(|| {
+                self.doc_frequencies
+                    .iter()
+                    .map(|(term, &df)| (term.clone(), self.idf_for_df(df as f32)))
+                    .collect()
+            })
+            .get(term)
+            .copied()
+    }
+
+    /// Calculate the IDF component for a term with document frequency `df`
+    /// using the configured `IdfVariant`, floored at epsilon so terms present
+    /// in (nearly) every document never contribute a negative or vanishing score
+    fn idf_for_df(&self, df: f32) -> f32 {
+        let n = self.total_docs as f32;
+        let idf = match self.params.idf {
+            IdfVariant::Lucene => ((n - df + 0.5) / (df + 0.5) + 1.0).ln(),
+            IdfVariant::Classic => (n / df.max(1.0)).ln(),
+            IdfVariant::Probabilistic => ((n - df + 0.5) / (df + 0.5)).ln(),
+        };
+        idf.max(self.params.epsilon)
+    }
+
+    fn get_term_frequency(&self, term: &str, doc_id: usize) -> usize {
+        self.inverted_index
+            .get(term)
+            .and_then(|postings| {
+                postings
+                    .iter()
+                    .find(|(id, _)| *id == doc_id)
+                    .map(|(_, freq)| *freq)
+            })
+            .unwrap_or(0)
+    }
+}
+
+/// A query split into boosted free terms and exact phrases
+#[derive(Debug, Default, Clone)]
+struct ParsedQuery {
+    terms: Vec<(String, f32)>,
+    phrases: Vec<Vec<String>>,
+}
+
+impl ParsedQuery {
+    /// Terms with each boost's bits, which are exact for the finite
+    /// positive boosts `parse_query` produces
+    fn term_bits(&self) -> impl Iterator<Item = (&str, u32)> {
+        self.terms.iter().map(|(term, boost)| (term.as_str(), boost.to_bits()))
+    }
+}
+
+/// Compared by boost bits, so parsed queries can key the query cache
+impl PartialEq for ParsedQuery {
+    fn eq(&self, other: &Self) -> bool {
+        self.term_bits().eq(other.term_bits()) && self.phrases == other.phrases
+    }
+}
+
+impl Eq for ParsedQuery {}
+
+impl Hash for ParsedQuery {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.term_bits().for_each(|term| term.hash(state));
+        self.phrases.hash(state);
+    }
+}
+
+/// Wrap each word of `text` that matches one of `query_tokens` in `pre`/`post`,
+/// e.g. `highlight(text, &tokens, "<mark>", "</mark>")`.
+///
+/// Words are normalized like the default tokenizer (lowercased runs of
+/// alphanumeric characters), while the original casing and all surrounding
+/// text are kept verbatim.
+pub fn highlight(text: &str, query_tokens: &[String], pre: &str, post: &str) -> String {
+    let terms: HashSet<String> = query_tokens.iter().map(|t| t.to_lowercase()).collect();
+    let mut highlighted = String::with_capacity(text.len());
+    let mut word_start: Option<usize> = None;
+
+    let flush = |highlighted: &mut String, word: &str| {
+        if terms.contains(&word.to_lowercase()) {
+            highlighted.push_str(pre);
+            highlighted.push_str(word);
+            highlighted.push_str(post);
+        } else {
+            highlighted.push_str(word);
+        }
+    };
+
+    for (i, c) in text.char_indices() {
+        if c.is_alphanumeric() {
+            word_start.get_or_insert(i);
+        } else {
+            if let Some(start) = word_start.take() {
+                flush(&mut highlighted, &text[start..i]);
+            }
+            highlighted.push(c);
+        }
+    }
+    if let Some(start) = word_start {
+        flush(&mut highlighted, &text[start..]);
+    }
+
+    highlighted
+}
+
+/// The run of `window` whitespace-separated words of `text` containing the
+/// most words that match `query_tokens`, for showing a short snippet instead
+/// of a whole chunk. The earliest window wins ties, so with no matches the
+/// snippet is the start of the text.
+///
+/// Words match as in `highlight`. The words are joined by single spaces, with
+/// `...` marking text cut off before or after the window.
+pub fn extract_snippet(text: &str, query_tokens: &[String], window: usize) -> String {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    if window == 0 || words.is_empty() {
+        return String::new();
+    }
+    let window = window.min(words.len());
+
+    let terms: HashSet<String> = query_tokens.iter().map(|t| t.to_lowercase()).collect();
+    let hits: Vec<usize> = words
+        .iter()
+        .map(|word| {
+            word.split(|c: char| !c.is_alphanumeric())
+                .any(|part| !part.is_empty() && terms.contains(&part.to_lowercase())) as usize
+        })
+        .collect();
+
+    let mut count: usize = hits[..window].iter().sum();
+    let (mut best_start, mut best_count) = (0, count);
+    for start in 1..=words.len() - window {
+        count = count + hits[start + window - 1] - hits[start - 1];
+        if count > best_count {
+            best_start = start;
+            best_count = count;
+        }
+    }
+
+    let end = best_start + window;
+    let mut snippet = String::new();
+    if best_start > 0 {
+        snippet.push_str("... ");
+    }
+    snippet.push_str(&words[best_start..end].join(" "));
+    if end < words.len() {
+        snippet.push_str(" ...");
+    }
+    snippet
+}
+
+/// Score every candidate document, keeping those with a positive score
+#[cfg(not(feature = "rayon"))]
+fn score_candidates(candidates: Vec<usize>, score: impl Fn(usize) -> f32) -> Vec<(usize, f32)> {
+    candidates
+        .into_iter()
+        .map(|doc_id| (doc_id, score(doc_id)))
+        .filter(|(_, score)| *score > 0.0)
+        .collect()
+}
+
+/// Score every candidate document in parallel, keeping those with a positive score
+#[cfg(feature = "rayon")]
+fn score_candidates(candidates: Vec<usize>, score: impl Fn(usize) -> f32 + Sync) -> Vec<(usize, f32)> {
+    use rayon::prelude::*;
+
+    candidates
+        .into_par_iter()
+        .map(|doc_id| (doc_id, score(doc_id)))
+        .filter(|(_, score)| *score > 0.0)
+        .collect()
+}
+
+/// The part of a query a score contribution came from
+#[derive(Debug, Clone, Copy)]
+enum QueryPart<'a> {
+    Term(&'a str),
+    Phrase(&'a [String]),
+}
+
+/// Components of one term's contribution to a document score
+#[derive(Debug, Clone, Copy)]
+struct TermScore {
+    boost: f32,
+    term_frequency: f32,
+    idf: f32,
+    length_norm: f32,
+    contribution: f32,
+}
+
+/// Split a query at double quotes into `(segment, quoted)` pairs. An
+/// unmatched quote is ignored and the text after it is unquoted.
+pub(crate) fn split_quoted(query: &str) -> impl Iterator<Item = (&str, bool)> {
+    let segments: Vec<&str> = query.split('"').collect();
+    let count = segments.len();
+    segments
+        .into_iter()
+        .enumerate()
+        .map(move |(i, segment)| (segment, i % 2 == 1 && i + 1 < count))
+}
+
+/// Split a `term^weight` word into its text and boost. Malformed boosts
+/// (`rust^`, `rust^abc`, `rust^-1`) leave the word as literal text with weight 1.0.
+pub(crate) fn parse_boost(word: &str) -> (&str, f32) {
+    if let Some((text, weight)) = word.rsplit_once('^')
+        && !text.is_empty()
+        && let Ok(boost) = weight.parse::<f32>()
+        && boost.is_finite()
+        && boost > 0.0
+    {
+        return (text, boost);
+    }
+    (word, 1.0)
+}
+
+/// Words of a boolean query, split by role
+#[derive(Debug, Default)]
+struct BooleanQuery<'a> {
+    /// Every non-negated word, in query order
+    scored: Vec<&'a str>,
+    /// Words joined by `AND`; also in `scored`
+    required: Vec<&'a str>,
+    /// Words following `NOT`
+    excluded: Vec<&'a str>,
+}
+
+fn parse_boolean_query(query: &str) -> Result<BooleanQuery<'_>> {
+    let mut parsed = BooleanQuery::default();
+    // The operator waiting for its right-hand term, if any
+    let mut pending: Option<&str> = None;
+    let mut negate = false;
+    let mut last_positive: Option<&str> = None;
+    let mut seen_term = false;
+
+    for word in query.split_whitespace() {
+        if word.contains(['(', ')']) {
+            return Err(anyhow::anyhow!("Parentheses are not supported in boolean queries"));
+        }
+        match word {
+            "AND" | "OR" => {
+                if !seen_term || pending.is_some() {
+                    return Err(anyhow::anyhow!("'{}' must come between two terms", word));
+                }
+                if word == "AND"
+                    && let Some(term) = last_positive
+                    && !parsed.required.contains(&term)
+                {
+                    parsed.required.push(term);
+                }
+                pending = Some(word);
+            }
+            "NOT" => {
+                if negate {
+                    return Err(anyhow::anyhow!("'NOT' must be followed by a term"));
+                }
+                negate = true;
+                pending = Some(word);
+            }
+            term => {
+                if negate {
+                    parsed.excluded.push(term);
+                    last_positive = None;
+                } else {
+                    parsed.scored.push(term);
+                    if pending == Some("AND") && !parsed.required.contains(&term) {
+                        parsed.required.push(term);
+                    }
+                    last_positive = Some(term);
+                }
+                negate = false;
+                pending = None;
+                seen_term = true;
+            }
+        }
+    }
+
+    if let Some(operator) = pending {
+        return Err(anyhow::anyhow!("Query ends with '{}' but no term follows it", operator));
+    }
+    if parsed.scored.is_empty() {
+        return Err(anyhow::anyhow!("Boolean query needs at least one term that isn't negated"));
+    }
+    Ok(parsed)
+}
+
+/// Levenshtein distance between two strings, counted in chars
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+
+    for (i, ca) in a.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+/// Per-field statistics for a document indexed with `add_document_fields`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct FieldedDoc {
+    /// Token count per field
+    lengths: HashMap<String, usize>,
+
+    /// term -> field -> term frequency. Fields are ordered so BM25F sums
+    /// them in the same order on every run
+    term_frequencies: HashMap<String, BTreeMap<String, usize>>,
+}
+
+/// Append `value` as LEB128: 7 bits per byte, high bit set on all but the last
+fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+/// Cursor over a byte buffer written with `write_varint`
+struct VarintReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> VarintReader<'a> {
+    fn varint(&mut self) -> Result<u64> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = *self.bytes.get(self.pos).ok_or_else(|| anyhow::anyhow!("Unexpected end of data"))?;
+            self.pos += 1;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(anyhow::anyhow!("Variable-length integer is too long"))
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self.pos.checked_add(len).filter(|&end| end <= self.bytes.len());
+        let end = end.ok_or_else(|| anyhow::anyhow!("Unexpected end of data"))?;
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn remaining(&self) -> usize {
+        self.bytes.len() - self.pos
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tokenizer::NgramTokenizer;
+    use crate::types::{ChunkContent, ChunkMetadata, TextChunk};
+    use std::path::PathBuf;
+
+    fn index_of(docs: &[&str]) -> Bm25Index {
+        let mut index = Bm25Index::new();
+        for (doc_id, text) in docs.iter().enumerate() {
+            index.add_document(doc_id, text);
+        }
+        index
+    }
+
+    fn ids(results: &[(usize, f32)]) -> Vec<usize> {
+        results.iter().map(|&(doc_id, _)| doc_id).collect()
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("edgerag-bm25-{}-{}", std::process::id(), name))
+    }
+
+    /// Every live document's score in doc_id order, computed one by one
+    /// through `explain` instead of from the inverted-index candidates
+    fn scan_all(index: &Bm25Index, query: &str) -> Vec<(usize, f32)> {
+        (0..index.doc_lengths.len())
+            .map(|doc_id| (doc_id, index.explain(query, doc_id).score))
+            .filter(|&(_, score)| score > 0.0)
+            .collect()
+    }
+
+    fn text_chunk(id: &str, text: &str) -> Chunk {
+        Chunk {
+            id: id.to_string(),
+            content: ChunkContent::Text {
+                text: TextChunk {
+                    id: id.to_string(),
+                    html: None,
+                    markdown: None,
+                    text: text.to_string(),
+                },
+            },
+            metadata: ChunkMetadata::default(),
+            enrichment: None,
+        }
+    }
+
+    const CORPUS: &[&str] = &[
+        "rust is a systems programming language",
+        "python is a popular scripting language",
+        "async rust makes concurrent programming easier",
+        "javascript runs in the browser",
+        "machine learning with python and rust",
+    ];
+
+    #[test]
+    fn test_add_document_and_search() {
+        let index = index_of(CORPUS);
+        let results = index.search("rust", 10);
+        assert_eq!(ids(&results).len(), 3);
+        assert!(ids(&results).iter().all(|doc_id| [0, 2, 4].contains(doc_id)));
+        assert!(results.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+        assert_eq!(index.search("rust", 2).len(), 2);
+        assert!(index.search("haskell", 10).is_empty());
+        assert!(Bm25Index::new().search("rust", 10).is_empty());
+    }
+
+    #[test]
+    fn test_added_documents_update_corpus_stats() {
+        let mut index = index_of(&["apple banana", "apple cherry date"]);
+        assert_eq!((index.total_docs, index.avg_doc_length), (2, 2.5));
+        let before = index.search("cherry", 10)[0].1;
+
+        index.add_document(2, "apple");
+        assert_eq!((index.total_docs, index.avg_doc_length), (3, 2.0));
+        assert_eq!(index.doc_frequencies["apple"], 3);
+        // A rarer term relative to the corpus scores higher
+        assert!(index.search("cherry", 10)[0].1 > before);
+    }
+
+    #[test]
+    fn test_build_from_chunks() {
+        let chunks = vec![
+            text_chunk("a", "alpha beta"),
+            text_chunk("b", "gamma delta"),
+            text_chunk("c", "epsilon zeta"),
+        ];
+        let index = Bm25Index::build_from_chunks(&chunks);
+
+        assert_eq!(index.total_docs, 3);
+        assert_eq!(ids(&index.search("gamma", 10)), vec![1]);
+        assert_eq!(index.external_id(2), Some("c"));
+        assert!(Bm25Index::build_from_chunks(&[]).search("gamma", 10).is_empty());
+    }
+
+    #[test]
+    fn test_new_uses_default_params() {
+        let index = Bm25Index::new();
+        assert_eq!(index.params().k1, 1.2);
+        assert_eq!(index.params().b, 0.75);
+        assert_eq!(index.params().epsilon, 0.25);
+        assert!(index.search("anything", 10).is_empty());
+
+        let params = Bm25Params { k1: 2.0, ..Bm25Params::default() };
+        assert_eq!(Bm25Index::with_params(params).params().k1, 2.0);
+    }
+
+    #[test]
+    fn test_epsilon_floors_idf_of_ubiquitous_term() {
+        for idf in [IdfVariant::Lucene, IdfVariant::Classic, IdfVariant::Probabilistic] {
+            let params = Bm25Params { idf, ..Bm25Params::default() };
+            let mut index = Bm25Index::with_params(params);
+            for doc_id in 0..5 {
+                index.add_document(doc_id, &format!("common word{}", doc_id));
+            }
+            assert!(index.idf("common") >= 0.25, "{:?}", idf);
+            assert!(index.search("common", 10).iter().all(|&(_, score)| score > 0.0));
+        }
+    }
+
+    #[test]
+    fn test_params_change_scores() {
+        let docs = ["rust", "rust filler filler filler filler filler"];
+        let normalized = index_of(&docs).search("rust", 10);
+        assert!(normalized[0].1 > normalized[1].1);
+
+        let mut unnormalized = Bm25Index::with_params(Bm25Params { b: 0.0, ..Bm25Params::default() });
+        let mut saturated = Bm25Index::with_params(Bm25Params { k1: 0.1, ..Bm25Params::default() });
+        for (doc_id, text) in docs.iter().enumerate() {
+            unnormalized.add_document(doc_id, text);
+            saturated.add_document(doc_id, text);
+        }
+        let unnormalized = unnormalized.search("rust", 10);
+        assert_eq!(unnormalized[0].1, unnormalized[1].1);
+        // A tiny k1 all but ignores length and term frequency
+        let saturated = saturated.search("rust", 10);
+        assert!(saturated[0].1 - saturated[1].1 < normalized[0].1 - normalized[1].1);
+    }
+
+    #[test]
+    fn test_remove_document() {
+        let mut index = index_of(CORPUS);
+        assert!(index.remove_document(4));
+        assert!(!index.remove_document(4));
+        assert!(!index.remove_document(99));
+        assert!(!index.contains_document(4));
+        assert!(!ids(&index.search("rust", 10)).contains(&4));
+        assert_eq!(index.total_docs, 4);
+
+        // Other documents keep their doc_ids
+        assert_eq!(ids(&index.search("python", 10)), vec![1]);
+
+        index.add_document(6, "rust again");
+        assert_eq!(index.total_docs, 5);
+        assert!(!index.contains_document(4));
+        assert!(!index.contains_document(5));
+    }
+
+    #[test]
+    fn test_add_document_replaces_existing() {
+        let mut index = index_of(CORPUS);
+        index.add_document(0, "haskell is lazy");
+        assert_eq!(index.total_docs, 5);
+        assert_eq!(ids(&index.search("haskell", 10)), vec![0]);
+        assert!(!ids(&index.search("systems", 10)).contains(&0));
+    }
+
+    #[test]
+    fn test_removal_does_not_change_unrelated_scores() {
+        let mut index = index_of(&["apple banana", "apple cherry", "zebra"]);
+        let reference = index_of(&["apple banana", "apple cherry"]);
+        index.remove_document(2);
+        // Both docs tie, so compare in doc_id order
+        let mut results = index.search("apple", 10);
+        results.sort_by_key(|&(doc_id, _)| doc_id);
+        let mut expected = reference.search("apple", 10);
+        expected.sort_by_key(|&(doc_id, _)| doc_id);
+        assert_eq!(results, expected);
+        assert!(!index.doc_frequencies.contains_key("zebra"));
+        assert!(!index.inverted_index.contains_key("zebra"));
+    }
+
+    #[test]
+    fn test_stopwords() {
+        let mut filtered = Bm25Index::new();
+        filtered.set_stopword_filter(StopwordFilter::english());
+        let mut unfiltered = Bm25Index::new();
+        for (doc_id, text) in CORPUS.iter().enumerate() {
+            filtered.add_document(doc_id, text);
+            unfiltered.add_document(doc_id, text);
+        }
+
+        assert!(filtered.inverted_index.len() < unfiltered.inverted_index.len());
+        assert!(!filtered.inverted_index.contains_key("the"));
+        assert!(filtered.search("the is a", 10).is_empty());
+        assert!(!unfiltered.search("the is", 10).is_empty());
+
+        let mut custom = Bm25Index::new();
+        custom.set_stopwords(HashSet::from(["Rust".to_string()]));
+        custom.add_document(0, "rust language");
+        assert_eq!(custom.analyze("Rust language"), vec!["language"]);
+        assert!(custom.search("rust", 10).is_empty());
+    }
+
+    #[test]
+    fn test_stopword_languages() {
+        let mut german = Bm25Index::new();
+        german.set_stopword_languages(&[Language::German]);
+        assert_eq!(german.analyze("der Hund"), vec!["hund"]);
+
+        let mut english = Bm25Index::new();
+        english.set_stopword_languages(&[Language::English]);
+        assert_eq!(english.analyze("der Hund"), vec!["der", "hund"]);
+
+        let mut mixed = Bm25Index::new();
+        mixed.set_stopword_languages(&[Language::English, Language::German]);
+        assert_eq!(mixed.analyze("the der Hund"), vec!["hund"]);
+
+        // No stopwords until a language is set
+        assert_eq!(Bm25Index::new().analyze("the Hund"), vec!["the", "hund"]);
+    }
+
+    struct WhitespaceTokenizer;
+
+    impl Tokenizer for WhitespaceTokenizer {
+        fn tokenize(&self, text: &str) -> Vec<String> {
+            text.split_whitespace().map(str::to_string).collect()
+        }
+    }
+
+    #[test]
+    fn test_custom_tokenizer() {
+        let mut index = Bm25Index::new();
+        index.set_tokenizer(WhitespaceTokenizer);
+        index.add_document(0, "state-of-the-art Rust");
+        index.add_document(1, "state of the art rust");
+
+        assert_eq!(ids(&index.search("state-of-the-art", 10)), vec![0]);
+        assert_eq!(ids(&index.search("Rust", 10)), vec![0]);
+
+        // The tokenizer is skipped when serializing, so loading falls back
+        // to the default one
+        let json = serde_json::to_string(&index).unwrap();
+        let loaded: Bm25Index = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.analyze("Rust"), vec!["rust"]);
+    }
+
+    #[test]
+    fn test_ngram_tokenizer_matches_partial_words() {
+        let mut index = Bm25Index::new();
+        index.set_tokenizer(NgramTokenizer::default());
+        index.add_document(0, "searching the archive");
+        index.add_document(1, "browsing the library");
+        assert_eq!(ids(&index.search("search", 10)), vec![0]);
+    }
+
+    #[test]
+    fn test_stemming() {
+        let params = Bm25Params { stemming: true, ..Bm25Params::default() };
+        let mut index = Bm25Index::with_params(params);
+        index.add_document(0, "she runs every morning");
+        index.add_document(1, "the cat sleeps");
+
+        assert_eq!(ids(&index.search("running", 10)), vec![0]);
+        assert_eq!(ids(&index.search("run", 10)), vec![0]);
+        // Short tokens never stem to nothing
+        assert!(index.analyze("as is").iter().all(|token| !token.is_empty()));
+
+        let err = index.set_params(Bm25Params::default()).unwrap_err();
+        assert!(err.to_string().contains("stemming"));
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let mut index = index_of(CORPUS);
+        index.add_document_with_id("extra", "rust and python together");
+        let path = temp_path("roundtrip");
+        index.save(&path).unwrap();
+        let loaded = Bm25Index::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        // Tied scores may come back in either order
+        let sorted = |mut results: Vec<(usize, f32)>| {
+            results.sort_by_key(|&(doc_id, _)| doc_id);
+            results
+        };
+        for query in ["rust", "python language", "async"] {
+            assert_eq!(sorted(index.search(query, 10)), sorted(loaded.search(query, 10)));
+        }
+        assert_eq!(loaded.search_by_id("together", 10)[0].0, "extra");
+        assert_eq!(loaded.doc_id_for("extra"), Some(5));
+    }
+
+    #[test]
+    fn test_load_rejects_bad_files() {
+        let path = temp_path("bad");
+        std::fs::write(&path, b"not an index").unwrap();
+        assert!(Bm25Index::load(&path).is_err());
+
+        let mut bytes = FILE_MAGIC.to_vec();
+        bytes.extend_from_slice(&(FILE_FORMAT_VERSION + 1).to_le_bytes());
+        std::fs::write(&path, &bytes).unwrap();
+        assert!(Bm25Index::load(&path).unwrap_err().to_string().contains("version"));
+
+        index_of(CORPUS).save(&path).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::write(&path, &bytes[..bytes.len() / 2]).unwrap();
+        assert!(Bm25Index::load(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(Bm25Index::load(temp_path("missing")).is_err());
+    }
+
+    #[test]
+    fn test_deserialize_without_total_tokens() {
+        let mut index = index_of(CORPUS);
+        let json = serde_json::to_string(&index).unwrap();
+        assert!(!json.contains("total_tokens"));
+
+        let mut loaded: Bm25Index = serde_json::from_str(&json).unwrap();
+        loaded.remove_document(0);
+        index.remove_document(0);
+        assert_eq!(loaded.stats().avg_doc_length, index.stats().avg_doc_length);
+        loaded.add_document(6, "one two three");
+        index.add_document(6, "one two three");
+        assert_eq!(loaded.stats().avg_doc_length, index.stats().avg_doc_length);
+    }
+
+    #[test]
+    fn test_phrase_search() {
+        let index = index_of(&[
+            "machine learning is fun",
+            "learning about the machine",
+            "the machine is learning fast and machine learning",
+        ]);
+        let results = index.search_phrase("machine learning", 10);
+        assert_eq!(ids(&results).len(), 2);
+        assert!(!ids(&results).contains(&1));
+
+        // A quoted phrase in a normal query puts adjacent matches first
+        let results = index.search("\"machine learning\"", 10);
+        assert_eq!(ids(&results).len(), 2);
+        let unquoted = index.search("machine learning", 10);
+        assert_eq!(unquoted.len(), 3);
+        let mixed = index.search("\"machine learning\" about", 10);
+        assert_eq!(mixed.len(), 3);
+        assert!(index.search_phrase("learning machine", 10).is_empty());
+    }
+
+    #[test]
+    fn test_adjacent_phrase_outranks_scattered_words() {
+        let index = index_of(&["machine learning models", "machine models learning"]);
+        let results = index.search("\"machine learning\" models", 10);
+        assert_eq!(ids(&results), vec![0, 1]);
+        assert!(results[0].1 > results[1].1);
+    }
+
+    #[test]
+    fn test_removed_document_drops_positions() {
+        let mut index = index_of(&["machine learning", "machine learning again"]);
+        index.remove_document(0);
+        assert_eq!(ids(&index.search_phrase("machine learning", 10)), vec![1]);
+        assert!(!index.term_positions["machine"].contains_key(&0));
+    }
+
+    #[test]
+    fn test_idf_cache_matches_formula_and_updates() {
+        let mut index = index_of(CORPUS);
+        let n = 5.0f32;
+        let df = 3.0f32;
+        let expected = ((n - df + 0.5) / (df + 0.5) + 1.0).ln();
+        assert_eq!(index.idf("rust").to_bits(), expected.to_bits());
+        assert_eq!(index.term_idf("rust").unwrap().to_bits(), expected.to_bits());
+        assert_eq!(index.term_idf("haskell"), None);
+
+        index.add_document(5, "rust everywhere");
+        let expected = ((6.0f32 - 4.0 + 0.5) / (4.0 + 0.5) + 1.0).ln();
+        assert_eq!(index.term_idf("rust").unwrap().to_bits(), expected.to_bits());
+
+        index.remove_document(5);
+        index.remove_document(0);
+        let expected = ((4.0f32 - 2.0 + 0.5) / (2.0 + 0.5) + 1.0).ln();
+        assert_eq!(index.term_idf("rust").unwrap().to_bits(), expected.to_bits());
+
+        // New params can move the epsilon floor, so they drop the cache too
+        index.set_params(Bm25Params { epsilon: 5.0, ..Bm25Params::default() }).unwrap();
+        assert_eq!(index.term_idf("rust"), Some(5.0));
+    }
+
+    #[test]
+    fn test_candidates_match_full_scan() {
+        let mut index = index_of(CORPUS);
+        index.add_document(7, "rust rust rust");
+        index.remove_document(2);
+        for query in ["rust", "python language", "programming", "\"scripting language\" rust^2"] {
+            let mut results = index.search(query, 10);
+            results.sort_by_key(|&(doc_id, _)| doc_id);
+            assert_eq!(results, scan_all(&index, query), "{}", query);
+        }
+        assert!(index.search("haskell", 10).is_empty());
+    }
+
+    #[test]
+    fn test_top_k_limit_and_order() {
+        let index = index_of(CORPUS);
+        let results = index.search("rust python language", 2);
+        assert_eq!(results.len(), 2);
+        assert!(results[0].1 >= results[1].1);
+
+        let all = index.search("rust python language", 0);
+        assert_eq!(all.len(), 4);
+        assert_eq!(index.search("rust python language", usize::MAX), all);
+        assert_eq!(index.search("rust python language", 1 << 40), all);
+    }
+
+    #[test]
+    fn test_ties_break_by_doc_id() {
+        let index = index_of(&["same words", "other", "same words", "same words"]);
+        let results = index.search("same", 10);
+        assert_eq!(ids(&results), vec![0, 2, 3]);
+        assert_eq!(results[0].1, results[2].1);
+        assert_eq!(ids(&index.search("same", 2)), vec![0, 2]);
+    }
+
+    #[test]
+    fn test_bm25f_title_outranks_body() {
+        let params = Bm25Params {
+            field_weights: HashMap::from([("title".to_string(), 3.0)]),
+            ..Bm25Params::default()
+        };
+        let mut index = Bm25Index::with_params(params);
+        let fields = |title: &str, body: &str| {
+            HashMap::from([("title".to_string(), title.to_string()), ("body".to_string(), body.to_string())])
+        };
+        index.add_document_fields(0, fields("cooking basics", "how to use rust in the kitchen"));
+        index.add_document_fields(1, fields("rust basics", "how to use cooking in the kitchen"));
+
+        assert_eq!(ids(&index.search("rust", 10)), vec![1, 0]);
+        // No phrase across the field boundary
+        assert!(index.search_phrase("basics how", 10).is_empty());
+
+        let explanation = index.explain("rust", 1);
+        assert_eq!(explanation.score, index.search("rust", 10)[0].1);
+
+        index.remove_document(1);
+        assert_eq!(index.field_length_totals["title"], 2);
+        assert_eq!(ids(&index.search("rust", 10)), vec![0]);
+    }
+
+    #[test]
+    fn test_boost_reorders_results() {
+        let index = index_of(&["rust language", "async language"]);
+        let plain = index.search("rust async", 10);
+        assert_eq!(plain[0].1, plain[1].1);
+
+        assert_eq!(ids(&index.search("rust^3 async", 10)), vec![0, 1]);
+        assert_eq!(ids(&index.search("rust async^3", 10)), vec![1, 0]);
+        let boosted = index.search("rust^3", 10);
+        assert!((boosted[0].1 - 3.0 * index.search("rust", 10)[0].1).abs() < 1e-6);
+
+        let explanation = index.explain("rust^3", 0);
+        assert_eq!(explanation.terms[0].boost, 3.0);
+    }
+
+    #[test]
+    fn test_malformed_boost_is_literal() {
+        assert_eq!(parse_boost("rust^2"), ("rust", 2.0));
+        assert_eq!(parse_boost("rust^1.5"), ("rust", 1.5));
+        for word in ["rust^", "rust^abc", "rust^-1", "rust^0", "^2"] {
+            assert_eq!(parse_boost(word), (word, 1.0));
+        }
+        let index = index_of(&["rust abc"]);
+        assert_eq!(index.search("rust^abc", 10).len(), 1);
+    }
+
+    #[test]
+    fn test_fuzzy_matching() {
+        let params = Bm25Params { max_edit_distance: 1, ..Bm25Params::default() };
+        let mut index = Bm25Index::with_params(params);
+        index.add_document(0, "machine learning");
+        index.add_document(1, "machne shop");
+        index.add_document(2, "cooking recipes");
+
+        assert_eq!(ids(&index.search("machie", 10)), vec![0, 1]);
+        // Indexed terms are never expanded, and a fuzzy match scores below
+        // the exact one
+        assert_eq!(ids(&index.search("machne", 10)), vec![1]);
+        let exact = index.search("machine", 10);
+        let fuzzy = index.search("machie", 10);
+        assert_eq!((exact[0].0, fuzzy[0].0), (0, 0));
+        assert!(exact[0].1 > fuzzy[0].1);
+
+        assert!(index_of(&["machine"]).search("machne", 10).is_empty());
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+
+    #[test]
+    fn test_explain_matches_search() {
+        let index = index_of(CORPUS);
+        for query in ["rust programming", "\"programming language\" rust^2", "python"] {
+            for (doc_id, score) in index.search(query, 10) {
+                let explanation = index.explain(query, doc_id);
+                assert_eq!(explanation.score.to_bits(), score.to_bits());
+                let sum: f32 = explanation.terms.iter().map(|t| t.contribution).sum();
+                assert!((sum - score).abs() < 1e-6);
+            }
+        }
+        let explanation = index.explain("rust", 1);
+        assert!(explanation.terms.is_empty());
+        assert_eq!(explanation.score, 0.0);
+        assert!(index.explain("rust", 99).terms.is_empty());
+    }
+
+    #[test]
+    fn test_scoring_many_candidates() {
+        let docs: Vec<String> = (0..500).map(|i| format!("common word{} {}", i % 7, "filler ".repeat(i % 5))).collect();
+        let docs: Vec<&str> = docs.iter().map(String::as_str).collect();
+        let index = index_of(&docs);
+        let mut results = index.search("common word3", 1000);
+        assert_eq!(results.len(), 500);
+        results.sort_by_key(|&(doc_id, _)| doc_id);
+        assert_eq!(results, scan_all(&index, "common word3"));
+    }
+
+    #[test]
+    fn test_highlight() {
+        let tokens = vec!["rust".to_string(), "fast".to_string()];
+        assert_eq!(
+            highlight("Rust is FAST, really fast!", &tokens, "<b>", "</b>"),
+            "<b>Rust</b> is <b>FAST</b>, really <b>fast</b>!"
+        );
+        assert_eq!(highlight("nothing (here)", &tokens, "<b>", "</b>"), "nothing (here)");
+        assert_eq!(highlight("rustacean trust", &tokens, "[", "]"), "rustacean trust");
+        assert_eq!(highlight("", &tokens, "[", "]"), "");
+    }
+
+    #[test]
+    fn test_min_score() {
+        let index = index_of(CORPUS);
+        let all = index.search("rust", 10);
+        assert_eq!(index.search_with_min_score("rust", 10, None), all);
+        let threshold = all[0].1;
+        let strict = index.search_with_min_score("rust", 10, Some(threshold));
+        assert!(strict.len() < all.len());
+        assert!(strict.iter().all(|&(_, score)| score >= threshold));
+
+        let (results, total) = index.search_with_count("rust", 1);
+        assert_eq!((results.len(), total), (1, 3));
+    }
+
+    #[test]
+    fn test_external_ids() {
+        let mut index = Bm25Index::new();
+        let a = index.add_document_with_id("a", "rust language");
+        let b = index.add_document_with_id("b", "rust compiler");
+        assert_eq!((a, b), (0, 1));
+        assert_eq!(index.add_document_with_id("a", "python language"), 0);
+
+        assert_eq!(index.search_by_id("rust", 10), vec![("b".to_string(), index.search("rust", 10)[0].1)]);
+        index.remove_document(1);
+        assert!(index.search_by_id("rust", 10).is_empty());
+        assert_eq!(index.doc_id_for("b"), None);
+
+        // Documents added without an id are left out
+        index.add_document(5, "python snake");
+        assert_eq!(index.search_by_id("python", 10).len(), 1);
+    }
+
+    #[test]
+    fn test_stats() {
+        let index = index_of(&["apple banana", "apple cherry date", "apple"]);
+        let stats = index.stats();
+        assert_eq!(stats.total_docs, 3);
+        assert_eq!(stats.avg_doc_length, 2.0);
+        assert_eq!(stats.vocabulary_size, 4);
+        assert_eq!(stats.total_postings, 6);
+        assert_eq!(stats.top_terms[0], ("apple".to_string(), 3));
+        assert_eq!(stats.top_terms[1], ("banana".to_string(), 1));
+
+        let wide = index_of(&["aa bb cc dd ee ff gg hh ii jj kk ll"]);
+        assert_eq!(wide.stats().top_terms.len(), STATS_TOP_TERMS);
+        assert_eq!(wide.stats().top_terms[0].0, "aa");
+
+        let stats = Bm25Index::new().stats();
+        assert_eq!((stats.total_docs, stats.avg_doc_length), (0, 0.0));
+    }
+
+    #[test]
+    fn test_search_boolean() {
+        let index = index_of(&[
+            "rust async runtime",
+            "rust sync code",
+            "javascript async promises",
+            "rust async javascript bindings",
+        ]);
+        let search = |query| ids(&index.search_boolean(query, 10).unwrap());
+
+        let mut and = search("rust AND async");
+        and.sort();
+        assert_eq!(and, vec![0, 3]);
+        assert_eq!(search("rust AND async NOT javascript"), vec![0]);
+        let mut or = search("sync OR promises");
+        or.sort();
+        assert_eq!(or, vec![1, 2]);
+        assert!(search("rust AND promises").is_empty());
+
+        for query in ["AND rust", "rust AND", "rust NOT", "NOT rust", "rust AND OR async", "(rust)"] {
+            assert!(index.search_boolean(query, 10).is_err(), "{}", query);
+        }
+    }
+
+    #[test]
+    fn test_search_filtered() {
+        let index = index_of(CORPUS);
+        let allowed = HashSet::from([2, 4]);
+        let filtered = index.search_filtered("rust", 10, &allowed);
+        let unfiltered = index.search("rust", 10);
+        assert_eq!(filtered.len(), 2);
+        assert!(filtered.iter().all(|result| unfiltered.contains(result)));
+        assert_eq!(index.search_filtered("rust", 1, &allowed).len(), 1);
+        assert!(index.search_filtered("rust", 10, &HashSet::new()).is_empty());
+    }
+
+    #[test]
+    fn test_search_iter_matches_search() {
+        let index = index_of(CORPUS);
+        for k in 1..=5 {
+            let lazy: Vec<(usize, f32)> = index.search_iter("rust language programming").take(k).collect();
+            assert_eq!(lazy, index.search("rust language programming", k));
+        }
+        assert_eq!(index.search_iter("absent").count(), 0);
+    }
+
+    #[cfg(feature = "lru")]
+    #[test]
+    fn test_query_cache() {
+        let mut index = index_of(CORPUS);
+        index.enable_query_cache(8);
+        let first = index.search("rust", 10);
+        assert_eq!(index.search("rust", 10), first);
+        let stats = index.stats();
+        assert_eq!((stats.cache_hits, stats.cache_misses), (1, 1));
+
+        index.add_document(9, "rust again");
+        let updated = index.search("rust", 10);
+        assert_eq!(updated.len(), first.len() + 1);
+        assert_eq!(index.stats().cache_misses, 2);
+
+        // Keyed on the parsed query: case and spacing don't matter, boosts do
+        assert_eq!(index.search("Rust ", 10), updated);
+        assert_eq!(index.stats().cache_hits, 2);
+        index.search("rust^2", 10);
+        index.search("rust", 5);
+        assert_eq!(index.stats().cache_misses, 4);
+    }
+
+    #[test]
+    fn test_length_norm_edge_cases() {
+        let single = index_of(&["rust"]);
+        let score = single.search("rust", 10)[0].1;
+        assert!(score.is_finite() && score > 0.0);
+
+        let mut empty = index_of(&[""]);
+        assert!(empty.search("rust", 10).is_empty());
+        assert_eq!(empty.length_norm(0), 1.0);
+        empty.add_document(1, "rust");
+        empty.remove_document(1);
+        assert_eq!(empty.stats().avg_doc_length, 0.0);
+
+        let unnormalized = Bm25Index::with_params(Bm25Params { b: 0.0, ..Bm25Params::default() });
+        assert_eq!(unnormalized.normalize_length(100.0, 1.0), 1.0);
+    }
+
+    #[test]
+    fn test_analyze() {
+        let mut index = Bm25Index::new();
+        assert_eq!(index.analyze("The Quick fox"), vec!["the", "quick", "fox"]);
+        index.set_stopword_filter(StopwordFilter::english());
+        assert_eq!(index.analyze("The Quick fox"), vec!["quick", "fox"]);
+    }
+
+    #[test]
+    fn test_unseen_term_gets_highest_idf() {
+        let index = index_of(CORPUS);
+        assert_eq!(index.document_frequency("haskell"), 0);
+        assert!(index.idf("haskell") > index.idf("rust"));
+        assert_eq!(index.idf("haskell"), index.idf_for_df(0.0));
+    }
+
+    #[test]
+    fn test_document_frequency_and_postings() {
+        let index = index_of(&["rust rust code", "Rust tools", "python"]);
+        assert_eq!(index.document_frequency("RUST"), 2);
+        assert_eq!(index.document_frequency("python"), 1);
+        assert_eq!(index.document_frequency("go"), 0);
+        assert_eq!(index.postings("Rust"), Some(&[(0, 2), (1, 1)][..]));
+        assert_eq!(index.postings("missing"), None);
+    }
+
+    #[test]
+    fn test_idf_variants() {
+        let mut docs = vec!["rare"; 1];
+        docs.extend(["common"; 9]);
+        let build = |idf| {
+            let mut index = Bm25Index::with_params(Bm25Params { idf, epsilon: 0.0, ..Bm25Params::default() });
+            for (doc_id, text) in docs.iter().enumerate() {
+                index.add_document(doc_id, text);
+            }
+            index
+        };
+
+        let lucene = build(IdfVariant::Lucene);
+        assert!((lucene.idf("rare") - (9.5f32 / 1.5 + 1.0).ln()).abs() < 1e-6);
+        let classic = build(IdfVariant::Classic);
+        assert!((classic.idf("rare") - 10.0f32.ln()).abs() < 1e-6);
+        assert!((classic.idf("common") - (10.0f32 / 9.0).ln()).abs() < 1e-6);
+        let probabilistic = build(IdfVariant::Probabilistic);
+        assert!((probabilistic.idf("rare") - (9.5f32 / 1.5).ln()).abs() < 1e-6);
+        // Negative before the epsilon floor, which is 0 here
+        assert_eq!(probabilistic.idf("common"), 0.0);
+    }
+
+    #[test]
+    fn test_load_checks_doc_slots() {
+        let path = temp_path("slots");
+        for compressed in [false, true] {
+            let save = |index: &Bm25Index, path: &Path| match compressed {
+                false => index.save(path).unwrap(),
+                true => index.save_compressed(path).unwrap(),
+            };
+            let load = |path: &Path| match compressed {
+                false => Bm25Index::load(path),
+                true => Bm25Index::load_compressed(path),
+            };
+            // A stale total_docs is recomputed, so removing every document can't underflow
+            let mut index = index_of(CORPUS);
+            index.remove_document(1);
+            index.total_docs = 1;
+            save(&index, &path);
+            let mut loaded = load(&path).unwrap();
+            assert_eq!(loaded.stats().total_docs, CORPUS.len() - 1);
+            for doc_id in 0..CORPUS.len() {
+                loaded.remove_document(doc_id);
+            }
+            assert_eq!(loaded.stats().total_docs, 0);
+
+            let mut index = index_of(CORPUS);
+            index.tombstones.insert(CORPUS.len());
+            save(&index, &path);
+            let err = format!("{:#}", load(&path).unwrap_err());
+            assert!(err.contains("out of range"), "{}", err);
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_compressed_round_trip() {
+        let mut index = Bm25Index::new();
+        for doc_id in 0..200 {
+            index.add_document(doc_id, &format!("common words topic{} and more topic{}", doc_id % 10, doc_id % 3));
+        }
+        index.add_document_with_id("named", "named rust document");
+        index.remove_document(17);
+
+        let (plain, compressed) = (temp_path("plain"), temp_path("compressed"));
+        index.save(&plain).unwrap();
+        index.save_compressed(&compressed).unwrap();
+        let plain_size = std::fs::metadata(&plain).unwrap().len();
+        let compressed_size = std::fs::metadata(&compressed).unwrap().len();
+        let loaded = Bm25Index::load_compressed(&compressed).unwrap();
+        std::fs::remove_file(&plain).unwrap();
+        std::fs::remove_file(&compressed).unwrap();
+
+        assert!(compressed_size * 2 < plain_size, "{} vs {}", compressed_size, plain_size);
+        for query in ["topic3", "common topic1", "\"more topic2\"", "rust"] {
+            assert_eq!(loaded.search(query, 300), index.search(query, 300), "{}", query);
+        }
+        assert_eq!(loaded.doc_id_for("named"), Some(200));
+        assert!(!loaded.contains_document(17));
+    }
+
+    #[test]
+    fn test_load_compressed_rejects_corrupt_postings() {
+        let index = index_of(&["alpha beta", "beta gamma"]);
+        let path = temp_path("corrupt");
+        index.save_compressed(&path).unwrap();
+        let good = std::fs::read(&path).unwrap();
+
+        // The last posting of the last term ("gamma": doc 1, tf 1, one
+        // position) ends with its doc gap 1, tf 1, 1 position, delta 1
+        let tail = good.len() - 4;
+        assert_eq!(&good[tail..], &[1, 1, 1, 1]);
+        for (gap, message) in [(0x7f, "out of range"), (0x05, "out of range")] {
+            let mut bytes = good.clone();
+            bytes[tail] = gap;
+            std::fs::write(&path, &bytes).unwrap();
+            let err = format!("{:#}", Bm25Index::load_compressed(&path).unwrap_err());
+            assert!(err.contains(message), "{}", err);
+        }
+
+        let mut bytes = good.clone();
+        bytes.push(0);
+        std::fs::write(&path, &bytes).unwrap();
+        assert!(Bm25Index::load_compressed(&path).is_err());
+        std::fs::write(&path, &good[..good.len() - 2]).unwrap();
+        assert!(Bm25Index::load_compressed(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_varint_overflow_is_an_error() {
+        let mut reader = VarintReader { bytes: &[0xff; 11], pos: 0 };
+        assert!(reader.varint().is_err());
+
+        let mut bytes = Vec::new();
+        write_varint(&mut bytes, u64::MAX);
+        write_varint(&mut bytes, 300);
+        let mut reader = VarintReader { bytes: &bytes, pos: 0 };
+        assert_eq!(reader.varint().unwrap(), u64::MAX);
+        assert_eq!(reader.varint().unwrap(), 300);
+        assert_eq!(reader.remaining(), 0);
+    }
+
+    #[test]
+    fn test_parallel_ingestion_matches_sequential() {
+        let docs: Vec<(usize, String)> = (0..50)
+            .map(|i| (i * 2, format!("doc {} about topic{} and topic{}", i, i % 7, i % 3)))
+            .chain([(4, "replaced topic1".to_string())])
+            .collect();
+        let mut sequential = Bm25Index::new();
+        for (doc_id, text) in &docs {
+            sequential.add_document(*doc_id, text);
+        }
+        let mut parallel = Bm25Index::new();
+        parallel.add_documents_parallel(&docs);
+
+        assert_eq!(parallel.doc_lengths, sequential.doc_lengths);
+        assert_eq!(parallel.tombstones, sequential.tombstones);
+        assert_eq!(parallel.doc_frequencies, sequential.doc_frequencies);
+        assert_eq!(parallel.term_positions, sequential.term_positions);
+        assert_eq!(parallel.avg_doc_length, sequential.avg_doc_length);
+        for query in ["topic1", "doc topic3", "replaced"] {
+            assert_eq!(parallel.search(query, 100), sequential.search(query, 100));
+        }
+    }
+
+    #[test]
+    fn test_params_builder_validation() {
+        assert!(Bm25Params::builder().k1(1.5).b(0.5).epsilon(0.1).build().is_ok());
+        assert!(Bm25Params::builder().build().is_ok());
+        assert!(Bm25Params::builder().k1(0.0).build().is_err());
+        assert!(Bm25Params::builder().k1(-1.0).build().is_err());
+        assert!(Bm25Params::builder().k1(f32::NAN).build().is_err());
+        assert!(Bm25Params::builder().b(-0.1).build().is_err());
+        assert!(Bm25Params::builder().b(1.1).build().is_err());
+        assert!(Bm25Params::builder().epsilon(-0.5).build().is_err());
+        assert!(Bm25Params::builder().field_weight("title", -1.0).build().is_err());
+
+        let mut index = Bm25Index::new();
+        assert!(index.set_params(Bm25Params { b: 2.0, ..Bm25Params::default() }).is_err());
+        assert_eq!(index.params().b, 0.75);
+    }
+
+    #[test]
+    fn test_extract_snippet() {
+        let text = "Intro words here.\n\nSome filler text goes on.\n\nThe rust compiler checks rust code.\n\nEnd.";
+        let tokens = vec!["rust".to_string()];
+        assert_eq!(extract_snippet(text, &tokens, 4), "... rust compiler checks rust ...");
+        assert_eq!(extract_snippet(text, &["none".to_string()], 3), "Intro words here. ...");
+        assert_eq!(extract_snippet("a rust b rust", &tokens, 2), "a rust ...");
+        assert_eq!(extract_snippet("short", &tokens, 10), "short");
+        assert_eq!(extract_snippet(text, &tokens, 0), "");
+    }
+
+    #[test]
+    fn test_prune_vocabulary() {
+        let mut index = index_of(&["the rare apple", "the banana kiwi", "the banana cherry", "the kiwi"]);
+        let before = index.stats().vocabulary_size;
+
+        // "rare", "apple" and "cherry" are too rare, "the" is too common
+        assert_eq!(index.prune_vocabulary(2, 0.75), 4);
+        assert_eq!(index.stats().vocabulary_size, before - 4);
+        assert!(index.search("apple", 10).is_empty());
+        assert!(index.search("the", 10).is_empty());
+        assert_eq!(index.search("banana", 10).len(), 2);
+        assert_eq!(index.prune_vocabulary(1, 1.0), 0);
+    }
+
+    #[test]
+    fn test_out_of_range_doc_id() {
+        let index = index_of(CORPUS);
+        let parsed = index.parse_query("rust");
+        assert_eq!(index.calculate_score(&parsed, 1000), 0.0);
+        assert!(index.length_norm(1000).is_finite());
+        assert!(!index.contains_document(1000));
+    }
+
+    #[test]
+    fn test_avg_doc_length_is_maintained() {
+        let mut index = Bm25Index::new();
+        let check = |index: &Bm25Index| {
+            let live: Vec<usize> = (0..index.doc_lengths.len()).filter(|&d| index.contains_document(d)).collect();
+            let expected = if live.is_empty() {
+                0.0
+            } else {
+                live.iter().map(|&d| index.doc_lengths[d]).sum::<usize>() as f32 / live.len() as f32
+            };
+            assert_eq!(index.stats().avg_doc_length, expected);
+        };
+
+        index.add_document(0, "one two three");
+        check(&index);
+        index.add_document(3, "one");
+        check(&index);
+        index.add_document(0, "one two three four five");
+        check(&index);
+        index.remove_document(0);
+        check(&index);
+        index.add_document_fields(1, HashMap::from([("title".to_string(), "a title here".to_string())]));
+        check(&index);
+        index.remove_document(3);
+        index.remove_document(1);
+        check(&index);
+        assert_eq!(index.stats().total_docs, 0);
+    }
+
+    #[test]
+    fn test_search_with_matches() {
+        let index = index_of(&["rust compiler", "python and rust", "machine learning"]);
+        let results = index.search_with_matches("rust python haskell", 10);
+        assert_eq!(results[0].0, 1);
+        assert_eq!(results[0].2, vec!["rust", "python"]);
+        assert_eq!(results[1].2, vec!["rust"]);
+
+        let phrase = index.search_with_matches("\"machine learning\"", 10);
+        assert_eq!(phrase[0].2, vec!["machine", "learning"]);
+    }
+
+    #[test]
+    fn test_limit_zero_returns_all_matches() {
+        let mut index = Bm25Index::new();
+        for doc_id in 0..30 {
+            index.add_document(doc_id, if doc_id % 3 == 0 { "match here" } else { "other" });
+        }
+        assert_eq!(index.search("match", 0).len(), 10);
+        assert_eq!(index.search_phrase("match here", 0).len(), 10);
+        assert_eq!(index.search_boolean("match", 0).unwrap().len(), 10);
+        assert_eq!(index.search_filtered("match", 0, &(0..30).collect()).len(), 10);
+    }
+
+    #[test]
+    fn test_more_like_this() {
+        let index = index_of(&[
+            "rust borrow checker ownership lifetimes",
+            "ownership and lifetimes in rust explained",
+            "baking sourdough bread at home",
+            "bread recipes for home bakers",
+        ]);
+        let similar = index.more_like_this(0, 3, 10);
+        assert_eq!(similar[0].0, 1);
+        assert!(!ids(&similar).contains(&0));
+        assert!(!ids(&similar).contains(&2));
+        assert!(index.more_like_this(0, 0, 10).is_empty());
+        assert!(index.more_like_this(99, 3, 10).is_empty());
+    }
+
+    #[test]
+    fn test_scores_are_bit_identical_across_builds() {
+        let docs: Vec<String> = (0..100)
+            .map(|i| format!("shared term{} text{} words{} shared", i % 13, i % 7, i % 5))
+            .collect();
+        let build = || {
+            let mut index = Bm25Index::new();
+            for (doc_id, text) in docs.iter().enumerate() {
+                index.add_document(doc_id, text);
+            }
+            index.add_document_fields(100, HashMap::from([
+                ("title".to_string(), "shared term1".to_string()),
+                ("body".to_string(), "text2 words3 shared".to_string()),
+            ]));
+            index
+        };
+        let bits = |results: Vec<(usize, f32)>| -> Vec<(usize, u32)> {
+            results.into_iter().map(|(doc_id, score)| (doc_id, score.to_bits())).collect()
+        };
+
+        let (first, second) = (build(), build());
+        for query in ["shared term1 text2 words3", "\"shared term4\"", "term5^2 words1"] {
+            assert_eq!(bits(first.search(query, 0)), bits(first.search(query, 0)), "{}", query);
+            assert_eq!(bits(first.search(query, 0)), bits(second.search(query, 0)), "{}", query);
+        }
+    }
+
+    #[test]
+    fn test_removal_uses_forward_index() {
+        let mut index = index_of(CORPUS);
+        index.add_document_fields(9, HashMap::from([("title".to_string(), "rust fields".to_string())]));
+        let json = serde_json::to_string(&index).unwrap();
+        let mut loaded: Bm25Index = serde_json::from_str(&json).unwrap();
+        let mut pruned = index.clone();
+        assert!(pruned.prune_vocabulary(0, 0.5) > 0);
+
+        for index in [&mut index, &mut loaded, &mut pruned] {
+            assert!(index.remove_document(0));
+            assert!(index.remove_document(9));
+            assert!(!index.remove_document(0));
+            assert!(!ids(&index.search("rust fields", 0)).iter().any(|&d| d == 0 || d == 9));
+            assert!(index.inverted_index.values().all(|postings| !postings.is_empty()));
+            assert!(!index.token_to_docs.values().any(|docs| docs.contains(&0)));
+        }
+        assert_eq!(loaded.search("rust python", 0), index.search("rust python", 0));
+    }
+
+    #[test]
+    fn test_doc_id_gap_within_limit() {
+        let mut index = Bm25Index::new();
+        index.add_document(MAX_DOC_ID_GAP, "far away");
+        assert_eq!(index.stats().total_docs, 1);
+        assert_eq!(ids(&index.search("far", 0)), vec![MAX_DOC_ID_GAP]);
+    }
+
+    #[test]
+    #[should_panic(expected = "past the end of the index")]
+    fn test_huge_doc_id_panics() {
+        Bm25Index::new().add_document(1 << 40, "too far");
+    }
+
+    #[test]
+    #[should_panic(expected = "past the end of the index")]
+    fn test_huge_doc_id_in_batch_panics() {
+        let docs = [(0, "near".to_string()), (MAX_DOC_ID_GAP + 2, "too far".to_string())];
+        Bm25Index::new().add_documents_parallel(&docs);
+    }
+}
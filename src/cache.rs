@@ -0,0 +1,156 @@
+use lru::LruCache;
+use parking_lot::Mutex;
+use std::fmt;
+use std::hash::Hash;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+type Results = Vec<(usize, f32)>;
+
+/// LRU cache of search results keyed by (query, limit), with hit/miss
+/// counters. Disabled until given a capacity.
+///
+/// `Bm25Index` keys it on the parsed query, so queries that analyze to the
+/// same terms (e.g. "Rust" and "rust " with the default tokenizer) share
+/// an entry.
+pub(crate) struct QueryCache<K> {
+    entries: Mutex<Option<LruCache<(K, usize), Results>>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl<K: Hash + Eq> QueryCache<K> {
+    pub(crate) fn new() -> Self {
+        Self {
+            entries: Mutex::new(None),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Keep up to `capacity` result lists; 0 disables the cache
+    pub(crate) fn set_capacity(&self, capacity: usize) {
+        *self.entries.lock() = NonZeroUsize::new(capacity).map(LruCache::new);
+    }
+
+    pub(crate) fn capacity(&self) -> usize {
+        self.entries.lock().as_ref().map_or(0, |cache| cache.cap().get())
+    }
+
+    /// Cached results for `(query, limit)`, or the result of `compute`, which
+    /// is then stored. Counts a hit or miss only while the cache is enabled.
+    pub(crate) fn get_or_insert(&self, query: K, limit: usize, compute: impl FnOnce() -> Results) -> Results {
+        let key = (query, limit);
+        {
+            let mut entries = self.entries.lock();
+            let Some(cache) = entries.as_mut() else {
+                drop(entries);
+                return compute();
+            };
+            if let Some(results) = cache.get(&key) {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                return results.clone();
+            }
+        }
+
+        // Score without holding the lock, so other queries aren't blocked
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let results = compute();
+        if let Some(cache) = self.entries.lock().as_mut() {
+            cache.put(key, results.clone());
+        }
+        results
+    }
+
+    pub(crate) fn clear(&self) {
+        if let Some(cache) = self.entries.lock().as_mut() {
+            cache.clear();
+        }
+    }
+
+    pub(crate) fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+}
+
+impl<K: Hash + Eq> Default for QueryCache<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A clone starts empty with the same capacity
+impl<K: Hash + Eq> Clone for QueryCache<K> {
+    fn clone(&self) -> Self {
+        let cache = Self::new();
+        cache.set_capacity(self.capacity());
+        cache
+    }
+}
+
+impl<K: Hash + Eq> fmt::Debug for QueryCache<K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("QueryCache")
+            .field("capacity", &self.capacity())
+            .field("hits", &self.hits())
+            .field("misses", &self.misses())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disabled_by_default() {
+        let cache = QueryCache::new();
+        let mut calls = 0;
+        for _ in 0..2 {
+            cache.get_or_insert("q", 5, || {
+                calls += 1;
+                vec![(1, 1.0)]
+            });
+        }
+        assert_eq!((calls, cache.hits(), cache.misses(), cache.capacity()), (2, 0, 0, 0));
+    }
+
+    #[test]
+    fn test_hits_misses_and_eviction() {
+        let cache = QueryCache::new();
+        cache.set_capacity(2);
+        assert_eq!(cache.get_or_insert("a", 5, || vec![(1, 1.0)]), vec![(1, 1.0)]);
+        assert_eq!(cache.get_or_insert("a", 5, || unreachable!()), vec![(1, 1.0)]);
+        // The limit is part of the key
+        cache.get_or_insert("a", 6, Vec::new);
+        assert_eq!((cache.hits(), cache.misses()), (1, 2));
+
+        // "a"/5 was used least recently, so "b" evicts it
+        cache.get_or_insert("b", 5, Vec::new);
+        let mut recomputed = false;
+        cache.get_or_insert("a", 5, || {
+            recomputed = true;
+            Vec::new()
+        });
+        assert!(recomputed);
+
+        cache.clear();
+        cache.get_or_insert("b", 5, Vec::new);
+        assert_eq!(cache.misses(), 5);
+    }
+
+    #[test]
+    fn test_clone_keeps_capacity_only() {
+        let cache = QueryCache::new();
+        cache.set_capacity(3);
+        cache.get_or_insert("a", 1, Vec::new);
+        let clone = cache.clone();
+        assert_eq!((clone.capacity(), clone.misses()), (3, 0));
+        cache.set_capacity(0);
+        assert_eq!(cache.capacity(), 0);
+    }
+}
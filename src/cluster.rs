@@ -0,0 +1,253 @@
+use crate::rng::SplitMix64;
+use crate::vector::{DistanceMetric, VectorStore};
+
+/// Cluster id given to removed embeddings by `VectorStore::kmeans`
+pub const UNASSIGNED_CLUSTER: usize = usize::MAX;
+
+impl VectorStore {
+    /// Group the live embeddings into `k` clusters with k-means, returning a
+    /// cluster id in `0..k` for every slot of `embeddings`
+    /// (`UNASSIGNED_CLUSTER` for removed ones).
+    ///
+    /// Distances follow the store's metric: L2 for `Euclidean`, cosine
+    /// distance for `Cosine` and `DotProduct`. Centroids are seeded with
+    /// k-means++ from `seed`, so results are reproducible. `k` is capped at
+    /// the number of live embeddings, and a cluster that ends up empty is
+    /// reseeded with the point farthest from its centroid. Stops after
+    /// `max_iters` updates or once assignments stop changing.
+    pub fn kmeans(&self, k: usize, max_iters: usize, seed: u64) -> Vec<usize> {
+        let mut assignments = vec![UNASSIGNED_CLUSTER; self.embeddings.len()];
+        let live: Vec<usize> = (0..self.embeddings.len()).filter(|&idx| self.contains(idx)).collect();
+        let k = k.min(live.len());
+        if k == 0 {
+            return assignments;
+        }
+
+        let points: Vec<&[f32]> = live.iter().map(|&idx| self.embeddings[idx].as_slice()).collect();
+        let mut rng = SplitMix64::new(seed);
+        let mut centroids = self.kmeans_plus_plus(&points, k, &mut rng);
+        let mut labels = self.assign(&points, &centroids);
+
+        for _ in 0..max_iters {
+            centroids = self.update_centroids(&points, &mut labels, k);
+            let next = self.assign(&points, &centroids);
+            if next == labels {
+                break;
+            }
+            labels = next;
+        }
+
+        for (&idx, label) in live.iter().zip(labels) {
+            assignments[idx] = label;
+        }
+        assignments
+    }
+
+    /// Average of the embeddings at `indices`, e.g. to search for "more like
+    /// these". With `normalize` the result is scaled to unit length.
+    /// Returns `None` if `indices` is empty or any index isn't a live embedding.
+    pub fn centroid(&self, indices: &[usize], normalize: bool) -> Option<Vec<f32>> {
+        if indices.is_empty() || !indices.iter().all(|&idx| self.contains(idx)) {
+            return None;
+        }
+
+        let mut centroid = vec![0.0f32; self.dimension];
+        for &idx in indices {
+            for (sum, x) in centroid.iter_mut().zip(&self.embeddings[idx]) {
+                *sum += x;
+            }
+        }
+        for x in centroid.iter_mut() {
+            *x /= indices.len() as f32;
+        }
+
+        if normalize {
+            Self::l2_normalize(&mut centroid);
+        }
+        Some(centroid)
+    }
+
+    /// Pick `k` starting centroids, each new one sampled with probability
+    /// proportional to its squared distance from the nearest one so far
+    fn kmeans_plus_plus(&self, points: &[&[f32]], k: usize, rng: &mut SplitMix64) -> Vec<Vec<f32>> {
+        let first = (rng.next_u64() % points.len() as u64) as usize;
+        let mut chosen = vec![first];
+        let mut nearest: Vec<f32> = points
+            .iter()
+            .map(|p| self.cluster_distance(p, points[first]).powi(2))
+            .collect();
+
+        while chosen.len() < k {
+            let total: f64 = nearest.iter().map(|&d| d as f64).sum();
+            let next = if total > 0.0 {
+                let mut target = rng.next_f64() * total;
+                let mut pick = points.len() - 1;
+                for (i, &d) in nearest.iter().enumerate() {
+                    target -= d as f64;
+                    if d > 0.0 && target < 0.0 {
+                        pick = i;
+                        break;
+                    }
+                }
+                pick
+            } else {
+                // Every point sits on a centroid already; take any unused one
+                (0..points.len()).find(|i| !chosen.contains(i)).unwrap_or(0)
+            };
+
+            chosen.push(next);
+            for (i, p) in points.iter().enumerate() {
+                let d = self.cluster_distance(p, points[next]).powi(2);
+                if d < nearest[i] {
+                    nearest[i] = d;
+                }
+            }
+        }
+
+        chosen.into_iter().map(|i| points[i].to_vec()).collect()
+    }
+
+    /// Index of the nearest centroid for every point, ties to the lower index
+    fn assign(&self, points: &[&[f32]], centroids: &[Vec<f32>]) -> Vec<usize> {
+        points
+            .iter()
+            .map(|p| {
+                let mut best = 0;
+                let mut best_distance = f32::INFINITY;
+                for (c, centroid) in centroids.iter().enumerate() {
+                    let d = self.cluster_distance(p, centroid);
+                    if d < best_distance {
+                        best = c;
+                        best_distance = d;
+                    }
+                }
+                best
+            })
+            .collect()
+    }
+
+    /// Mean of each cluster's points. An empty cluster takes over the point
+    /// farthest from its current centroid, and `labels` is updated to match
+    fn update_centroids(&self, points: &[&[f32]], labels: &mut [usize], k: usize) -> Vec<Vec<f32>> {
+        let mut sums = vec![vec![0.0f32; self.dimension]; k];
+        let mut counts = vec![0usize; k];
+        for (p, &label) in points.iter().zip(labels.iter()) {
+            counts[label] += 1;
+            for (sum, x) in sums[label].iter_mut().zip(p.iter()) {
+                *sum += x;
+            }
+        }
+
+        for (sum, &count) in sums.iter_mut().zip(&counts) {
+            if count > 0 {
+                for x in sum.iter_mut() {
+                    *x /= count as f32;
+                }
+            }
+        }
+
+        let empty_clusters: Vec<usize> = (0..k).filter(|&c| counts[c] == 0).collect();
+        for empty in empty_clusters {
+            let farthest = (0..points.len())
+                .filter(|&i| counts[labels[i]] > 1)
+                .max_by(|&a, &b| {
+                    let da = self.cluster_distance(points[a], &sums[labels[a]]);
+                    let db = self.cluster_distance(points[b], &sums[labels[b]]);
+                    da.total_cmp(&db).then_with(|| b.cmp(&a))
+                });
+            if let Some(i) = farthest {
+                counts[labels[i]] -= 1;
+                counts[empty] = 1;
+                labels[i] = empty;
+                sums[empty] = points[i].to_vec();
+            }
+        }
+
+        sums
+    }
+
+    fn cluster_distance(&self, a: &[f32], b: &[f32]) -> f32 {
+        match self.metric {
+            DistanceMetric::Euclidean => Self::euclidean_distance(a, b),
+            DistanceMetric::Cosine | DistanceMetric::DotProduct => 1.0 - Self::cosine_similarity(a, b),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::SplitMix64;
+
+    /// `per_cluster` noisy points around each of `centers`
+    fn blobs(centers: &[[f32; 2]], per_cluster: usize, seed: u64) -> Vec<Vec<f32>> {
+        let mut rng = SplitMix64::new(seed);
+        let mut noise = || (rng.next_f64() as f32 - 0.5) * 0.2;
+        centers
+            .iter()
+            .flat_map(|c| (0..per_cluster).map(|_| vec![c[0] + noise(), c[1] + noise()]).collect::<Vec<_>>())
+            .collect()
+    }
+
+    #[test]
+    fn test_kmeans_recovers_separated_clusters() {
+        let centers = [[0.0, 0.0], [10.0, 10.0], [-10.0, 10.0]];
+        let store = VectorStore::with_metric(blobs(&centers, 20, 7), DistanceMetric::Euclidean).unwrap();
+        let labels = store.kmeans(3, 50, 1);
+
+        for cluster in labels.chunks(20) {
+            assert!(cluster.iter().all(|&label| label == cluster[0]));
+        }
+        let mut distinct: Vec<usize> = labels.chunks(20).map(|c| c[0]).collect();
+        distinct.sort();
+        assert_eq!(distinct, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_kmeans_cosine_groups_by_direction() {
+        let embeddings = vec![vec![1.0, 0.05], vec![5.0, 0.1], vec![0.05, 1.0], vec![0.1, 7.0]];
+        let labels = VectorStore::new(embeddings).unwrap().kmeans(2, 20, 3);
+        assert_eq!(labels[0], labels[1]);
+        assert_eq!(labels[2], labels[3]);
+        assert_ne!(labels[0], labels[2]);
+    }
+
+    #[test]
+    fn test_kmeans_is_seeded() {
+        let store = VectorStore::with_metric(blobs(&[[0.0, 0.0], [3.0, 3.0]], 30, 11), DistanceMetric::Euclidean)
+            .unwrap();
+        assert_eq!(store.kmeans(4, 10, 5), store.kmeans(4, 10, 5));
+    }
+
+    #[test]
+    fn test_kmeans_edge_cases() {
+        let mut store = VectorStore::with_metric(blobs(&[[0.0, 0.0]], 3, 1), DistanceMetric::Euclidean).unwrap();
+        // k is capped at the number of points, so each gets its own cluster
+        let mut labels = store.kmeans(10, 10, 0);
+        labels.sort();
+        assert_eq!(labels, vec![0, 1, 2]);
+
+        store.remove(1).unwrap();
+        let labels = store.kmeans(2, 10, 0);
+        assert_eq!(labels[1], UNASSIGNED_CLUSTER);
+        assert!(labels[0] < 2 && labels[2] < 2);
+
+        assert!(VectorStore::default().kmeans(3, 10, 0).is_empty());
+        assert_eq!(store.kmeans(0, 10, 0), vec![UNASSIGNED_CLUSTER; 3]);
+    }
+
+    #[test]
+    fn test_centroid() {
+        let mut store = VectorStore::new(vec![vec![1.0, 2.0], vec![1.0, 2.0], vec![3.0, 0.0]]).unwrap();
+        assert_eq!(store.centroid(&[0, 1], false), Some(vec![1.0, 2.0]));
+        assert_eq!(store.centroid(&[0, 2], false), Some(vec![2.0, 1.0]));
+
+        let unit = store.centroid(&[2], true).unwrap();
+        assert_eq!(unit, vec![1.0, 0.0]);
+
+        assert_eq!(store.centroid(&[], false), None);
+        assert_eq!(store.centroid(&[0, 5], false), None);
+        store.remove(1).unwrap();
+        assert_eq!(store.centroid(&[1], false), None);
+    }
+}
@@ -0,0 +1,178 @@
+use anyhow::Result;
+
+use crate::rng::SplitMix64;
+use crate::tokenizer::tokenize;
+use crate::vector::VectorStore;
+
+/// Turns text into embeddings, so a model or API can be plugged into `RagEngine`.
+///
+/// `embed` returns one vector per input text, each `dimension()` long.
+pub trait Embedder {
+    fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>>;
+
+    fn dimension(&self) -> usize;
+}
+
+/// Call `embedder` and check it returned one vector of the declared
+/// dimension per text
+pub(crate) fn embed_checked<E: Embedder + ?Sized>(embedder: &E, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+    let embeddings = embedder.embed(texts)?;
+    if embeddings.len() != texts.len() {
+        return Err(anyhow::anyhow!(
+            "Embedder returned {} embeddings for {} texts",
+            embeddings.len(), texts.len()
+        ));
+    }
+
+    let dimension = embedder.dimension();
+    for (i, emb) in embeddings.iter().enumerate() {
+        if emb.len() != dimension {
+            return Err(anyhow::anyhow!(
+                "Embedding {} has dimension {} but the embedder declares {}",
+                i, emb.len(), dimension
+            ));
+        }
+    }
+    Ok(embeddings)
+}
+
+/// Deterministic embedder for tests and demos.
+///
+/// Each token is hashed into one of `dimension` buckets with a sign, and the
+/// result is L2-normalized, so texts sharing words get similar vectors.
+#[derive(Debug, Clone)]
+pub struct MockEmbedder {
+    dimension: usize,
+}
+
+impl MockEmbedder {
+    pub fn new(dimension: usize) -> Self {
+        Self { dimension }
+    }
+
+    fn embed_one(&self, text: &str) -> Vec<f32> {
+        let mut embedding = vec![0.0; self.dimension];
+        if self.dimension == 0 {
+            return embedding;
+        }
+        for token in tokenize(text) {
+            let hash = fnv1a(token.as_bytes());
+            let bucket = (hash % self.dimension as u64) as usize;
+            let sign = if hash >> 63 == 0 { 1.0 } else { -1.0 };
+            embedding[bucket] += sign;
+        }
+        VectorStore::l2_normalize(&mut embedding);
+        embedding
+    }
+}
+
+impl Embedder for MockEmbedder {
+    fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        Ok(texts.iter().map(|text| self.embed_one(text)).collect())
+    }
+
+    fn dimension(&self) -> usize {
+        self.dimension
+    }
+}
+
+/// A pseudo-random unit vector of length `dim` seeded by a hash of `text`, so
+/// the same text always maps to the same vector on every platform. Unlike
+/// `MockEmbedder`, any change to the text gives an unrelated vector, so
+/// distinct texts are close to orthogonal.
+pub fn mock_embed(text: &str, dim: usize) -> Vec<f32> {
+    let mut rng = SplitMix64::new(fnv1a(text.as_bytes()));
+    let mut embedding: Vec<f32> = (0..dim).map(|_| (rng.next_f64() * 2.0 - 1.0) as f32).collect();
+    VectorStore::l2_normalize(&mut embedding);
+    embedding
+}
+
+/// 64-bit FNV-1a, stable across platforms and Rust versions
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mock_embedder_is_deterministic() {
+        let embedder = MockEmbedder::new(64);
+        let texts = ["rust search", "baking bread"].map(String::from);
+        let e = embedder.embed(&texts).unwrap();
+        assert_eq!(e, embedder.embed(&texts).unwrap());
+        assert_ne!(e[0], e[1]);
+        assert!((VectorStore::dot_product(&e[0], &e[0]) - 1.0).abs() < 1e-5);
+        // Reference FNV-1a value, so a change to the hash shows up here
+        assert_eq!(fnv1a(b"doc 1"), 0x42ca_71ab_72f3_70fa);
+    }
+
+    #[test]
+    fn test_mock_embed_is_deterministic() {
+        let a = mock_embed("doc 1", 64);
+        assert_eq!(a, mock_embed("doc 1", 64));
+        assert_ne!(a, mock_embed("doc 2", 64));
+        assert!((VectorStore::dot_product(&a, &a) - 1.0).abs() < 1e-5);
+        assert!(mock_embed("doc 1", 0).is_empty());
+    }
+
+    #[test]
+    fn test_mock_embed_is_near_orthogonal() {
+        let embeddings: Vec<Vec<f32>> = (0..50).map(|i| mock_embed(&format!("doc {}", i), 256)).collect();
+        let mut total = 0.0;
+        let mut pairs = 0;
+        for (i, a) in embeddings.iter().enumerate() {
+            for b in &embeddings[i + 1..] {
+                total += VectorStore::cosine_similarity(a, b).abs();
+                pairs += 1;
+            }
+        }
+        assert!(total / (pairs as f32) < 0.1);
+    }
+
+    #[test]
+    fn test_mock_embedder_shares_words() {
+        let embedder = MockEmbedder::new(128);
+        let texts = ["rust search engine", "a search engine in rust", "baking bread"].map(String::from);
+        let e = embedder.embed(&texts).unwrap();
+        assert_eq!(e.len(), 3);
+        assert!(e.iter().all(|v| v.len() == 128));
+        assert!(VectorStore::cosine_similarity(&e[0], &e[1]) > VectorStore::cosine_similarity(&e[0], &e[2]));
+        assert_eq!(embedder.embed(&texts).unwrap(), e);
+        assert!(MockEmbedder::new(0).embed(&texts).unwrap().iter().all(Vec::is_empty));
+    }
+
+    /// Returns `count` vectors of length `len`, declaring `dimension`
+    struct Broken {
+        count: usize,
+        len: usize,
+        dimension: usize,
+    }
+
+    impl Embedder for Broken {
+        fn embed(&self, _texts: &[String]) -> Result<Vec<Vec<f32>>> {
+            Ok(vec![vec![0.5; self.len]; self.count])
+        }
+
+        fn dimension(&self) -> usize {
+            self.dimension
+        }
+    }
+
+    #[test]
+    fn test_embed_checked() {
+        let texts = vec!["a".to_string(), "b".to_string()];
+        assert_eq!(embed_checked(&Broken { count: 2, len: 3, dimension: 3 }, &texts).unwrap().len(), 2);
+
+        let err = embed_checked(&Broken { count: 1, len: 3, dimension: 3 }, &texts).unwrap_err();
+        assert!(err.to_string().contains("1 embeddings for 2 texts"));
+        let err = embed_checked(&Broken { count: 2, len: 4, dimension: 3 }, &texts).unwrap_err();
+        assert!(err.to_string().contains("dimension 4 but the embedder declares 3"));
+
+        let dynamic: &dyn Embedder = &MockEmbedder::new(8);
+        assert_eq!(embed_checked(dynamic, &texts).unwrap()[0].len(), 8);
+    }
+}
@@ -0,0 +1,639 @@
+use anyhow::Result;
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use crate::bm25::Bm25Index;
+use crate::embedder::{embed_checked, Embedder};
+use crate::filter::MetadataFilter;
+use crate::hybrid::{hybrid_search_with, HybridConfig};
+use crate::ranking::cmp_score;
+use crate::recency::RecencyConfig;
+use crate::reranker::Reranker;
+use crate::synonyms::SynonymConfig;
+use crate::types::{normalized_text, Chunk, SearchResult, SearchType};
+use crate::vector::{check_finite, VectorStore};
+
+/// Keyword, semantic and hybrid search over one set of chunks.
+///
+/// Chunk `i`, BM25 document `i` and embedding `i` always refer to the same
+/// thing, so results come back as `SearchResult`s with the chunk filled in.
+#[derive(Debug, Clone, Default)]
+pub struct RagEngine {
+    bm25: Bm25Index,
+    vectors: VectorStore,
+    chunks: Vec<Chunk>,
+    hybrid: HybridConfig,
+    dedup: bool,
+    recency: Option<RecencyConfig>,
+    synonyms: Option<SynonymConfig>,
+    reranker: Option<Arc<dyn Reranker>>,
+    rerank_top_n: usize,
+}
+
+impl RagEngine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use `bm25` for keyword search, e.g. one with custom params or a tokenizer.
+    /// It should be empty; chunks are indexed into it by `ingest`
+    pub fn with_bm25(mut self, bm25: Bm25Index) -> Self {
+        self.bm25 = bm25;
+        self
+    }
+
+    pub fn with_hybrid_config(mut self, config: HybridConfig) -> Self {
+        self.hybrid = config;
+        self
+    }
+
+    /// Skip chunks at ingestion whose text (ignoring case and whitespace)
+    /// matches a chunk already in the engine or earlier in the same batch
+    pub fn with_dedup(mut self, dedup: bool) -> Self {
+        self.dedup = dedup;
+        self
+    }
+
+    /// Expand keyword queries with synonyms, see `SynonymConfig`. Errors if
+    /// the weight isn't in (0, 1]
+    pub fn with_synonyms(mut self, config: SynonymConfig) -> Result<Self> {
+        config.validate()?;
+        self.synonyms = Some(config);
+        Ok(self)
+    }
+
+    /// Boost newer chunks after scoring, see `RecencyConfig`. Results are
+    /// reordered by the boosted scores before any reranking. Errors if the
+    /// decay rate is negative or not finite
+    pub fn with_recency(mut self, config: RecencyConfig) -> Result<Self> {
+        config.validate()?;
+        self.recency = Some(config);
+        Ok(self)
+    }
+
+    /// Pass the best `top_n` results of every search through `reranker`.
+    /// Results past `top_n` keep their order and follow the reranked ones
+    pub fn with_reranker<R: Reranker + 'static>(mut self, reranker: R, top_n: usize) -> Self {
+        self.reranker = Some(Arc::new(reranker));
+        self.rerank_top_n = top_n;
+        self
+    }
+
+    pub fn bm25(&self) -> &Bm25Index {
+        &self.bm25
+    }
+
+    pub fn vectors(&self) -> &VectorStore {
+        &self.vectors
+    }
+
+    pub fn chunks(&self) -> &[Chunk] {
+        &self.chunks
+    }
+
+    pub fn hybrid_config(&self) -> &HybridConfig {
+        &self.hybrid
+    }
+
+    pub fn len(&self) -> usize {
+        self.chunks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+
+    /// Add chunks with one embedding each. Nothing is added if the counts or
+    /// embedding dimensions don't match, or an embedding contains NaN or
+    /// infinity. With `with_dedup`, duplicate chunks and their embeddings are
+    /// dropped.
+    pub fn ingest(&mut self, chunks: Vec<Chunk>, embeddings: Vec<Vec<f32>>) -> Result<()> {
+        if chunks.len() != embeddings.len() {
+            return Err(anyhow::anyhow!(
+                "Got {} chunks but {} embeddings",
+                chunks.len(), embeddings.len()
+            ));
+        }
+
+        let dimension = if self.vectors.embeddings.is_empty() {
+            embeddings.first().map_or(0, |e| e.len())
+        } else {
+            self.vectors.dimension
+        };
+        for (i, emb) in embeddings.iter().enumerate() {
+            if emb.len() != dimension {
+                return Err(anyhow::anyhow!(
+                    "Embedding {} has dimension {} but expected {}",
+                    i, emb.len(), dimension
+                ));
+            }
+            check_finite(emb).map_err(|e| anyhow::anyhow!("Embedding {} {}", i, e))?;
+        }
+
+        let mut seen: HashSet<String> = if self.dedup {
+            self.chunks.iter().map(normalized_text).collect()
+        } else {
+            HashSet::new()
+        };
+
+        for (chunk, embedding) in chunks.into_iter().zip(embeddings) {
+            if self.dedup && !seen.insert(normalized_text(&chunk)) {
+                continue;
+            }
+            let idx = self.vectors.add(embedding)?;
+            self.bm25.add_document(idx, &chunk.as_text());
+            self.chunks.push(chunk);
+        }
+        Ok(())
+    }
+
+    /// Embed each chunk's text with `embedder` and add them. Errors if the
+    /// embedder returns the wrong number or dimension of vectors.
+    pub fn ingest_texts<E: Embedder + ?Sized>(&mut self, chunks: Vec<Chunk>, embedder: &E) -> Result<()> {
+        let texts: Vec<String> = chunks.iter().map(|chunk| chunk.as_text()).collect();
+        let embeddings = embed_checked(embedder, &texts)?;
+        self.ingest(chunks, embeddings)
+    }
+
+    /// Search with the given mode. `query` is used for keyword search and
+    /// `query_embedding` for semantic search; hybrid uses both.
+    pub fn search(
+        &self,
+        query: &str,
+        query_embedding: &[f32],
+        mode: SearchType,
+        limit: usize,
+    ) -> Result<Vec<SearchResult>> {
+        let results = match mode {
+            SearchType::Keyword => self.bm25.search(&self.keyword_query(query), limit),
+            SearchType::Semantic => self.vectors.search(query_embedding, limit)?,
+            SearchType::Hybrid => {
+                // Over-fetch for fusion; saturating keeps huge limits from
+                // overflowing and 0 ("all") as 0
+                let candidates = limit.saturating_mul(2);
+                let keyword = self.bm25.search(&self.keyword_query(query), candidates);
+                let semantic = self.vectors.search(query_embedding, candidates)?;
+                hybrid_search_with(keyword, semantic, limit, &self.hybrid)?
+            }
+        };
+
+        let results = self.apply_recency(resolve_results(&results, &self.chunks, mode.clone()), &mode);
+        Ok(self.rerank(query, results))
+    }
+
+    /// Like `search`, but only chunks whose metadata matches `filter` are
+    /// scored: the others are dropped from the candidates of both keyword and
+    /// semantic search beforehand. `limit` applies after filtering.
+    pub fn search_filtered(
+        &self,
+        query: &str,
+        query_embedding: &[f32],
+        mode: SearchType,
+        limit: usize,
+        filter: &MetadataFilter,
+    ) -> Result<Vec<SearchResult>> {
+        let allowed: HashSet<usize> = self
+            .chunks
+            .iter()
+            .enumerate()
+            .filter(|(_, chunk)| filter.matches(&chunk.metadata))
+            .map(|(idx, _)| idx)
+            .collect();
+
+        let results = match mode {
+            SearchType::Keyword => self.bm25.search_filtered(&self.keyword_query(query), limit, &allowed),
+            SearchType::Semantic => self.vectors.search_filtered(query_embedding, limit, &allowed)?,
+            SearchType::Hybrid => {
+                let candidates = limit.saturating_mul(2);
+                let keyword = self.bm25.search_filtered(&self.keyword_query(query), candidates, &allowed);
+                let semantic = self.vectors.search_filtered(query_embedding, candidates, &allowed)?;
+                hybrid_search_with(keyword, semantic, limit, &self.hybrid)?
+            }
+        };
+
+        let results = self.apply_recency(resolve_results(&results, &self.chunks, mode.clone()), &mode);
+        Ok(self.rerank(query, results))
+    }
+
+    /// `query` with synonyms added, if configured, for BM25
+    fn keyword_query<'a>(&self, query: &'a str) -> Cow<'a, str> {
+        match &self.synonyms {
+            Some(synonyms) => Cow::Owned(synonyms.expand(query)),
+            None => Cow::Borrowed(query),
+        }
+    }
+
+    /// Scale scores by the recency decay, if configured, and reorder. Positive
+    /// similarities are multiplied by the decay; negative similarities (e.g.
+    /// cosine below 0, dot products, z-score fusion) and Euclidean distances
+    /// are divided by it instead, so older chunks always move down
+    fn apply_recency(&self, mut results: Vec<SearchResult>, mode: &SearchType) -> Vec<SearchResult> {
+        let Some(recency) = &self.recency else {
+            return results;
+        };
+
+        let distances = *mode == SearchType::Semantic && !self.vectors.metric.higher_is_better();
+        for result in results.iter_mut() {
+            if let Some(decay) = recency.decay(&result.chunk.metadata) {
+                if distances || result.score < 0.0 {
+                    result.score /= decay.max(f32::MIN_POSITIVE);
+                } else {
+                    result.score *= decay;
+                }
+            }
+        }
+
+        if distances {
+            results.sort_by(|a, b| cmp_score(-a.score, -b.score).reverse());
+        } else {
+            results.sort_by(|a, b| cmp_score(b.score, a.score));
+        }
+        set_ranks(&mut results);
+        results
+    }
+
+    /// Apply the reranker, if any, to the first `rerank_top_n` results
+    fn rerank(&self, query: &str, mut results: Vec<SearchResult>) -> Vec<SearchResult> {
+        let Some(reranker) = &self.reranker else {
+            return results;
+        };
+        let rest = results.split_off(self.rerank_top_n.min(results.len()));
+        let mut reranked = reranker.rerank(query, results);
+        reranked.extend(rest);
+        set_ranks(&mut reranked);
+        reranked
+    }
+
+    /// `search` on tokio's blocking thread pool, so CPU-bound scoring doesn't
+    /// stall the async runtime. Must be called from within a tokio runtime.
+    #[cfg(feature = "tokio")]
+    pub async fn search_async(
+        self: Arc<Self>,
+        query: String,
+        query_embedding: Vec<f32>,
+        mode: SearchType,
+        limit: usize,
+    ) -> Result<Vec<SearchResult>> {
+        tokio::task::spawn_blocking(move || self.search(&query, &query_embedding, mode, limit))
+            .await
+            .map_err(|e| anyhow::anyhow!("Search task failed: {}", e))?
+    }
+}
+
+/// Turn `(index, score)` results into `SearchResult`s with the chunk at each
+/// index, keeping the order of `results` and ranked from 1. Indices past the
+/// end of `chunks` are skipped with a warning: a `tracing` event when that
+/// feature is on, otherwise the console on wasm32 and stderr elsewhere.
+pub fn resolve_results(results: &[(usize, f32)], chunks: &[Chunk], search_type: SearchType) -> Vec<SearchResult> {
+    let mut resolved: Vec<SearchResult> = results
+        .iter()
+        .filter_map(|&(idx, score)| {
+            let Some(chunk) = chunks.get(idx) else {
+                warn(&format!("Skipping search result {} (only {} chunks loaded)", idx, chunks.len()));
+                return None;
+            };
+            Some(SearchResult {
+                score,
+                chunk: chunk.clone(),
+                search_type: search_type.clone(),
+                rank: 0,
+            })
+        })
+        .collect();
+    set_ranks(&mut resolved);
+    resolved
+}
+
+/// Number results by their position, starting at 1
+fn set_ranks(results: &mut [SearchResult]) {
+    for (position, result) in results.iter_mut().enumerate() {
+        result.rank = position + 1;
+    }
+}
+
+#[cfg(feature = "tracing")]
+fn warn(message: &str) {
+    tracing::warn!("{}", message);
+}
+
+#[cfg(all(not(feature = "tracing"), target_arch = "wasm32"))]
+fn warn(message: &str) {
+    web_sys::console::warn_1(&message.into());
+}
+
+#[cfg(all(not(feature = "tracing"), not(target_arch = "wasm32")))]
+fn warn(message: &str) {
+    eprintln!("warning: {}", message);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embedder::MockEmbedder;
+    use crate::hybrid::FusionStrategy;
+    use crate::reranker::IdentityReranker;
+    use crate::types::{chunk_text, ChunkContent, ChunkMetadata, TextChunk};
+    use crate::vector::DistanceMetric;
+
+    const TEXTS: [&str; 4] = [
+        "rust is a systems programming language",
+        "python is popular for machine learning",
+        "search engines rank documents by relevance",
+        "rust makes search engines fast",
+    ];
+
+    fn text_chunk(id: &str, text: &str) -> Chunk {
+        Chunk {
+            id: id.to_string(),
+            content: ChunkContent::Text {
+                text: TextChunk {
+                    id: id.to_string(),
+                    html: None,
+                    markdown: None,
+                    text: text.to_string(),
+                },
+            },
+            metadata: ChunkMetadata::default(),
+            enrichment: None,
+        }
+    }
+
+    fn chunks() -> Vec<Chunk> {
+        TEXTS.iter().enumerate().map(|(i, text)| text_chunk(&format!("doc-{}", i), text)).collect()
+    }
+
+    /// One axis each for "rust", "python", "search" and everything else
+    fn embed(text: &str) -> Vec<f32> {
+        let mut embedding = vec![0.1; 4];
+        for word in text.split_whitespace() {
+            match word {
+                "rust" => embedding[0] += 1.0,
+                "python" => embedding[1] += 1.0,
+                "search" | "engines" => embedding[2] += 1.0,
+                _ => embedding[3] += 0.1,
+            }
+        }
+        embedding
+    }
+
+    fn texts(results: &[SearchResult]) -> Vec<String> {
+        results.iter().map(|r| r.chunk.as_text()).collect()
+    }
+
+    fn engine() -> RagEngine {
+        let mut engine = RagEngine::new();
+        engine.ingest(chunks(), TEXTS.iter().map(|t| embed(t)).collect()).unwrap();
+        engine
+    }
+
+    #[test]
+    fn test_search_modes() {
+        let engine = engine();
+        let query = embed("rust search engines");
+        assert_eq!(engine.len(), 4);
+
+        for mode in [SearchType::Keyword, SearchType::Semantic, SearchType::Hybrid] {
+            let results = engine.search("rust search engines", &query, mode.clone(), 2).unwrap();
+            assert_eq!(results.len(), 2);
+            assert_eq!(results[0].chunk.as_text(), TEXTS[3]);
+            assert!(results.iter().all(|r| r.search_type == mode));
+            assert_eq!(results.iter().map(|r| r.rank).collect::<Vec<_>>(), vec![1, 2]);
+        }
+    }
+
+    #[test]
+    fn test_ingest_rejects_mismatched_input() {
+        let mut engine = RagEngine::new();
+        let err = engine.ingest(chunks(), vec![vec![1.0, 0.0]; 3]).unwrap_err();
+        assert_eq!(err.to_string(), "Got 4 chunks but 3 embeddings");
+
+        let mut embeddings = vec![vec![1.0, 0.0]; 4];
+        embeddings[2] = vec![1.0, 0.0, 0.0];
+        let err = engine.ingest(chunks(), embeddings).unwrap_err();
+        assert_eq!(err.to_string(), "Embedding 2 has dimension 3 but expected 2");
+
+        let mut embeddings = vec![vec![1.0, 0.0]; 4];
+        embeddings[1][0] = f32::NAN;
+        assert!(engine.ingest(chunks(), embeddings).unwrap_err().to_string().starts_with("Embedding 1"));
+        // Nothing was added by the failed calls
+        assert!(engine.is_empty());
+        assert!(engine.bm25().search("rust", 10).is_empty());
+
+        engine.ingest(chunks(), vec![vec![1.0, 0.0]; 4]).unwrap();
+        // Later batches must match the dimension of the first
+        let err = engine.ingest(chunks()[..1].to_vec(), vec![vec![1.0; 3]]).unwrap_err();
+        assert_eq!(err.to_string(), "Embedding 0 has dimension 3 but expected 2");
+        assert_eq!(engine.len(), 4);
+    }
+
+    /// Declares one dimension but returns another
+    struct WrongDimension;
+
+    impl Embedder for WrongDimension {
+        fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+            Ok(vec![vec![1.0; 3]; texts.len()])
+        }
+
+        fn dimension(&self) -> usize {
+            4
+        }
+    }
+
+    #[test]
+    fn test_ingest_texts() {
+        let embedder = MockEmbedder::new(64);
+        let mut engine = RagEngine::new();
+        engine.ingest_texts(chunks(), &embedder).unwrap();
+        assert_eq!(engine.len(), 4);
+        assert_eq!(engine.vectors().dimension, 64);
+
+        let mut engine = RagEngine::new();
+        assert!(engine.ingest_texts(chunks(), &WrongDimension).is_err());
+        assert!(engine.is_empty());
+    }
+
+    #[test]
+    fn test_semantic_query_is_checked() {
+        let engine = engine();
+        assert!(engine.search("rust", &[1.0, 0.0], SearchType::Semantic, 5).is_err());
+        // Keyword search ignores the embedding
+        assert!(engine.search("rust", &[], SearchType::Keyword, 5).is_ok());
+    }
+
+    #[test]
+    fn test_resolve_results() {
+        let chunks = chunks();
+        let resolved = resolve_results(&[(2, 0.9), (7, 0.8), (0, 0.5)], &chunks, SearchType::Keyword);
+        assert_eq!(texts(&resolved), vec![TEXTS[2], TEXTS[0]]);
+        assert_eq!(resolved.iter().map(|r| (r.rank, r.score)).collect::<Vec<_>>(), vec![(1, 0.9), (2, 0.5)]);
+        assert!(resolve_results(&[(0, 1.0)], &[], SearchType::Semantic).is_empty());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn test_search_async() {
+        let engine = engine();
+        let query = embed("rust");
+        let expected = engine.search("rust", &query, SearchType::Hybrid, 3).unwrap();
+
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        let results = runtime
+            .block_on(Arc::new(engine).search_async("rust".to_string(), query, SearchType::Hybrid, 3))
+            .unwrap();
+        assert_eq!(texts(&results), texts(&expected));
+    }
+
+    #[test]
+    fn test_hybrid_limits() {
+        let engine = engine();
+        let query = embed("rust");
+        let all = engine.search("rust", &query, SearchType::Hybrid, usize::MAX).unwrap();
+        assert_eq!(all.len(), 4);
+        assert_eq!(engine.search("rust", &query, SearchType::Hybrid, 0).unwrap().len(), 4);
+        assert_eq!(all.iter().map(|r| r.rank).collect::<Vec<_>>(), vec![1, 2, 3, 4]);
+
+        let comb_sum = HybridConfig { strategy: FusionStrategy::CombSum, ..HybridConfig::default() };
+        let engine = engine.with_hybrid_config(comb_sum);
+        assert_eq!(engine.hybrid_config().strategy, FusionStrategy::CombSum);
+        assert!(engine.search("rust", &query, SearchType::Hybrid, 3).unwrap()[0].score <= 1.0);
+    }
+
+    #[test]
+    fn test_search_filtered() {
+        let mut engine = engine();
+        let mut extra = text_chunk("extra", "rust on page nine");
+        extra.metadata.page_number = 9;
+        engine.ingest(vec![extra], vec![embed("rust on page nine")]).unwrap();
+
+        let filter = MetadataFilter::page_range(Some(5), None);
+        let query = embed("rust");
+        for mode in [SearchType::Keyword, SearchType::Semantic, SearchType::Hybrid] {
+            let results = engine.search_filtered("rust", &query, mode, 10, &filter).unwrap();
+            assert_eq!(texts(&results), vec!["rust on page nine"]);
+            assert_eq!(results[0].rank, 1);
+        }
+        let all = engine.search_filtered("rust", &query, SearchType::Hybrid, usize::MAX, &filter).unwrap();
+        assert_eq!(texts(&all), vec!["rust on page nine"]);
+        let none = MetadataFilter::equals("source_file", "missing");
+        assert!(engine.search_filtered("rust", &query, SearchType::Hybrid, 10, &none).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_dedup() {
+        let embedder = MockEmbedder::new(16);
+        let mut engine = RagEngine::new().with_dedup(true);
+        let mut batch = chunks();
+        batch.push(text_chunk("copy", "RUST is a   systems programming language"));
+        engine.ingest_texts(batch, &embedder).unwrap();
+        assert_eq!(engine.len(), 4);
+
+        // Duplicates of chunks from earlier batches are dropped too
+        engine.ingest_texts(chunks()[..2].to_vec(), &embedder).unwrap();
+        assert_eq!((engine.len(), engine.vectors().len()), (4, 4));
+
+        let mut keep_all = RagEngine::new();
+        keep_all.ingest_texts(chunks(), &embedder).unwrap();
+        keep_all.ingest_texts(chunks(), &embedder).unwrap();
+        assert_eq!(keep_all.len(), 8);
+    }
+
+    /// Reverses the candidates and scores them by their new position
+    struct Reverse;
+
+    impl Reranker for Reverse {
+        fn rerank(&self, _query: &str, mut candidates: Vec<SearchResult>) -> Vec<SearchResult> {
+            candidates.reverse();
+            for (i, c) in candidates.iter_mut().enumerate() {
+                c.score = 10.0 - i as f32;
+            }
+            candidates
+        }
+    }
+
+    #[test]
+    fn test_reranker_top_n() {
+        let engine = engine();
+        let query = embed("rust search engines");
+        let plain = engine.search("rust search engines", &query, SearchType::Hybrid, 4).unwrap();
+        assert_eq!(plain.len(), 4);
+
+        let reranked = engine
+            .clone()
+            .with_reranker(Reverse, 2)
+            .search("rust search engines", &query, SearchType::Hybrid, 4)
+            .unwrap();
+        assert_eq!(reranked[0].chunk.id, plain[1].chunk.id);
+        assert_eq!(reranked[1].chunk.id, plain[0].chunk.id);
+        assert_eq!(reranked[0].score, 10.0);
+        // Results past top_n are untouched
+        assert_eq!(texts(&reranked[2..]), texts(&plain[2..]));
+        assert_eq!(reranked.iter().map(|r| r.rank).collect::<Vec<_>>(), vec![1, 2, 3, 4]);
+
+        let identity = engine.with_reranker(IdentityReranker, 10);
+        let same = identity.search("rust search engines", &query, SearchType::Hybrid, 4).unwrap();
+        assert_eq!(texts(&same), texts(&plain));
+    }
+
+    #[test]
+    fn test_synonyms() {
+        let engine = engine();
+        let query = embed("speedy");
+        assert!(engine.search("speedy", &query, SearchType::Keyword, 5).unwrap().is_empty());
+
+        let synonyms = SynonymConfig::new([("speedy".to_string(), vec!["fast".to_string()])].into());
+        let engine = engine.with_synonyms(synonyms.clone()).unwrap();
+        let results = engine.search("speedy", &query, SearchType::Keyword, 5).unwrap();
+        assert_eq!(texts(&results), vec![TEXTS[3]]);
+
+        assert!(RagEngine::new().with_synonyms(synonyms.clone().with_weight(0.0)).is_err());
+        assert!(RagEngine::new().with_synonyms(synonyms.with_weight(2.0)).is_err());
+    }
+
+    fn dated_engine(metric: DistanceMetric) -> RagEngine {
+        let mut chunks = chunk_text("old news", "a", 10, 0).unwrap();
+        chunks.extend(chunk_text("new news", "b", 10, 0).unwrap());
+        chunks[0].metadata.date = Some("2020-01-01".to_string());
+        chunks[1].metadata.date = Some("2024-01-01".to_string());
+        let mut engine = RagEngine::new().with_bm25(Bm25Index::new());
+        engine.vectors = VectorStore::with_metric(Vec::new(), metric).unwrap();
+        // The old chunk is the better match on its own
+        engine.ingest(chunks, vec![vec![1.0, 0.0], vec![0.8, 0.6]]).unwrap();
+        engine
+    }
+
+    #[test]
+    fn test_recency_reorders() {
+        let now = 19_723 * 86_400; // 2024-01-01
+        let engine = dated_engine(DistanceMetric::Cosine);
+        let before = engine.search("news", &[1.0, 0.0], SearchType::Semantic, 10).unwrap();
+        assert_eq!(texts(&before), vec!["old news", "new news"]);
+
+        let engine = engine.with_recency(RecencyConfig::with_half_life("date", 30.0, now)).unwrap();
+        let after = engine.search("news", &[1.0, 0.0], SearchType::Semantic, 10).unwrap();
+        assert_eq!(texts(&after), vec!["new news", "old news"]);
+        assert!((after[0].score - 0.8).abs() < 1e-6);
+        assert_eq!(after[1].rank, 2);
+
+        // Negative similarities are pushed further down, not towards 0
+        let negative = engine.search("news", &[-1.0, 0.0], SearchType::Semantic, 10).unwrap();
+        assert_eq!(texts(&negative), vec!["new news", "old news"]);
+        assert!(negative[1].score < -1.0);
+
+        for lambda in [-1.0, f32::NAN, f32::INFINITY] {
+            assert!(RagEngine::new().with_recency(RecencyConfig::new("date", lambda, now)).is_err());
+        }
+    }
+
+    #[test]
+    fn test_recency_with_distances() {
+        let now = 19_723 * 86_400;
+        let engine = dated_engine(DistanceMetric::Euclidean)
+            .with_recency(RecencyConfig::with_half_life("date", 30.0, now))
+            .unwrap();
+        let results = engine.search("news", &[1.0, 0.1], SearchType::Semantic, 10).unwrap();
+        // Older chunks look farther away
+        assert_eq!(texts(&results), vec!["new news", "old news"]);
+        assert!(results[0].score <= results[1].score);
+    }
+}
@@ -0,0 +1,151 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::types::ChunkMetadata;
+
+/// Condition on a chunk's `ChunkMetadata`, used to restrict a search to
+/// matching chunks before scoring.
+///
+/// Fields are named as in `ChunkMetadata` (`document_title`, `year`, ...);
+/// any other name is looked up in `custom_metadata`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MetadataFilter {
+    /// The field is present and equal to `value`
+    Equals { field: String, value: Value },
+    /// The field is present and not null
+    Exists { field: String },
+    /// `page_number` lies within the inclusive bounds; `None` means unbounded
+    PageRange { min: Option<u32>, max: Option<u32> },
+    /// Every filter matches
+    All(Vec<MetadataFilter>),
+    /// At least one filter matches
+    Any(Vec<MetadataFilter>),
+}
+
+impl MetadataFilter {
+    pub fn equals(field: &str, value: impl Into<Value>) -> Self {
+        MetadataFilter::Equals {
+            field: field.to_string(),
+            value: value.into(),
+        }
+    }
+
+    pub fn exists(field: &str) -> Self {
+        MetadataFilter::Exists {
+            field: field.to_string(),
+        }
+    }
+
+    pub fn page_range(min: Option<u32>, max: Option<u32>) -> Self {
+        MetadataFilter::PageRange { min, max }
+    }
+
+    pub fn matches(&self, metadata: &ChunkMetadata) -> bool {
+        match self {
+            MetadataFilter::Equals { field, value } => field_value(metadata, field).as_ref() == Some(value),
+            MetadataFilter::Exists { field } => field_value(metadata, field).is_some_and(|v| !v.is_null()),
+            MetadataFilter::PageRange { min, max } => {
+                min.is_none_or(|min| metadata.page_number >= min)
+                    && max.is_none_or(|max| metadata.page_number <= max)
+            }
+            MetadataFilter::All(filters) => filters.iter().all(|f| f.matches(metadata)),
+            MetadataFilter::Any(filters) => filters.iter().any(|f| f.matches(metadata)),
+        }
+    }
+}
+
+/// A metadata field as JSON, built-in fields first, then `custom_metadata`
+pub(crate) fn field_value(metadata: &ChunkMetadata, field: &str) -> Option<Value> {
+    let text = |value: &Option<String>| value.clone().map(Value::String);
+    match field {
+        "source_file" => text(&metadata.source_file),
+        "document_title" => text(&metadata.document_title),
+        "document_type" => text(&metadata.document_type),
+        "document_subtype" => text(&metadata.document_subtype),
+        "document_status" => text(&metadata.document_status),
+        "date" => text(&metadata.date),
+        "year" => metadata.year.map(Value::from),
+        "valid_from" => text(&metadata.valid_from),
+        "valid_to" => text(&metadata.valid_to),
+        "page_number" => Some(Value::from(metadata.page_number)),
+        "source_page_image_id" => text(&metadata.source_page_image_id),
+        "preceding_chunk_id" => text(&metadata.preceding_chunk_id),
+        "following_chunk_id" => text(&metadata.following_chunk_id),
+        _ => metadata.custom_metadata.get(field).cloned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata() -> ChunkMetadata {
+        ChunkMetadata {
+            document_title: Some("Handbook".to_string()),
+            year: Some(2021),
+            page_number: 7,
+            custom_metadata: [
+                ("team".to_string(), Value::from("search")),
+                ("reviewed".to_string(), Value::Null),
+            ]
+            .into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_equals() {
+        let meta = metadata();
+        assert!(MetadataFilter::equals("document_title", "Handbook").matches(&meta));
+        assert!(MetadataFilter::equals("year", 2021).matches(&meta));
+        assert!(MetadataFilter::equals("team", "search").matches(&meta));
+        assert!(!MetadataFilter::equals("team", "ads").matches(&meta));
+        assert!(!MetadataFilter::equals("year", "2021").matches(&meta));
+        assert!(!MetadataFilter::equals("missing", "x").matches(&meta));
+    }
+
+    #[test]
+    fn test_exists() {
+        let meta = metadata();
+        assert!(MetadataFilter::exists("team").matches(&meta));
+        assert!(MetadataFilter::exists("page_number").matches(&meta));
+        assert!(!MetadataFilter::exists("reviewed").matches(&meta));
+        assert!(!MetadataFilter::exists("document_type").matches(&meta));
+    }
+
+    #[test]
+    fn test_page_range() {
+        let meta = metadata();
+        assert!(MetadataFilter::page_range(Some(7), Some(7)).matches(&meta));
+        assert!(MetadataFilter::page_range(None, Some(10)).matches(&meta));
+        assert!(MetadataFilter::page_range(Some(1), None).matches(&meta));
+        assert!(MetadataFilter::page_range(None, None).matches(&meta));
+        assert!(!MetadataFilter::page_range(Some(8), None).matches(&meta));
+        assert!(!MetadataFilter::page_range(None, Some(6)).matches(&meta));
+    }
+
+    #[test]
+    fn test_combinators() {
+        let meta = metadata();
+        let title = MetadataFilter::equals("document_title", "Handbook");
+        let wrong = MetadataFilter::equals("team", "ads");
+        assert!(MetadataFilter::All(vec![title.clone(), MetadataFilter::page_range(Some(5), None)]).matches(&meta));
+        assert!(!MetadataFilter::All(vec![title.clone(), wrong.clone()]).matches(&meta));
+        assert!(MetadataFilter::Any(vec![wrong.clone(), title]).matches(&meta));
+        assert!(!MetadataFilter::Any(vec![wrong]).matches(&meta));
+        assert!(MetadataFilter::All(vec![]).matches(&meta));
+        assert!(!MetadataFilter::Any(vec![]).matches(&meta));
+    }
+
+    #[test]
+    fn test_serde() {
+        let filter = MetadataFilter::All(vec![
+            MetadataFilter::equals("year", 2021),
+            MetadataFilter::page_range(Some(1), None),
+        ]);
+        let json = serde_json::to_value(&filter).unwrap();
+        assert_eq!(json["all"][0]["equals"]["field"], "year");
+        assert_eq!(serde_json::from_value::<MetadataFilter>(json).unwrap(), filter);
+    }
+}
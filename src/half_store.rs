@@ -0,0 +1,148 @@
+use anyhow::Result;
+use half::f16;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+use crate::vector::{check_finite, rank_results, DistanceMetric, VectorStore};
+
+/// A `VectorStore` with embeddings stored as IEEE half-precision floats.
+///
+/// Halves memory with much less precision loss than `QuantizedStore`:
+/// relative error is about 0.05% per component. Values outside the f16 range
+/// (±65504) are clamped to it. Search converts each vector back to f32 and
+/// scores it with the original store's metric, so indices and result ordering
+/// match `VectorStore::search`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HalfStore {
+    /// Row-major values, `dimension` per vector
+    values: Vec<f16>,
+    count: usize,
+    dimension: usize,
+    metric: DistanceMetric,
+    /// Indices that were removed in the source store
+    removed: HashSet<usize>,
+}
+
+impl HalfStore {
+    pub(crate) fn from_store(store: &VectorStore) -> Self {
+        let max = f16::MAX.to_f32();
+        let values = store
+            .embeddings
+            .iter()
+            .flatten()
+            .map(|&x| f16::from_f32(x.clamp(-max, max)))
+            .collect();
+        let removed = (0..store.embeddings.len()).filter(|&idx| !store.contains(idx)).collect();
+
+        Self {
+            values,
+            count: store.embeddings.len(),
+            dimension: store.dimension,
+            metric: store.metric,
+            removed,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.count - self.removed.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    /// The embedding at `idx` converted back to f32
+    pub fn embedding(&self, idx: usize) -> Option<Vec<f32>> {
+        if idx >= self.count {
+            return None;
+        }
+        let mut out = vec![0.0; self.dimension];
+        self.decode_into(idx, &mut out);
+        Some(out)
+    }
+
+    fn decode_into(&self, idx: usize, out: &mut [f32]) {
+        let row = &self.values[idx * self.dimension..(idx + 1) * self.dimension];
+        for (value, half) in out.iter_mut().zip(row) {
+            *value = half.to_f32();
+        }
+    }
+
+    /// Same contract as `VectorStore::search`, scored on the f16 vectors
+    pub fn search(&self, query_embedding: &[f32], limit: usize) -> Result<Vec<(usize, f32)>> {
+        if self.count == 0 {
+            return Ok(vec![]);
+        }
+        if query_embedding.len() != self.dimension {
+            return Err(anyhow::anyhow!(
+                "Query has dimension {} but the store expects {}",
+                query_embedding.len(), self.dimension
+            ));
+        }
+        check_finite(query_embedding).map_err(|e| anyhow::anyhow!("Query {}", e))?;
+
+        let mut buffer = vec![0.0; self.dimension];
+        let scores: Vec<(usize, f32)> = (0..self.count)
+            .filter(|idx| !self.removed.contains(idx))
+            .map(|idx| {
+                self.decode_into(idx, &mut buffer);
+                (idx, self.metric.score(query_embedding, &buffer))
+            })
+            .collect();
+
+        Ok(rank_results(self.metric, scores, limit))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embedder::mock_embed;
+
+    #[test]
+    fn test_recall_at_10() {
+        let embeddings = (0..1000).map(|i| mock_embed(&format!("doc {}", i), 64)).collect();
+        let store = VectorStore::new(embeddings).unwrap();
+        let half = store.to_f16();
+
+        let mut found = 0;
+        for i in 0..20 {
+            let query = mock_embed(&format!("query {}", i), 64);
+            let exact = store.search(&query, 10).unwrap();
+            let approximate = half.search(&query, 10).unwrap();
+            found += exact.iter().filter(|(idx, _)| approximate.iter().any(|(a, _)| a == idx)).count();
+        }
+        let recall = found as f32 / 200.0;
+        assert!(recall >= 0.99, "recall@10 {}", recall);
+    }
+
+    #[test]
+    fn test_round_trip_within_tolerance() {
+        let store = VectorStore::new(vec![vec![0.1, -2.5, 1000.0, 1e-3], vec![1e6, -1e6, 0.0, 1.0]]).unwrap();
+        let half = store.to_f16();
+        for (a, b) in half.embedding(0).unwrap().iter().zip(&store.embeddings[0]) {
+            assert!((a - b).abs() <= b.abs() * 1e-3, "{} vs {}", a, b);
+        }
+        // Out of range values are clamped, not turned into infinity
+        assert_eq!(half.embedding(1).unwrap()[..2], [65504.0, -65504.0]);
+        assert_eq!(half.embedding(2), None);
+    }
+
+    #[test]
+    fn test_search_contract() {
+        let mut store = VectorStore::new(vec![vec![1.0, 0.0], vec![0.0, 1.0], vec![0.9, 0.1]]).unwrap();
+        store.remove(0).unwrap();
+        let half = store.to_f16();
+        assert_eq!((half.len(), half.dimension()), (2, 2));
+
+        let results = half.search(&[1.0, 0.0], 10).unwrap();
+        assert_eq!(results.iter().map(|&(idx, _)| idx).collect::<Vec<_>>(), vec![2, 1]);
+        assert!(half.search(&[1.0], 10).is_err());
+        assert!(half.search(&[f32::INFINITY, 0.0], 10).is_err());
+        assert!(VectorStore::default().to_f16().search(&[1.0], 10).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,321 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashSet};
+
+use crate::ranking::cmp_score;
+use crate::rng::SplitMix64;
+use crate::vector::{DistanceMetric, VectorStore};
+
+/// Tuning parameters for `HnswIndex`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HnswParams {
+    /// Neighbors kept per node on upper layers (level 0 keeps 2 * m).
+    /// Higher values = better recall, more memory
+    pub m: usize,
+
+    /// Candidate list size while inserting. Higher values = better graph, slower build
+    pub ef_construction: usize,
+
+    /// Candidate list size while searching; raised to `limit` if smaller
+    pub ef_search: usize,
+
+    /// Seed for level assignment, so builds are reproducible
+    pub seed: u64,
+}
+
+impl Default for HnswParams {
+    fn default() -> Self {
+        Self {
+            m: 16,
+            ef_construction: 200,
+            ef_search: 64,
+            seed: 42,
+        }
+    }
+}
+
+/// Approximate nearest neighbor index (Hierarchical Navigable Small World graph)
+/// over the live embeddings of a `VectorStore`.
+///
+/// Search visits a small part of the graph instead of every vector, trading
+/// exact results for speed on large stores. Results use the same values and
+/// ordering as `VectorStore::search` for the store's metric.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HnswIndex {
+    store: VectorStore,
+    params: HnswParams,
+    /// Per node, its neighbor lists for levels 0..=node_level
+    neighbors: Vec<Vec<Vec<usize>>>,
+    entry_point: Option<usize>,
+    max_level: usize,
+}
+
+impl HnswIndex {
+    pub fn build(store: VectorStore, params: HnswParams) -> Self {
+        let mut index = Self {
+            neighbors: vec![Vec::new(); store.embeddings.len()],
+            store,
+            params,
+            entry_point: None,
+            max_level: 0,
+        };
+
+        let mut rng = SplitMix64::new(index.params.seed);
+        let level_mult = 1.0 / (index.params.m.max(2) as f64).ln();
+
+        for idx in 0..index.store.embeddings.len() {
+            if !index.store.contains(idx) {
+                continue;
+            }
+            let level = (-rng.next_f64().max(f64::MIN_POSITIVE).ln() * level_mult) as usize;
+            index.insert(idx, level);
+        }
+        index
+    }
+
+    pub fn store(&self) -> &VectorStore {
+        &self.store
+    }
+
+    pub fn params(&self) -> &HnswParams {
+        &self.params
+    }
+
+    /// Approximate `limit` nearest neighbors of `query_embedding`, or every
+    /// reachable live embedding when `limit` is 0, as in `VectorStore::search`.
+    /// Errors if the query doesn't match the store's dimension or isn't finite.
+    pub fn search(&self, query_embedding: &[f32], limit: usize) -> Result<Vec<(usize, f32)>> {
+        let Some(mut entry) = self.entry_point else {
+            return Ok(vec![]);
+        };
+        self.store.check_query(query_embedding)?;
+        let limit = if limit == 0 { self.store.len() } else { limit };
+
+        for level in (1..=self.max_level).rev() {
+            entry = self.search_layer(query_embedding, &[entry], 1, level)[0].idx;
+        }
+
+        let ef = self.params.ef_search.max(limit);
+        Ok(self.search_layer(query_embedding, &[entry], ef, 0)
+            .into_iter()
+            .take(limit)
+            .map(|c| (c.idx, self.flip_similarity(c.distance)))
+            .collect())
+    }
+
+    fn insert(&mut self, idx: usize, level: usize) {
+        self.neighbors[idx] = vec![Vec::new(); level + 1];
+
+        let Some(mut entry) = self.entry_point else {
+            self.entry_point = Some(idx);
+            self.max_level = level;
+            return;
+        };
+
+        let query = self.store.embeddings[idx].clone();
+
+        for layer in (level + 1..=self.max_level).rev() {
+            entry = self.search_layer(&query, &[entry], 1, layer)[0].idx;
+        }
+
+        let mut entries = vec![entry];
+        for layer in (0..=level.min(self.max_level)).rev() {
+            let found = self.search_layer(&query, &entries, self.params.ef_construction, layer);
+            let max_neighbors = self.max_neighbors(layer);
+
+            let selected: Vec<usize> = found.iter().take(max_neighbors).map(|c| c.idx).collect();
+            for &neighbor in &selected {
+                self.neighbors[neighbor][layer].push(idx);
+                if self.neighbors[neighbor][layer].len() > max_neighbors {
+                    self.prune(neighbor, layer, max_neighbors);
+                }
+            }
+            self.neighbors[idx][layer] = selected;
+            entries = found.into_iter().map(|c| c.idx).collect();
+        }
+
+        if level > self.max_level {
+            self.entry_point = Some(idx);
+            self.max_level = level;
+        }
+    }
+
+    /// Keep only the `max_neighbors` closest neighbors of `node` on `layer`
+    fn prune(&mut self, node: usize, layer: usize, max_neighbors: usize) {
+        let base = &self.store.embeddings[node];
+        let mut scored: Vec<Candidate> = self.neighbors[node][layer]
+            .iter()
+            .map(|&n| Candidate {
+                distance: self.distance(base, &self.store.embeddings[n]),
+                idx: n,
+            })
+            .collect();
+        scored.sort();
+        self.neighbors[node][layer] = scored.into_iter().take(max_neighbors).map(|c| c.idx).collect();
+    }
+
+    /// Best-first search of one layer, returning up to `ef` nodes nearest first
+    fn search_layer(&self, query: &[f32], entries: &[usize], ef: usize, layer: usize) -> Vec<Candidate> {
+        let mut visited: HashSet<usize> = entries.iter().copied().collect();
+        let mut candidates: BinaryHeap<Reverse<Candidate>> = BinaryHeap::new();
+        let mut nearest: BinaryHeap<Candidate> = BinaryHeap::new();
+
+        for &idx in entries {
+            let candidate = Candidate {
+                distance: self.distance(query, &self.store.embeddings[idx]),
+                idx,
+            };
+            candidates.push(Reverse(candidate));
+            nearest.push(candidate);
+        }
+
+        while let Some(Reverse(current)) = candidates.pop() {
+            if let Some(furthest) = nearest.peek()
+                && nearest.len() >= ef
+                && current.distance > furthest.distance
+            {
+                break;
+            }
+
+            for &neighbor in self.neighbors[current.idx].get(layer).into_iter().flatten() {
+                if !visited.insert(neighbor) {
+                    continue;
+                }
+                let candidate = Candidate {
+                    distance: self.distance(query, &self.store.embeddings[neighbor]),
+                    idx: neighbor,
+                };
+                let closer = nearest.peek().is_none_or(|furthest| candidate < *furthest);
+                if nearest.len() < ef || closer {
+                    candidates.push(Reverse(candidate));
+                    nearest.push(candidate);
+                    if nearest.len() > ef {
+                        nearest.pop();
+                    }
+                }
+            }
+        }
+
+        nearest.into_sorted_vec()
+    }
+
+    fn max_neighbors(&self, layer: usize) -> usize {
+        if layer == 0 { self.params.m * 2 } else { self.params.m }
+    }
+
+    /// Metric turned into "lower is closer" for graph traversal
+    fn distance(&self, a: &[f32], b: &[f32]) -> f32 {
+        self.flip_similarity(self.store.metric.score(a, b))
+    }
+
+    /// Negate similarity metrics so lower is closer. The mapping is its own
+    /// inverse, so it also turns traversal distances back into the values
+    /// `VectorStore::search` returns.
+    fn flip_similarity(&self, distance: f32) -> f32 {
+        match self.store.metric {
+            DistanceMetric::Euclidean => distance,
+            DistanceMetric::Cosine | DistanceMetric::DotProduct => -distance,
+        }
+    }
+}
+
+/// A node with its distance to the current query, ordered nearest first
+/// (NaN distances last, ties by index)
+#[derive(Debug, Clone, Copy)]
+struct Candidate {
+    distance: f32,
+    idx: usize,
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Negated so lower distances sort first and NaN sorts last
+        cmp_score(-self.distance, -other.distance)
+            .reverse()
+            .then_with(|| self.idx.cmp(&other.idx))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embedder::mock_embed;
+
+    fn random_store(count: usize, dim: usize, metric: DistanceMetric) -> VectorStore {
+        let embeddings = (0..count).map(|i| mock_embed(&format!("doc {}", i), dim)).collect();
+        VectorStore::with_metric(embeddings, metric).unwrap()
+    }
+
+    fn recall_at_10(index: &HnswIndex) -> f32 {
+        let mut found = 0;
+        let queries = 50;
+        for i in 0..queries {
+            let query = mock_embed(&format!("query {}", i), index.store().dimension);
+            let exact = index.store().search(&query, 10).unwrap();
+            let approximate = index.search(&query, 10).unwrap();
+            found += exact.iter().filter(|(idx, _)| approximate.iter().any(|(a, _)| a == idx)).count();
+        }
+        found as f32 / (queries * 10) as f32
+    }
+
+    #[test]
+    fn test_recall_at_10() {
+        for metric in [DistanceMetric::Cosine, DistanceMetric::Euclidean, DistanceMetric::DotProduct] {
+            let index = HnswIndex::build(random_store(500, 16, metric), HnswParams::default());
+            let recall = recall_at_10(&index);
+            assert!(recall > 0.9, "{:?} recall@10 {}", metric, recall);
+        }
+    }
+
+    #[test]
+    fn test_results_match_store_values() {
+        let index = HnswIndex::build(random_store(200, 16, DistanceMetric::Euclidean), HnswParams::default());
+        let query = mock_embed("query", 16);
+        let results = index.search(&query, 5).unwrap();
+        assert_eq!(results.len(), 5);
+        assert!(results.windows(2).all(|w| w[0].1 <= w[1].1));
+        for &(idx, distance) in &results {
+            let expected = VectorStore::euclidean_distance(&query, &index.store().embeddings[idx]);
+            assert_eq!(distance, expected);
+        }
+    }
+
+    #[test]
+    fn test_build_is_deterministic() {
+        let build = || HnswIndex::build(random_store(300, 8, DistanceMetric::Cosine), HnswParams::default());
+        let (a, b) = (build(), build());
+        assert_eq!(a.neighbors, b.neighbors);
+        let query = mock_embed("query", 8);
+        assert_eq!(a.search(&query, 10).unwrap(), b.search(&query, 10).unwrap());
+    }
+
+    #[test]
+    fn test_skips_removed_and_checks_query() {
+        let mut store = random_store(50, 8, DistanceMetric::Cosine);
+        let query = store.embeddings[7].clone();
+        store.remove(7).unwrap();
+        let index = HnswIndex::build(store, HnswParams::default());
+        assert!(index.search(&query, 50).unwrap().iter().all(|&(idx, _)| idx != 7));
+
+        assert!(index.search(&[1.0], 5).is_err());
+        assert!(index.search(&[f32::NAN; 8], 5).is_err());
+        assert_eq!(index.search(&query, 0).unwrap().len(), 49);
+        let empty = HnswIndex::build(VectorStore::default(), HnswParams::default());
+        assert!(empty.search(&[1.0, 2.0], 5).unwrap().is_empty());
+    }
+}
@@ -1,31 +1,569 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+use crate::ranking::top_k;
+use crate::vector::VectorStore;
+
+/// How each result list is turned into fusable scores
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FusionStrategy {
+    /// Reciprocal Rank Fusion: only ranks matter, `1 / (k + rank)`
+    #[default]
+    Rrf,
+    /// Min-max normalize each list's scores to [0, 1] and sum them.
+    /// Keeps the score gaps that RRF throws away, so it works best when
+    /// scores are well calibrated
+    CombSum,
+    /// Standardize each list's scores to mean 0 and standard deviation 1,
+    /// then take the weighted sum. Suited to lists whose score distributions
+    /// differ wildly, such as unbounded BM25 scores against cosine similarities
+    ZScore,
+}
+
+/// How a document's contributions are combined when both lists return it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ScoreCombiner {
+    /// Add the contributions, rewarding agreement between the lists
+    #[default]
+    Sum,
+    /// Keep the larger contribution, so appearing in both lists isn't double-counted
+    Max,
+    /// Mean of the contributions from the lists the document appears in
+    Average,
+}
+
+/// Settings for fusing keyword and semantic results in `hybrid_search_with`
+#[derive(Debug, Clone, Copy)]
+pub struct HybridConfig {
+    pub strategy: FusionStrategy,
+
+    /// RRF constant: each list contributes `1 / (k + rank)` (unused by other strategies).
+    /// Smaller values give more weight to the top few ranks. Must be > 0
+    pub k: f32,
+
+    /// Weight of the semantic list in [0, 1]; the keyword list gets `1 - alpha`.
+    /// If one list is empty the other is used at full weight
+    pub alpha: f32,
+
+    /// Combination of a document's contributions from the two lists
+    pub combiner: ScoreCombiner,
+
+    /// Drop fused results scoring below this before taking the top `limit`
+    pub min_score: Option<f32>,
+}
+
+impl Default for HybridConfig {
+    fn default() -> Self {
+        Self {
+            strategy: FusionStrategy::Rrf,
+            k: 60.0,
+            alpha: 0.5,
+            combiner: ScoreCombiner::Sum,
+            min_score: None,
+        }
+    }
+}
+
+impl HybridConfig {
+    fn validate(&self) -> Result<()> {
+        if !(self.k.is_finite() && self.k > 0.0) {
+            return Err(anyhow::anyhow!("RRF k must be a positive number, got {}", self.k));
+        }
+        if !(0.0..=1.0).contains(&self.alpha) {
+            return Err(anyhow::anyhow!("alpha must be between 0 and 1, got {}", self.alpha));
+        }
+        Ok(())
+    }
+}
+
+/// A fused result together with the scores it had in each source list
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FusedResult {
+    pub idx: usize,
+    pub fused_score: f32,
+    /// Original keyword score, if the keyword list contributed this document
+    pub keyword_score: Option<f32>,
+    /// Original semantic score, if the semantic list contributed this document
+    pub semantic_score: Option<f32>,
+}
+
+/// Fuse keyword and semantic rankings with the default `HybridConfig`.
+/// If one list is empty, up to `limit` results come from the other alone;
+/// a `limit` of 0 returns every fused result
 pub fn hybrid_search(
     keyword_results: Vec<(usize, f32)>,
     semantic_results: Vec<(usize, f32)>,
     limit: usize,
 ) -> Vec<(usize, f32)> {
-    let mut combined_scores: HashMap<usize, f32> = HashMap::new();
-    let k = 60.0;
-
-    for (rank, (idx, _score)) in keyword_results.iter().enumerate() {
-        let score = 1.0 / (k + (rank + 1) as f32);
-        combined_scores
-            .entry(*idx)
-            .and_modify(|s| *s += score)
-            .or_insert(score);
-    }
-
-    for (rank, (idx, _score)) in semantic_results.iter().enumerate() {
-        let score = 1.0 / (k + (rank + 1) as f32);
-        combined_scores
-            .entry(*idx)
-            .and_modify(|s| *s += score)
-            .or_insert(score);
-    }
-
-    let mut final_results: Vec<(usize, f32)> = combined_scores.into_iter().collect();
-    final_results.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
-    final_results.truncate(limit);
-    final_results
-}
\ No newline at end of file
+    to_pairs(fuse_pair(&keyword_results, &semantic_results, limit, &HybridConfig::default()))
+}
+
+/// Fuse keyword and semantic rankings. Errors if the config is invalid
+pub fn hybrid_search_with(
+    keyword_results: Vec<(usize, f32)>,
+    semantic_results: Vec<(usize, f32)>,
+    limit: usize,
+    config: &HybridConfig,
+) -> Result<Vec<(usize, f32)>> {
+    Ok(to_pairs(hybrid_search_detailed(&keyword_results, &semantic_results, limit, config)?))
+}
+
+/// Like `hybrid_search_with`, but keeps each document's per-source scores
+pub fn hybrid_search_detailed(
+    keyword_results: &[(usize, f32)],
+    semantic_results: &[(usize, f32)],
+    limit: usize,
+    config: &HybridConfig,
+) -> Result<Vec<FusedResult>> {
+    config.validate()?;
+    Ok(fuse_pair(keyword_results, semantic_results, limit, config))
+}
+
+/// Fuse any number of ranked lists, e.g. from keyword, dense and sparse
+/// retrievers, with `config`'s strategy, combiner and `min_score`.
+///
+/// `weights` gives one weight per list (all equal if `None`) and replaces
+/// `config.alpha`. As with two lists, weights are rescaled over the lists
+/// that returned anything. Errors if the config is invalid, or on a weight
+/// count that doesn't match `lists` or a negative or non-finite weight
+pub fn fuse(
+    lists: &[Vec<(usize, f32)>],
+    weights: Option<&[f32]>,
+    limit: usize,
+    config: &HybridConfig,
+) -> Result<Vec<(usize, f32)>> {
+    config.validate()?;
+    let equal = vec![1.0; lists.len()];
+    let weights = weights.unwrap_or(&equal);
+    if weights.len() != lists.len() {
+        return Err(anyhow::anyhow!("Expected {} list weights, got {}", lists.len(), weights.len()));
+    }
+    if let Some(weight) = weights.iter().find(|w| !(w.is_finite() && **w >= 0.0)) {
+        return Err(anyhow::anyhow!("List weights must be non-negative numbers, got {}", weight));
+    }
+
+    let lists: Vec<&[(usize, f32)]> = lists.iter().map(Vec::as_slice).collect();
+    Ok(fuse_lists(&lists, weights, limit, config)
+        .into_iter()
+        .map(|(idx, score, _)| (idx, score))
+        .collect())
+}
+
+/// Rerank `candidates` with Maximal Marginal Relevance to reduce near-duplicates.
+///
+/// Repeatedly picks the candidate maximizing
+/// `lambda * relevance - (1 - lambda) * max_similarity_to_selected`, where
+/// similarity is cosine similarity between embeddings in `embeddings`.
+/// `lambda = 1.0` keeps the original order, lower values favor diversity.
+/// Similarity may be negative, rewarding candidates pointing away from the
+/// ones already picked. Candidates without a live embedding count as
+/// dissimilar (0.0) to everything.
+/// Returned scores are the original relevance scores, in MMR order.
+pub fn mmr_rerank(
+    candidates: &[(usize, f32)],
+    embeddings: &VectorStore,
+    lambda: f32,
+    limit: usize,
+) -> Vec<(usize, f32)> {
+    let vector = |idx: usize| embeddings.contains(idx).then(|| embeddings.embeddings[idx].as_slice());
+
+    let mut remaining: Vec<(usize, f32)> = candidates.to_vec();
+    let mut selected: Vec<(usize, f32)> = Vec::with_capacity(limit.min(remaining.len()));
+
+    while selected.len() < limit && !remaining.is_empty() {
+        let mut best = 0;
+        let mut best_score = f32::NEG_INFINITY;
+
+        for (pos, &(idx, relevance)) in remaining.iter().enumerate() {
+            let redundancy = match vector(idx) {
+                Some(candidate) => selected
+                    .iter()
+                    .filter_map(|&(chosen, _)| vector(chosen))
+                    .map(|chosen| VectorStore::cosine_similarity(candidate, chosen))
+                    .reduce(f32::max)
+                    .unwrap_or(0.0),
+                None => 0.0,
+            };
+            let score = lambda * relevance - (1.0 - lambda) * redundancy;
+            if score > best_score {
+                best = pos;
+                best_score = score;
+            }
+        }
+
+        selected.push(remaining.remove(best));
+    }
+
+    selected
+}
+
+/// Weighted fusion of two lists, keeping each document's per-list score
+fn fuse_pair(
+    keyword_results: &[(usize, f32)],
+    semantic_results: &[(usize, f32)],
+    limit: usize,
+    config: &HybridConfig,
+) -> Vec<FusedResult> {
+    let lists = [keyword_results, semantic_results];
+    fuse_lists(&lists, &[1.0 - config.alpha, config.alpha], limit, config)
+        .into_iter()
+        .map(|(idx, fused_score, originals)| FusedResult {
+            idx,
+            fused_score,
+            keyword_score: originals[0],
+            semantic_score: originals[1],
+        })
+        .collect()
+}
+
+/// Fuse `lists`, returning each document's fused score and its original
+/// score in every list. Weights are rescaled to sum to 1 over the non-empty
+/// lists (or split evenly if those sum to 0), so an empty list hands its
+/// weight to the others
+fn fuse_lists(
+    lists: &[&[(usize, f32)]],
+    weights: &[f32],
+    limit: usize,
+    config: &HybridConfig,
+) -> Vec<(usize, f32, Vec<Option<f32>>)> {
+    let live = lists.iter().filter(|list| !list.is_empty()).count();
+    if live == 0 {
+        return Vec::new();
+    }
+    let live_total: f32 = lists.iter().zip(weights).filter(|(list, _)| !list.is_empty()).map(|(_, w)| w).sum();
+
+    let mut combined: HashMap<usize, (f32, Vec<Option<f32>>)> = HashMap::new();
+    for (i, (results, &weight)) in lists.iter().zip(weights).enumerate() {
+        if results.is_empty() {
+            continue;
+        }
+        let weight = if live_total > 0.0 { weight / live_total } else { 1.0 / live as f32 };
+        if weight == 0.0 {
+            continue;
+        }
+        for ((idx, score), &(_, original)) in list_scores(results, config).into_iter().zip(*results) {
+            let (fused_score, originals) = combined.entry(idx).or_insert((0.0, vec![None; lists.len()]));
+            let contribution = weight * score;
+            let first = originals.iter().all(Option::is_none);
+            *fused_score = match config.combiner {
+                ScoreCombiner::Max if !first => fused_score.max(contribution),
+                ScoreCombiner::Max => contribution,
+                ScoreCombiner::Sum | ScoreCombiner::Average => *fused_score + contribution,
+            };
+            originals[i].get_or_insert(original);
+        }
+    }
+
+    if config.combiner == ScoreCombiner::Average {
+        for (fused_score, originals) in combined.values_mut() {
+            *fused_score /= originals.iter().flatten().count() as f32;
+        }
+    }
+
+    let fused = combined
+        .iter()
+        .map(|(&idx, &(score, _))| (idx, score))
+        .filter(|&(_, score)| config.min_score.is_none_or(|threshold| score >= threshold));
+    top_k(fused, limit)
+        .into_iter()
+        .map(|(idx, score)| (idx, score, combined.remove(&idx).map(|(_, originals)| originals).unwrap_or_default()))
+        .collect()
+}
+
+fn to_pairs(results: Vec<FusedResult>) -> Vec<(usize, f32)> {
+    results.into_iter().map(|r| (r.idx, r.fused_score)).collect()
+}
+
+/// Per-document scores from one ranked list under the configured strategy
+fn list_scores(results: &[(usize, f32)], config: &HybridConfig) -> Vec<(usize, f32)> {
+    match config.strategy {
+        FusionStrategy::Rrf => results
+            .iter()
+            .enumerate()
+            .map(|(rank, &(idx, _))| (idx, 1.0 / (config.k + (rank + 1) as f32)))
+            .collect(),
+        FusionStrategy::CombSum => min_max_normalize(results),
+        FusionStrategy::ZScore => z_score_normalize(results),
+    }
+}
+
+/// Standardize scores to mean 0 and standard deviation 1, flipping an
+/// ascending list so better results always score higher. If all scores are
+/// equal every document gets 0.0, the same as a document missing from the list
+fn z_score_normalize(results: &[(usize, f32)]) -> Vec<(usize, f32)> {
+    let (Some(&(_, first)), Some(&(_, last))) = (results.first(), results.last()) else {
+        return vec![];
+    };
+    let n = results.len() as f32;
+    let mean = results.iter().map(|&(_, s)| s).sum::<f32>() / n;
+    let std = (results.iter().map(|&(_, s)| (s - mean).powi(2)).sum::<f32>() / n).sqrt();
+    let sign = if first < last { -1.0 } else { 1.0 };
+
+    results
+        .iter()
+        .map(|&(idx, score)| {
+            if std <= f32::EPSILON {
+                return (idx, 0.0);
+            }
+            (idx, sign * (score - mean) / std)
+        })
+        .collect()
+}
+
+/// Scale scores to [0, 1] with the best result at 1. Lists arrive best first,
+/// so an ascending list (e.g. Euclidean distances) is flipped. If all scores
+/// are equal every document gets 1.0
+fn min_max_normalize(results: &[(usize, f32)]) -> Vec<(usize, f32)> {
+    let (Some(&(_, first)), Some(&(_, last))) = (results.first(), results.last()) else {
+        return vec![];
+    };
+    let (min, max) = results
+        .iter()
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), &(_, s)| (min.min(s), max.max(s)));
+    let range = max - min;
+    let ascending = first < last;
+
+    results
+        .iter()
+        .map(|&(idx, score)| {
+            if range <= f32::EPSILON {
+                return (idx, 1.0);
+            }
+            let normalized = (score - min) / range;
+            (idx, if ascending { 1.0 - normalized } else { normalized })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(results: &[(usize, f32)]) -> Vec<usize> {
+        results.iter().map(|&(idx, _)| idx).collect()
+    }
+
+    fn config(strategy: FusionStrategy) -> HybridConfig {
+        HybridConfig { strategy, ..HybridConfig::default() }
+    }
+
+    #[test]
+    fn test_rrf_rewards_agreement() {
+        let keyword = vec![(1, 9.0), (2, 5.0), (3, 1.0)];
+        let semantic = vec![(2, 0.9), (4, 0.8), (1, 0.1)];
+        let results = hybrid_search(keyword, semantic, 0);
+
+        assert_eq!(ids(&results)[..2], [2, 1]);
+        let expected = 0.5 / 62.0 + 0.5 / 61.0;
+        assert!((results[0].1 - expected).abs() < 1e-7);
+        assert_eq!(results.len(), 4);
+    }
+
+    #[test]
+    fn test_rrf_k_controls_top_weight() {
+        // Doc 1 tops one list and is fourth in the other, doc 2 is second in both
+        let keyword = vec![(1, 3.0), (2, 2.0), (3, 1.0)];
+        let semantic = vec![(4, 0.9), (2, 0.8), (5, 0.7), (1, 0.1)];
+        let sharp = HybridConfig { k: 0.5, ..HybridConfig::default() };
+        let flat = HybridConfig { k: 100.0, ..HybridConfig::default() };
+
+        let sharp = hybrid_search_with(keyword.clone(), semantic.clone(), 10, &sharp).unwrap();
+        let flat = hybrid_search_with(keyword, semantic, 10, &flat).unwrap();
+        assert_eq!(sharp[0].0, 1);
+        assert_eq!(flat[0].0, 2);
+
+        for k in [0.0, -1.0, f32::NAN, f32::INFINITY] {
+            let bad = HybridConfig { k, ..HybridConfig::default() };
+            assert!(hybrid_search_with(vec![], vec![], 5, &bad).is_err());
+        }
+    }
+
+    #[test]
+    fn test_alpha_weights_lists() {
+        let keyword = vec![(1, 5.0), (2, 1.0)];
+        let semantic = vec![(2, 0.9), (1, 0.2)];
+
+        let keyword_only = HybridConfig { alpha: 0.0, ..HybridConfig::default() };
+        let semantic_only = HybridConfig { alpha: 1.0, ..HybridConfig::default() };
+        let r = hybrid_search_with(keyword.clone(), semantic.clone(), 10, &keyword_only).unwrap();
+        assert_eq!(ids(&r), vec![1, 2]);
+        let r = hybrid_search_with(keyword.clone(), semantic.clone(), 10, &semantic_only).unwrap();
+        assert_eq!(ids(&r), vec![2, 1]);
+
+        // Equal weight and mirrored ranks tie
+        let r = hybrid_search_with(keyword, semantic, 10, &HybridConfig::default()).unwrap();
+        assert_eq!(r[0].1, r[1].1);
+
+        for alpha in [-0.1, 1.1, f32::NAN] {
+            let bad = HybridConfig { alpha, ..HybridConfig::default() };
+            assert!(hybrid_search_with(vec![], vec![], 5, &bad).is_err());
+        }
+    }
+
+    #[test]
+    fn test_one_list_empty() {
+        let keyword = vec![(3, 4.0), (1, 2.0), (2, 1.0)];
+        let only = hybrid_search(keyword.clone(), vec![], 2);
+        assert_eq!(ids(&only), vec![3, 1]);
+        // The lone list gets full weight even with alpha at 1
+        let cfg = HybridConfig { alpha: 1.0, ..HybridConfig::default() };
+        let only = hybrid_search_with(keyword, vec![], 10, &cfg).unwrap();
+        assert_eq!(ids(&only), vec![3, 1, 2]);
+        assert!((only[0].1 - 1.0 / 61.0).abs() < 1e-7);
+
+        assert!(hybrid_search(vec![], vec![], 10).is_empty());
+        assert_eq!(hybrid_search(vec![(0, 1.0)], vec![(1, 1.0)], usize::MAX).len(), 2);
+    }
+
+    #[test]
+    fn test_comb_sum_keeps_score_gaps() {
+        // Doc 1 is far ahead on keywords and barely behind on semantics
+        let keyword = vec![(1, 10.0), (2, 1.0), (3, 0.0)];
+        let semantic = vec![(2, 0.91), (1, 0.9), (3, 0.0)];
+
+        let rrf = hybrid_search_with(keyword.clone(), semantic.clone(), 10, &config(FusionStrategy::Rrf)).unwrap();
+        assert_eq!(rrf[0].1, rrf[1].1);
+
+        let comb = hybrid_search_with(keyword, semantic, 10, &config(FusionStrategy::CombSum)).unwrap();
+        assert_eq!(ids(&comb), vec![1, 2, 3]);
+        assert!(comb[0].1 <= 1.0 && comb[2].1 == 0.0);
+    }
+
+    #[test]
+    fn test_comb_sum_flips_distances_and_equal_scores() {
+        // Euclidean distances come smallest first
+        let semantic = vec![(5, 0.1), (6, 2.0)];
+        let cfg = HybridConfig { alpha: 1.0, ..config(FusionStrategy::CombSum) };
+        let r = hybrid_search_with(vec![], semantic, 10, &cfg).unwrap();
+        assert_eq!(r, vec![(5, 1.0), (6, 0.0)]);
+
+        let equal = hybrid_search_with(vec![(1, 2.0), (2, 2.0)], vec![], 10, &cfg).unwrap();
+        assert!(equal.iter().all(|&(_, score)| score == 1.0));
+    }
+
+    #[test]
+    fn test_mmr_demotes_near_duplicates() {
+        let store = VectorStore::new(vec![vec![1.0, 0.0], vec![0.99, 0.01], vec![0.0, 1.0]]).unwrap();
+        let candidates = vec![(0, 0.9), (1, 0.85), (2, 0.6)];
+
+        assert_eq!(mmr_rerank(&candidates, &store, 1.0, 3), candidates);
+        let diverse = mmr_rerank(&candidates, &store, 0.5, 3);
+        assert_eq!(ids(&diverse), vec![0, 2, 1]);
+        // Scores stay the original relevance
+        assert_eq!(diverse[1], (2, 0.6));
+        assert_eq!(mmr_rerank(&candidates, &store, 0.5, 2).len(), 2);
+        assert!(mmr_rerank(&candidates, &store, 0.5, 0).is_empty());
+    }
+
+    #[test]
+    fn test_mmr_missing_embeddings() {
+        let mut store = VectorStore::new(vec![vec![1.0, 0.0], vec![1.0, 0.0]]).unwrap();
+        store.remove(1).unwrap();
+        let candidates = vec![(0, 0.9), (1, 0.8), (7, 0.7)];
+        // Neither the removed nor the unknown index counts as a duplicate
+        assert_eq!(ids(&mmr_rerank(&candidates, &store, 0.5, 3)), vec![0, 1, 7]);
+    }
+
+    #[test]
+    fn test_mmr_negative_similarity() {
+        let store = VectorStore::new(vec![vec![1.0, 0.0], vec![-1.0, 0.0], vec![-0.5, 0.866]]).unwrap();
+        // Both follow-ups point away from 0, by 1 the most
+        let candidates = vec![(0, 0.9), (2, 0.5), (1, 0.5)];
+        assert_eq!(ids(&mmr_rerank(&candidates, &store, 0.5, 3)), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_detailed_keeps_sources() {
+        let keyword = vec![(1, 7.5), (2, 3.0)];
+        let semantic = vec![(1, 0.8), (3, 0.4)];
+        let fused = hybrid_search_detailed(&keyword, &semantic, 10, &HybridConfig::default()).unwrap();
+        let by_idx = |idx| fused.iter().find(|r| r.idx == idx).unwrap();
+
+        assert_eq!(fused[0].idx, 1);
+        assert_eq!((by_idx(1).keyword_score, by_idx(1).semantic_score), (Some(7.5), Some(0.8)));
+        assert_eq!((by_idx(2).keyword_score, by_idx(2).semantic_score), (Some(3.0), None));
+        assert_eq!((by_idx(3).keyword_score, by_idx(3).semantic_score), (None, Some(0.4)));
+
+        let pairs = hybrid_search_with(keyword, semantic, 10, &HybridConfig::default()).unwrap();
+        assert_eq!(pairs, to_pairs(fused));
+    }
+
+    #[test]
+    fn test_min_score_and_limit() {
+        let keyword = vec![(1, 3.0), (2, 2.0), (3, 1.0)];
+        let cfg = HybridConfig { min_score: Some(0.5), ..config(FusionStrategy::CombSum) };
+        let r = hybrid_search_with(keyword.clone(), vec![], 10, &cfg).unwrap();
+        assert_eq!(ids(&r), vec![1, 2]);
+        let r = hybrid_search_with(keyword, vec![], 1, &cfg).unwrap();
+        assert_eq!(ids(&r), vec![1]);
+    }
+
+    #[test]
+    fn test_z_score() {
+        let keyword = vec![(1, 40.0), (2, 20.0), (3, 0.0)];
+        let semantic = vec![(3, 0.9), (2, 0.5), (1, 0.1)];
+        let r = hybrid_search_with(keyword, semantic, 10, &config(FusionStrategy::ZScore)).unwrap();
+        // Symmetric lists cancel out
+        assert!(r.iter().all(|&(_, score)| score.abs() < 1e-6));
+
+        let single = hybrid_search_with(vec![(1, 3.0), (2, 1.0)], vec![], 10, &config(FusionStrategy::ZScore)).unwrap();
+        assert_eq!(single, vec![(1, 1.0), (2, -1.0)]);
+
+        let flat = hybrid_search_with(vec![(1, 3.0), (2, 3.0)], vec![], 10, &config(FusionStrategy::ZScore)).unwrap();
+        assert!(flat.iter().all(|&(_, score)| score == 0.0));
+    }
+
+    #[test]
+    fn test_combiners() {
+        let keyword = vec![(1, 1.0), (2, 0.5)];
+        let semantic = vec![(1, 1.0), (3, 0.5)];
+        let with = |combiner| {
+            let cfg = HybridConfig { combiner, ..HybridConfig::default() };
+            hybrid_search_with(keyword.clone(), semantic.clone(), 10, &cfg).unwrap()
+        };
+
+        let sum = with(ScoreCombiner::Sum);
+        assert!((sum[0].1 - 1.0 / 61.0).abs() < 1e-7);
+        let max = with(ScoreCombiner::Max);
+        assert!((max[0].1 - 0.5 / 61.0).abs() < 1e-7);
+        let average = with(ScoreCombiner::Average);
+        assert!((average[0].1 - 0.5 / 61.0).abs() < 1e-7);
+        // Docs in one list are unaffected by the combiner
+        assert_eq!(sum[1].1, max[1].1);
+        assert_eq!(sum[1].1, average[1].1);
+    }
+
+    #[test]
+    fn test_fuse_three_lists() {
+        let lists = vec![vec![(1, 1.0), (2, 0.5)], vec![(2, 0.9), (3, 0.1)], vec![(2, 5.0)]];
+        let r = fuse(&lists, None, 0, &HybridConfig::default()).unwrap();
+        assert_eq!(r[0].0, 2);
+        assert_eq!(r.len(), 3);
+
+        // All weight on the first list
+        let r = fuse(&lists, Some(&[1.0, 0.0, 0.0]), 0, &HybridConfig::default()).unwrap();
+        assert_eq!(ids(&r), vec![1, 2]);
+
+        // Two lists with the same weights match hybrid_search_with
+        let pair = vec![lists[0].clone(), lists[1].clone()];
+        let cfg = HybridConfig { alpha: 0.3, ..HybridConfig::default() };
+        assert_eq!(
+            fuse(&pair, Some(&[0.7, 0.3]), 0, &cfg).unwrap(),
+            hybrid_search_with(lists[0].clone(), lists[1].clone(), 0, &cfg).unwrap()
+        );
+
+        assert!(fuse(&lists, Some(&[1.0, 1.0]), 0, &HybridConfig::default()).is_err());
+        assert!(fuse(&lists, Some(&[1.0, -1.0, 1.0]), 0, &HybridConfig::default()).is_err());
+        assert!(fuse(&lists, Some(&[1.0, f32::NAN, 1.0]), 0, &HybridConfig::default()).is_err());
+        assert!(fuse(&[], None, 5, &HybridConfig::default()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_zero_weights_split_evenly() {
+        let lists = vec![vec![(1, 1.0)], vec![(2, 1.0)]];
+        let r = fuse(&lists, Some(&[0.0, 0.0]), 0, &HybridConfig::default()).unwrap();
+        assert_eq!(r.len(), 2);
+        assert_eq!(r[0].1, r[1].1);
+    }
+}
@@ -0,0 +1,125 @@
+use anyhow::{Context, Result};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use crate::types::Chunk;
+use crate::vector::check_finite;
+
+/// Read chunks from a JSON Lines file, one serialized `Chunk` per line.
+/// Blank lines are skipped; a malformed line errors with its line number.
+pub fn load_chunks_jsonl<P: AsRef<Path>>(path: P) -> Result<Vec<Chunk>> {
+    read_jsonl(path.as_ref(), "chunk", |_| Ok(()))
+}
+
+/// Write chunks as JSON Lines, one serialized `Chunk` per line
+pub fn write_chunks_jsonl<P: AsRef<Path>>(path: P, chunks: &[Chunk]) -> Result<()> {
+    write_jsonl(path.as_ref(), chunks, "chunks")
+}
+
+/// Read embeddings from a JSON Lines file, one array of numbers per line,
+/// in the same order as the chunks they belong to. Errors with the line
+/// number on a malformed line or a value out of f32 range.
+pub fn load_embeddings_jsonl<P: AsRef<Path>>(path: P) -> Result<Vec<Vec<f32>>> {
+    read_jsonl(path.as_ref(), "embedding", |emb: &Vec<f32>| {
+        check_finite(emb).map_err(|e| anyhow::anyhow!("Embedding {}", e))
+    })
+}
+
+/// Write embeddings as JSON Lines, one array of numbers per line
+pub fn write_embeddings_jsonl<P: AsRef<Path>>(path: P, embeddings: &[Vec<f32>]) -> Result<()> {
+    write_jsonl(path.as_ref(), embeddings, "embeddings")
+}
+
+/// Parse every non-blank line as a `T` and run `validate` on it
+fn read_jsonl<T: DeserializeOwned>(path: &Path, what: &str, validate: impl Fn(&T) -> Result<()>) -> Result<Vec<T>> {
+    let file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+
+    let mut items = Vec::new();
+    for (n, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.with_context(|| format!("Failed to read line {} of {}", n + 1, path.display()))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let item = serde_json::from_str(&line)
+            .map_err(anyhow::Error::from)
+            .and_then(|item| validate(&item).map(|_| item))
+            .with_context(|| format!("Invalid {} on line {} of {}", what, n + 1, path.display()))?;
+        items.push(item);
+    }
+    Ok(items)
+}
+
+fn write_jsonl<T: Serialize>(path: &Path, items: &[T], what: &str) -> Result<()> {
+    let write = || -> Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        for item in items {
+            serde_json::to_writer(&mut writer, item)?;
+            writer.write_all(b"\n")?;
+        }
+        writer.flush()?;
+        Ok(())
+    };
+    write().with_context(|| format!("Failed to write {} to {}", what, path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::chunk_text;
+    use std::path::PathBuf;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("edgerag-jsonl-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn test_chunks_round_trip() {
+        let chunks = chunk_text("one two three four five", "doc.txt", 2, 0).unwrap();
+        let path = temp_path("chunks");
+        write_chunks_jsonl(&path, &chunks).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 3);
+
+        let loaded = load_chunks_jsonl(&path).unwrap();
+        assert_eq!(loaded.len(), 3);
+        assert_eq!(loaded[1].id, "doc.txt-1");
+        assert_eq!(loaded[1].as_text(), "three four");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_embeddings_round_trip() {
+        let embeddings = vec![vec![0.25, -1.5], vec![1e-7, 3.0]];
+        let path = temp_path("embeddings");
+        write_embeddings_jsonl(&path, &embeddings).unwrap();
+        assert_eq!(load_embeddings_jsonl(&path).unwrap(), embeddings);
+
+        // Blank lines are skipped
+        std::fs::write(&path, "[1.0]\n\n  \n[2.0]\n").unwrap();
+        assert_eq!(load_embeddings_jsonl(&path).unwrap(), vec![vec![1.0], vec![2.0]]);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_errors_name_the_line() {
+        let path = temp_path("bad");
+        std::fs::write(&path, "[1.0]\n\n[oops]\n").unwrap();
+        let err = format!("{:#}", load_embeddings_jsonl(&path).unwrap_err());
+        assert!(err.contains("Invalid embedding on line 3"), "{}", err);
+
+        std::fs::write(&path, "[1.0]\n[1e39]\n").unwrap();
+        let err = format!("{:#}", load_embeddings_jsonl(&path).unwrap_err());
+        assert!(err.contains("line 2"), "{}", err);
+
+        std::fs::write(&path, "{\"id\": 1}\n").unwrap();
+        let err = format!("{:#}", load_chunks_jsonl(&path).unwrap_err());
+        assert!(err.contains("Invalid chunk on line 1"), "{}", err);
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(load_chunks_jsonl(temp_path("missing")).unwrap_err().to_string().contains("Failed to open"));
+        let unwritable = temp_path("missing-dir").join("out.jsonl");
+        assert!(write_chunks_jsonl(unwritable, &[]).is_err());
+    }
+}
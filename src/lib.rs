@@ -1,12 +1,70 @@
 mod types;
 mod vector;
 mod bm25;
+#[cfg(feature = "lru")]
+mod cache;
+mod cluster;
+mod embedder;
+mod engine;
+mod filter;
+#[cfg(feature = "f16")]
+mod half_store;
 mod hybrid;
+mod jsonl;
+mod multivector;
+mod quantized;
+mod ranking;
+mod recency;
+mod reranker;
+mod rng;
+mod shared;
+mod sparse;
+mod synonyms;
+mod tokenizer;
+mod trace;
+#[cfg(feature = "hnsw")]
+mod hnsw;
+#[cfg(feature = "mmap")]
+mod mmap;
 
 use wasm_bindgen::prelude::*;
-pub use types::{Chunk, SearchResult, SearchType};
-use vector::VectorStore;
-use bm25::Bm25Index;
+pub use types::{
+    chunk_by_sentences, chunk_text, dedup_chunks, Chunk, ChunkMetadata, SearchResult, SearchType,
+    VersionedSearchResult, SEARCH_RESULT_SCHEMA_VERSION,
+};
+pub use vector::{DistanceMetric, VectorStore};
+pub use cluster::UNASSIGNED_CLUSTER;
+pub use hybrid::{
+    fuse, hybrid_search, hybrid_search_detailed, hybrid_search_with, mmr_rerank, FusedResult, FusionStrategy,
+    HybridConfig, ScoreCombiner,
+};
+pub use multivector::{MultiVectorStore, VectorAggregation};
+pub use quantized::QuantizedStore;
+#[cfg(feature = "f16")]
+pub use half_store::HalfStore;
+#[cfg(feature = "mmap")]
+pub use mmap::MmapVectorStore;
+pub use embedder::{mock_embed, Embedder, MockEmbedder};
+pub use engine::{resolve_results, RagEngine};
+pub use filter::MetadataFilter;
+pub use jsonl::{load_chunks_jsonl, load_embeddings_jsonl, write_chunks_jsonl, write_embeddings_jsonl};
+pub use recency::RecencyConfig;
+pub use reranker::{IdentityReranker, Reranker};
+pub use bm25::{
+    extract_snippet, highlight, Bm25Index, Bm25Params, Bm25ParamsBuilder, IdfVariant, IndexStats,
+    ScoreExplanation, TermExplanation,
+};
+#[cfg(feature = "hnsw")]
+pub use hnsw::{HnswIndex, HnswParams};
+pub use shared::SharedIndex;
+pub use sparse::{sparse_cosine, sparse_dot, SparseVector, SparseVectorStore};
+pub use synonyms::SynonymConfig;
+pub use tokenizer::{
+    DefaultTokenizer, Language, NgramTokenizer, StopwordFilter, Tokenizer, ENGLISH_STOPWORDS, FRENCH_STOPWORDS,
+    GERMAN_STOPWORDS, SPANISH_STOPWORDS,
+};
+#[cfg(feature = "unicode-normalization")]
+pub use tokenizer::UnicodeForm;
 
 #[wasm_bindgen]
 pub struct EdgeRAG {
@@ -15,6 +73,12 @@ pub struct EdgeRAG {
     chunks: Vec<Chunk>,
 }
 
+impl Default for EdgeRAG {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[wasm_bindgen]
 impl EdgeRAG {
     #[wasm_bindgen(constructor)]
@@ -51,23 +115,23 @@ impl EdgeRAG {
     }
 
     #[wasm_bindgen]
-    pub fn search(&self, query_embedding: Vec<f32>, query_text: &str, k: usize) -> JsValue {
-        let vector_results = self.vectors.search(&query_embedding, k * 2);
-        let bm25_results = self.bm25.search(query_text, k * 2);
+    pub fn search(&self, query_embedding: Vec<f32>, query_text: &str, k: usize) -> Result<JsValue, JsValue> {
+        // Vectors and chunks are loaded separately; results would point at the
+        // wrong chunks if they came from different builds
+        if self.vectors.embeddings.len() != self.chunks.len() {
+            return Err(JsValue::from_str(&format!(
+                "Search failed: {} vectors loaded but {} chunks",
+                self.vectors.embeddings.len(), self.chunks.len()
+            )));
+        }
+        let vector_results = self.vectors.search(&query_embedding, k.saturating_mul(2))
+            .map_err(|e| JsValue::from_str(&format!("Search failed: {}", e)))?;
+        let bm25_results = self.bm25.search(query_text, k.saturating_mul(2));
         
-        let fused = hybrid::hybrid_search(vector_results, bm25_results, k);
+        let fused = hybrid::hybrid_search(bm25_results, vector_results, k);
         
-        let results: Vec<SearchResult> = fused
-            .into_iter()
-            .filter_map(|(idx, score)| {
-                self.chunks.get(idx).map(|chunk| SearchResult {
-                    score,
-                    chunk: chunk.clone(),
-                    search_type: SearchType::Hybrid,
-                })
-            })
-            .collect();
+        let results = resolve_results(&fused, &self.chunks, SearchType::Hybrid);
 
-        serde_wasm_bindgen::to_value(&results).unwrap()
+        Ok(serde_wasm_bindgen::to_value(&results).unwrap())
     }
 }
\ No newline at end of file
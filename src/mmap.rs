@@ -0,0 +1,197 @@
+use anyhow::{Context, Result};
+use memmap2::Mmap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::vector::{check_finite, rank_results, DistanceMetric, VectorStore};
+
+/// Read-only vector store backed by a memory-mapped file, for embedding sets
+/// that don't fit comfortably in RAM.
+///
+/// File format: no header, just `count * dimension` little-endian f32 values
+/// in row-major order (embedding 0, then embedding 1, ...). The dimension is
+/// supplied when opening, and the file length must be a multiple of
+/// `dimension * 4` bytes. `write_file` produces this format.
+#[derive(Debug)]
+pub struct MmapVectorStore {
+    mmap: Mmap,
+    dimension: usize,
+    count: usize,
+    metric: DistanceMetric,
+}
+
+impl MmapVectorStore {
+    pub fn open<P: AsRef<Path>>(path: P, dimension: usize) -> Result<Self> {
+        let path = path.as_ref();
+        if dimension == 0 {
+            return Err(anyhow::anyhow!("Dimension must be greater than 0"));
+        }
+
+        let file = File::open(path)
+            .with_context(|| format!("Failed to open embeddings file {}", path.display()))?;
+        // SAFETY: the mapping is read-only; callers must not truncate or
+        // rewrite the file while the store is open.
+        let mmap = unsafe { Mmap::map(&file) }
+            .with_context(|| format!("Failed to memory-map {}", path.display()))?;
+
+        let row_bytes = dimension * 4;
+        if mmap.len() % row_bytes != 0 {
+            return Err(anyhow::anyhow!(
+                "{} is {} bytes, which is not a multiple of dimension {} * 4",
+                path.display(), mmap.len(), dimension
+            ));
+        }
+
+        Ok(Self {
+            count: mmap.len() / row_bytes,
+            mmap,
+            dimension,
+            metric: DistanceMetric::Cosine,
+        })
+    }
+
+    /// Write embeddings in the format `open` expects
+    pub fn write_file<P: AsRef<Path>>(path: P, embeddings: &[Vec<f32>]) -> Result<()> {
+        let path = path.as_ref();
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create embeddings file {}", path.display()))?;
+        let mut writer = BufWriter::new(file);
+
+        let dimension = embeddings.first().map_or(0, |e| e.len());
+        for (i, emb) in embeddings.iter().enumerate() {
+            if emb.len() != dimension {
+                return Err(anyhow::anyhow!(
+                    "Embedding {} has dimension {} but expected {}",
+                    i, emb.len(), dimension
+                ));
+            }
+            for value in emb {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn with_metric(mut self, metric: DistanceMetric) -> Self {
+        self.metric = metric;
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    pub fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    /// Copy the embedding at `idx` out of the mapping
+    pub fn embedding(&self, idx: usize) -> Option<Vec<f32>> {
+        if idx >= self.count {
+            return None;
+        }
+        let mut out = vec![0.0; self.dimension];
+        self.read_row(idx, &mut out);
+        Some(out)
+    }
+
+    fn read_row(&self, idx: usize, out: &mut [f32]) {
+        let row_bytes = self.dimension * 4;
+        let row = &self.mmap[idx * row_bytes..(idx + 1) * row_bytes];
+        for (value, bytes) in out.iter_mut().zip(row.chunks_exact(4)) {
+            *value = f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+    }
+
+    /// Same contract as `VectorStore::search`
+    pub fn search(&self, query_embedding: &[f32], limit: usize) -> Result<Vec<(usize, f32)>> {
+        if self.count == 0 {
+            return Ok(vec![]);
+        }
+        if query_embedding.len() != self.dimension {
+            return Err(anyhow::anyhow!(
+                "Query has dimension {} but the store expects {}",
+                query_embedding.len(), self.dimension
+            ));
+        }
+        check_finite(query_embedding).map_err(|e| anyhow::anyhow!("Query {}", e))?;
+
+        let mut row = vec![0.0; self.dimension];
+        let scores = (0..self.count).map(|idx| {
+            self.read_row(idx, &mut row);
+            (idx, self.metric.score(query_embedding, &row))
+        });
+        Ok(rank_results(self.metric, scores, limit))
+    }
+}
+
+impl VectorStore {
+    /// Open a memory-mapped store, see `MmapVectorStore` for the file format
+    pub fn open_mmap<P: AsRef<Path>>(path: P, dimension: usize) -> Result<MmapVectorStore> {
+        MmapVectorStore::open(path, dimension)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("edgerag-mmap-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn test_write_and_open() {
+        let embeddings = vec![vec![1.0, 0.0, 0.0], vec![0.0, 1.0, 0.0], vec![0.7, 0.7, 0.0]];
+        let path = temp_path("roundtrip");
+        MmapVectorStore::write_file(&path, &embeddings).unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 36);
+
+        let mmap = VectorStore::open_mmap(&path, 3).unwrap();
+        assert_eq!((mmap.len(), mmap.dimension()), (3, 3));
+        assert_eq!(mmap.embedding(2), Some(vec![0.7, 0.7, 0.0]));
+        assert_eq!(mmap.embedding(3), None);
+
+        let store = VectorStore::new(embeddings.clone()).unwrap();
+        let query = [0.9, 0.3, 0.1];
+        assert_eq!(mmap.search(&query, 3).unwrap(), store.search(&query, 3).unwrap());
+
+        let euclidean = MmapVectorStore::open(&path, 3).unwrap().with_metric(DistanceMetric::Euclidean);
+        let in_memory = VectorStore::with_metric(embeddings, DistanceMetric::Euclidean).unwrap();
+        assert_eq!(euclidean.search(&query, 2).unwrap(), in_memory.search(&query, 2).unwrap());
+        assert!(mmap.search(&[1.0], 2).is_err());
+        assert!(mmap.search(&[f32::NAN, 0.0, 0.0], 2).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_open_rejects_bad_files() {
+        let path = temp_path("bad");
+        std::fs::write(&path, [0u8; 10]).unwrap();
+        let err = MmapVectorStore::open(&path, 2).unwrap_err();
+        assert!(err.to_string().contains("not a multiple of dimension 2"));
+        assert!(MmapVectorStore::open(&path, 0).is_err());
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(MmapVectorStore::open(temp_path("missing"), 2).is_err());
+        assert!(MmapVectorStore::write_file(&path, &[vec![1.0, 2.0], vec![1.0]]).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_empty_file() {
+        let path = temp_path("empty");
+        MmapVectorStore::write_file(&path, &[]).unwrap();
+        let mmap = MmapVectorStore::open(&path, 4).unwrap();
+        assert!(mmap.is_empty());
+        assert!(mmap.search(&[1.0, 0.0, 0.0, 0.0], 5).unwrap().is_empty());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,169 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::vector::{check_finite, rank_results, DistanceMetric, VectorStore};
+
+/// How `MultiVectorStore` combines the scores of a document's vectors
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum VectorAggregation {
+    /// Best-matching vector: highest similarity, or lowest `Euclidean` distance
+    #[default]
+    Max,
+    /// Sum over all of the document's vectors, favoring documents that match
+    /// in many places. For `Euclidean` this is a total distance (lower is better)
+    Sum,
+}
+
+/// Documents represented by several embeddings each (e.g. one per passage),
+/// scored by late interaction: every vector is compared with the query and
+/// the per-vector scores are aggregated per document.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MultiVectorStore {
+    store: VectorStore,
+    /// doc_id of each vector in `store`
+    owners: Vec<usize>,
+    aggregation: VectorAggregation,
+}
+
+impl MultiVectorStore {
+    pub fn new(metric: DistanceMetric, aggregation: VectorAggregation) -> Self {
+        let mut store = VectorStore::default();
+        store.metric = metric;
+        Self {
+            store,
+            owners: Vec::new(),
+            aggregation,
+        }
+    }
+
+    /// Add the vectors of `doc_id`. Calling it again for the same doc_id adds
+    /// more vectors to that document. Nothing is added if any vector fails
+    /// validation (dimension mismatch, NaN or infinity).
+    pub fn add_document(&mut self, doc_id: usize, vectors: Vec<Vec<f32>>) -> Result<()> {
+        let dimension = if self.owners.is_empty() {
+            vectors.first().map_or(0, |v| v.len())
+        } else {
+            self.store.dimension
+        };
+        for (i, vector) in vectors.iter().enumerate() {
+            if vector.len() != dimension {
+                return Err(anyhow::anyhow!(
+                    "Vector {} of document {} has dimension {} but expected {}",
+                    i, doc_id, vector.len(), dimension
+                ));
+            }
+            check_finite(vector).map_err(|e| anyhow::anyhow!("Vector {} of document {} {}", i, doc_id, e))?;
+        }
+
+        for vector in vectors {
+            self.store.add(vector)?;
+            self.owners.push(doc_id);
+        }
+        Ok(())
+    }
+
+    /// Number of distinct documents
+    pub fn len(&self) -> usize {
+        let mut docs = self.owners.clone();
+        docs.sort_unstable();
+        docs.dedup();
+        docs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.owners.is_empty()
+    }
+
+    /// Total number of vectors across all documents
+    pub fn vector_count(&self) -> usize {
+        self.owners.len()
+    }
+
+    pub fn aggregation(&self) -> VectorAggregation {
+        self.aggregation
+    }
+
+    /// The `limit` best documents as `(doc_id, aggregated_score)`, ordered
+    /// like `VectorStore::search` for the store's metric
+    pub fn search(&self, query_embedding: &[f32], limit: usize) -> Result<Vec<(usize, f32)>> {
+        if self.owners.is_empty() {
+            return Ok(vec![]);
+        }
+        self.store.check_query(query_embedding)?;
+
+        let metric = self.store.metric;
+        let mut aggregated: HashMap<usize, f32> = HashMap::new();
+        for (embedding, &doc_id) in self.store.embeddings.iter().zip(&self.owners) {
+            let score = metric.score(query_embedding, embedding);
+            aggregated
+                .entry(doc_id)
+                .and_modify(|total| {
+                    *total = match self.aggregation {
+                        VectorAggregation::Sum => *total + score,
+                        VectorAggregation::Max if metric.reaches(score, *total) => score,
+                        VectorAggregation::Max => *total,
+                    }
+                })
+                .or_insert(score);
+        }
+
+        Ok(rank_results(metric, aggregated, limit))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_max_picks_best_vector() {
+        let mut store = MultiVectorStore::new(DistanceMetric::Cosine, VectorAggregation::Max);
+        store.add_document(10, vec![vec![0.0, 1.0], vec![1.0, 0.0]]).unwrap();
+        store.add_document(20, vec![vec![0.7, 0.7], vec![0.6, 0.8]]).unwrap();
+
+        let results = store.search(&[1.0, 0.0], 10).unwrap();
+        assert_eq!(results[0].0, 10);
+        assert!((results[0].1 - 1.0).abs() < 1e-6);
+        assert!((results[1].1 - VectorStore::cosine_similarity(&[1.0, 0.0], &[0.7, 0.7])).abs() < 1e-6);
+        assert_eq!((store.len(), store.vector_count()), (2, 4));
+    }
+
+    #[test]
+    fn test_sum_favors_many_matches() {
+        let mut store = MultiVectorStore::new(DistanceMetric::DotProduct, VectorAggregation::Sum);
+        store.add_document(0, vec![vec![1.0, 0.0]]).unwrap();
+        store.add_document(1, vec![vec![0.6, 0.0], vec![0.6, 0.0]]).unwrap();
+        let results = store.search(&[1.0, 0.0], 10).unwrap();
+        assert_eq!(results[0], (1, 1.2));
+
+        let mut max = MultiVectorStore::new(DistanceMetric::DotProduct, VectorAggregation::Max);
+        max.add_document(0, vec![vec![1.0, 0.0]]).unwrap();
+        max.add_document(1, vec![vec![0.6, 0.0], vec![0.6, 0.0]]).unwrap();
+        assert_eq!(max.search(&[1.0, 0.0], 10).unwrap()[0], (0, 1.0));
+    }
+
+    #[test]
+    fn test_euclidean_max_is_nearest() {
+        let mut store = MultiVectorStore::new(DistanceMetric::Euclidean, VectorAggregation::Max);
+        store.add_document(0, vec![vec![5.0, 0.0], vec![0.5, 0.0]]).unwrap();
+        store.add_document(1, vec![vec![1.0, 0.0]]).unwrap();
+        assert_eq!(store.search(&[0.0, 0.0], 10).unwrap(), vec![(0, 0.5), (1, 1.0)]);
+    }
+
+    #[test]
+    fn test_add_document_is_all_or_nothing() {
+        let mut store = MultiVectorStore::new(DistanceMetric::Cosine, VectorAggregation::Max);
+        store.add_document(0, vec![vec![1.0, 0.0]]).unwrap();
+        assert!(store.add_document(1, vec![vec![1.0, 0.0], vec![1.0]]).is_err());
+        assert!(store.add_document(1, vec![vec![f32::NAN, 0.0]]).is_err());
+        assert_eq!(store.vector_count(), 1);
+
+        // More vectors for an existing document
+        store.add_document(0, vec![vec![0.0, 1.0]]).unwrap();
+        assert_eq!((store.len(), store.vector_count()), (1, 2));
+        assert!(store.search(&[1.0], 5).is_err());
+        assert!(MultiVectorStore::default().search(&[1.0], 5).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,174 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+
+/// Order scores so that NaN ranks below every number. Without this, NaN
+/// would compare above +inf under `total_cmp` and float to the top
+pub(crate) fn cmp_score(a: f32, b: f32) -> Ordering {
+    match (a.is_nan(), b.is_nan()) {
+        (true, true) => Ordering::Equal,
+        (true, false) => Ordering::Less,
+        (false, true) => Ordering::Greater,
+        (false, false) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+    }
+}
+
+/// A scored document ordered so that "greater" means ranked higher:
+/// higher score first, ties broken by ascending doc_id, NaN scores last
+#[derive(Debug, Clone, Copy)]
+struct Ranked(usize, f32);
+
+impl PartialEq for Ranked {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Ranked {}
+
+impl PartialOrd for Ranked {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Ranked {
+    fn cmp(&self, other: &Self) -> Ordering {
+        cmp_score(self.1, other.1).then_with(|| other.0.cmp(&self.0))
+    }
+}
+
+/// Best first: descending score, ties to the lower doc_id, NaN last
+fn better_first(a: &(usize, f32), b: &(usize, f32)) -> Ordering {
+    Ranked(b.0, b.1).cmp(&Ranked(a.0, a.1))
+}
+
+/// Select the `limit` best results in descending score order using a bounded
+/// min-heap, so only O(N log limit) work is done instead of sorting everything.
+/// A `limit` of 0 means no limit: every result is sorted and returned
+pub(crate) fn top_k(scores: impl IntoIterator<Item = (usize, f32)>, limit: usize) -> Vec<(usize, f32)> {
+    if limit == 0 {
+        let mut all: Vec<(usize, f32)> = scores.into_iter().collect();
+        all.sort_unstable_by(better_first);
+        return all;
+    }
+
+    // Callers may pass a huge limit to mean "everything", so size the heap
+    // by what the iterator can actually yield
+    let scores = scores.into_iter();
+    let capacity = limit.min(scores.size_hint().0).saturating_add(1);
+    let mut heap: BinaryHeap<Reverse<Ranked>> = BinaryHeap::with_capacity(capacity);
+    for (doc_id, score) in scores {
+        let candidate = Ranked(doc_id, score);
+        if heap.len() < limit {
+            heap.push(Reverse(candidate));
+        } else if let Some(Reverse(worst)) = heap.peek()
+            && candidate > *worst
+        {
+            heap.pop();
+            heap.push(Reverse(candidate));
+        }
+    }
+
+    heap.into_sorted_vec()
+        .into_iter()
+        .map(|Reverse(Ranked(doc_id, score))| (doc_id, score))
+        .collect()
+}
+
+/// Yield every result in descending score order (same tie-breaking as
+/// `top_k`). Heapifying is O(N); each item then costs O(log N), so taking
+/// only the first few is cheaper than a full sort.
+pub(crate) fn ranked(scores: Vec<(usize, f32)>) -> impl Iterator<Item = (usize, f32)> {
+    let mut heap: BinaryHeap<Ranked> = scores.into_iter().map(|(doc_id, score)| Ranked(doc_id, score)).collect();
+    std::iter::from_fn(move || heap.pop().map(|Ranked(doc_id, score)| (doc_id, score)))
+}
+
+/// Select the `limit` lowest-scoring results in ascending order (for
+/// distances), with the same tie-breaking as `top_k`
+pub(crate) fn bottom_k(scores: impl IntoIterator<Item = (usize, f32)>, limit: usize) -> Vec<(usize, f32)> {
+    // Negation is exact for floats, so this round-trips the original scores
+    top_k(scores.into_iter().map(|(idx, score)| (idx, -score)), limit)
+        .into_iter()
+        .map(|(idx, score)| (idx, -score))
+        .collect()
+}
+
+/// Like `top_k`, but reorders `results` in place and truncates it, so a
+/// reused buffer needs no new allocation
+pub(crate) fn top_k_in_place(results: &mut Vec<(usize, f32)>, limit: usize) {
+    if limit != 0 && limit < results.len() {
+        results.select_nth_unstable_by(limit - 1, better_first);
+        results.truncate(limit);
+    }
+    results.sort_unstable_by(better_first);
+}
+
+/// In-place counterpart of `bottom_k`
+pub(crate) fn bottom_k_in_place(results: &mut Vec<(usize, f32)>, limit: usize) {
+    for (_, score) in results.iter_mut() {
+        *score = -*score;
+    }
+    top_k_in_place(results, limit);
+    for (_, score) in results.iter_mut() {
+        *score = -*score;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_top_k_orders_and_limits() {
+        let scores = vec![(0, 0.5), (1, 2.0), (2, 1.0), (3, 3.0), (4, 0.1)];
+        assert_eq!(top_k(scores.clone(), 3), vec![(3, 3.0), (1, 2.0), (2, 1.0)]);
+        assert_eq!(top_k(scores.clone(), 10).len(), 5);
+        assert_eq!(top_k(scores.clone(), 0), top_k(scores, 5));
+        assert!(top_k(Vec::new(), 3).is_empty());
+    }
+
+    #[test]
+    fn test_top_k_huge_limit() {
+        let scores = vec![(0, 1.0), (1, 2.0)];
+        assert_eq!(top_k(scores.clone(), usize::MAX), vec![(1, 2.0), (0, 1.0)]);
+        assert_eq!(top_k(scores.clone(), 1 << 40), vec![(1, 2.0), (0, 1.0)]);
+        // An iterator without a useful size hint
+        let filtered = scores.into_iter().filter(|_| true);
+        assert_eq!(top_k(filtered, usize::MAX).len(), 2);
+    }
+
+    #[test]
+    fn test_ties_and_nan() {
+        let scores = vec![(5, 1.0), (2, f32::NAN), (3, 1.0), (1, 1.0), (0, -1.0)];
+        let ranked_ids: Vec<usize> = top_k(scores.clone(), 0).into_iter().map(|(id, _)| id).collect();
+        assert_eq!(ranked_ids, vec![1, 3, 5, 0, 2]);
+        let best: Vec<usize> = top_k(scores.clone(), 2).into_iter().map(|(id, _)| id).collect();
+        assert_eq!(best, vec![1, 3]);
+
+        let lazy: Vec<usize> = ranked(scores).map(|(id, _)| id).collect();
+        assert_eq!(lazy, ranked_ids);
+        assert_eq!(cmp_score(f32::NAN, f32::NEG_INFINITY), Ordering::Less);
+        assert_eq!(cmp_score(f32::INFINITY, f32::NAN), Ordering::Greater);
+    }
+
+    #[test]
+    fn test_bottom_k() {
+        let distances = vec![(0, 0.5), (1, 0.1), (2, 0.9), (3, 0.1)];
+        assert_eq!(bottom_k(distances.clone(), 3), vec![(1, 0.1), (3, 0.1), (0, 0.5)]);
+        assert_eq!(bottom_k(distances, 0).last(), Some(&(2, 0.9)));
+    }
+
+    #[test]
+    fn test_in_place_matches_heap() {
+        let scores: Vec<(usize, f32)> = (0..100).map(|i| (i, ((i * 37) % 11) as f32)).collect();
+        for limit in [0, 1, 10, 100, 200] {
+            let mut buffer = scores.clone();
+            top_k_in_place(&mut buffer, limit);
+            assert_eq!(buffer, top_k(scores.clone(), limit));
+
+            let mut buffer = scores.clone();
+            bottom_k_in_place(&mut buffer, limit);
+            assert_eq!(buffer, bottom_k(scores.clone(), limit));
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::filter::field_value;
+use crate::types::ChunkMetadata;
+
+const SECONDS_PER_DAY: f64 = 86_400.0;
+
+/// Post-scoring boost for newer chunks in `RagEngine`:
+/// `score * exp(-lambda * age_days)`. Negative scores and Euclidean
+/// distances are divided by the decay instead, so age always pushes a
+/// chunk down the ranking.
+///
+/// The timestamp is read from the metadata field `field` (a built-in field
+/// such as `date`, or a `custom_metadata` key) and may be a number of Unix
+/// seconds or a string starting with a `YYYY-MM-DD` date. Chunks without a
+/// readable timestamp keep their score. Ages are measured from `now`, also in
+/// Unix seconds, and timestamps after it count as age 0.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecencyConfig {
+    pub field: String,
+    /// Decay rate per day; `ln(2) / lambda` is the half-life in days. Must be >= 0
+    pub lambda: f32,
+    pub now: i64,
+}
+
+impl RecencyConfig {
+    pub fn new(field: &str, lambda: f32, now: i64) -> Self {
+        Self {
+            field: field.to_string(),
+            lambda,
+            now,
+        }
+    }
+
+    /// Decay with the given half-life in days
+    pub fn with_half_life(field: &str, half_life_days: f32, now: i64) -> Self {
+        Self::new(field, std::f32::consts::LN_2 / half_life_days, now)
+    }
+
+    pub(crate) fn validate(&self) -> Result<()> {
+        if !(self.lambda.is_finite() && self.lambda >= 0.0) {
+            return Err(anyhow::anyhow!("Recency lambda must be a non-negative number, got {}", self.lambda));
+        }
+        Ok(())
+    }
+
+    /// Multiplier for a chunk's score, or None if it has no readable timestamp
+    pub(crate) fn decay(&self, metadata: &ChunkMetadata) -> Option<f32> {
+        let timestamp = match field_value(metadata, &self.field)? {
+            Value::Number(n) => n.as_f64()?,
+            Value::String(s) => parse_date(&s)? as f64 * SECONDS_PER_DAY,
+            _ => return None,
+        };
+        let age_days = ((self.now as f64 - timestamp) / SECONDS_PER_DAY).max(0.0);
+        Some((-(self.lambda as f64) * age_days).exp() as f32)
+    }
+}
+
+/// Days since 1970-01-01 of a string starting with `YYYY-MM-DD`
+fn parse_date(text: &str) -> Option<i64> {
+    let date = text.get(..10)?;
+    let mut parts = date.split('-');
+    let year: i64 = parts.next()?.parse().ok()?;
+    let month: i64 = parts.next()?.parse().ok()?;
+    let day: i64 = parts.next()?.parse().ok()?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    // Days from civil date, counting years from March so leap days come last
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let year_of_era = y - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    Some(era * 146_097 + day_of_era - 719_468)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: i64 = SECONDS_PER_DAY as i64;
+
+    fn dated(field: &str, value: Value) -> ChunkMetadata {
+        let mut metadata = ChunkMetadata::default();
+        if field == "date" {
+            metadata.date = value.as_str().map(str::to_string);
+        } else {
+            metadata.custom_metadata.insert(field.to_string(), value);
+        }
+        metadata
+    }
+
+    #[test]
+    fn test_parse_date() {
+        assert_eq!(parse_date("1970-01-01"), Some(0));
+        assert_eq!(parse_date("2000-03-01"), Some(11_017));
+        assert_eq!(parse_date("2024-02-29T12:00:00Z"), Some(19_782));
+        assert_eq!(parse_date("1969-12-31"), Some(-1));
+        assert_eq!(parse_date("2024-13-01"), None);
+        assert_eq!(parse_date("2024-1-1"), None);
+        assert_eq!(parse_date("soon"), None);
+    }
+
+    #[test]
+    fn test_half_life() {
+        let now = 100 * DAY;
+        let config = RecencyConfig::with_half_life("published", 10.0, now);
+        let decay = |days: i64| config.decay(&dated("published", Value::from(now - days * DAY))).unwrap();
+
+        assert!((decay(0) - 1.0).abs() < 1e-6);
+        assert!((decay(10) - 0.5).abs() < 1e-6);
+        assert!((decay(20) - 0.25).abs() < 1e-6);
+        // Timestamps in the future count as age 0
+        assert_eq!(decay(-5), 1.0);
+    }
+
+    #[test]
+    fn test_date_strings_and_missing_fields() {
+        let now = parse_date("2024-01-11").unwrap() * DAY;
+        let config = RecencyConfig::new("date", 0.1, now);
+        let decay = config.decay(&dated("date", Value::from("2024-01-01"))).unwrap();
+        assert!((decay - (-1.0f32).exp()).abs() < 1e-6);
+
+        assert_eq!(config.decay(&ChunkMetadata::default()), None);
+        assert_eq!(config.decay(&dated("date", Value::from("last week"))), None);
+        let custom = RecencyConfig::new("published", 0.1, now);
+        assert_eq!(custom.decay(&dated("published", Value::Bool(true))), None);
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(RecencyConfig::new("date", 0.0, 0).validate().is_ok());
+        assert!(RecencyConfig::new("date", -0.1, 0).validate().is_err());
+        assert!(RecencyConfig::new("date", f32::NAN, 0).validate().is_err());
+        assert!(RecencyConfig::with_half_life("date", 0.0, 0).validate().is_err());
+    }
+}
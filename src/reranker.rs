@@ -0,0 +1,47 @@
+use std::fmt;
+
+use crate::types::SearchResult;
+
+/// Reorders search results with a heavier scorer than the first retrieval
+/// stage, e.g. a cross-encoder, so it can be plugged into `RagEngine`.
+///
+/// `rerank` gets the query and its best candidates, best first, and returns
+/// them in the new order, typically with updated scores.
+pub trait Reranker: Send + Sync {
+    fn rerank(&self, query: &str, candidates: Vec<SearchResult>) -> Vec<SearchResult>;
+}
+
+impl fmt::Debug for dyn Reranker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Reranker")
+    }
+}
+
+/// Reranker that keeps the candidates as they are
+#[derive(Debug, Clone, Copy, Default)]
+pub struct IdentityReranker;
+
+impl Reranker for IdentityReranker {
+    fn rerank(&self, _query: &str, candidates: Vec<SearchResult>) -> Vec<SearchResult> {
+        candidates
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::resolve_results;
+    use crate::types::{chunk_text, SearchType};
+
+    #[test]
+    fn test_identity_keeps_order() {
+        let chunks = chunk_text("a b c", "doc", 1, 0).unwrap();
+        let candidates = resolve_results(&[(2, 3.0), (0, 2.0), (1, 1.0)], &chunks, SearchType::Keyword);
+        let reranked = IdentityReranker.rerank("query", candidates);
+        let ids: Vec<&str> = reranked.iter().map(|r| r.chunk.id.as_str()).collect();
+        assert_eq!(ids, vec!["doc-2", "doc-0", "doc-1"]);
+
+        let boxed: Box<dyn Reranker> = Box::new(IdentityReranker);
+        assert_eq!(format!("{:?}", boxed), "Reranker");
+    }
+}
@@ -0,0 +1,93 @@
+use parking_lot::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::sync::Arc;
+
+use crate::bm25::Bm25Index;
+use crate::engine::RagEngine;
+use crate::vector::VectorStore;
+
+/// Cloneable handle to an index shared between threads: many concurrent
+/// readers, one writer at a time.
+#[derive(Debug, Default)]
+pub struct SharedIndex<T> {
+    inner: Arc<RwLock<T>>,
+}
+
+impl<T> SharedIndex<T> {
+    pub fn new(index: T) -> Self {
+        Self {
+            inner: Arc::new(RwLock::new(index)),
+        }
+    }
+
+    /// Shared access for searching; blocks while a writer holds the lock
+    pub fn read(&self) -> RwLockReadGuard<'_, T> {
+        self.inner.read()
+    }
+
+    /// Exclusive access for adding or removing documents
+    pub fn write(&self) -> RwLockWriteGuard<'_, T> {
+        self.inner.write()
+    }
+}
+
+impl<T> Clone for SharedIndex<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+// The indexes must stay shareable across threads
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Bm25Index>();
+    assert_send_sync::<VectorStore>();
+    assert_send_sync::<RagEngine>();
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_concurrent_readers_and_writer() {
+        let shared = SharedIndex::new(Bm25Index::new());
+        shared.write().add_document(0, "rust search engine");
+
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let index = shared.clone();
+                thread::spawn(move || {
+                    for _ in 0..200 {
+                        let results = index.read().search("rust", 200);
+                        assert!(!results.is_empty());
+                    }
+                })
+            })
+            .collect();
+        let writer = {
+            let index = shared.clone();
+            thread::spawn(move || {
+                for i in 1..=100 {
+                    index.write().add_document(i, "more text about rust");
+                }
+            })
+        };
+
+        writer.join().unwrap();
+        for reader in readers {
+            reader.join().unwrap();
+        }
+        assert_eq!(shared.read().search("rust", 200).len(), 101);
+    }
+
+    #[test]
+    fn test_clones_share_state() {
+        let a = SharedIndex::new(VectorStore::default());
+        let b = a.clone();
+        b.write().add(vec![1.0, 0.0]).unwrap();
+        assert_eq!(a.read().len(), 1);
+    }
+}
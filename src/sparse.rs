@@ -0,0 +1,201 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::ranking::top_k;
+use crate::vector::check_finite;
+
+/// A vector stored as its non-zero entries, indices strictly ascending
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(try_from = "RawSparseVector")]
+pub struct SparseVector {
+    indices: Vec<u32>,
+    values: Vec<f32>,
+}
+
+/// Serialized form of `SparseVector`, checked by `SparseVector::new` on load
+#[derive(Deserialize)]
+struct RawSparseVector {
+    indices: Vec<u32>,
+    values: Vec<f32>,
+}
+
+impl TryFrom<RawSparseVector> for SparseVector {
+    type Error = anyhow::Error;
+
+    fn try_from(raw: RawSparseVector) -> Result<Self> {
+        Self::new(raw.indices, raw.values)
+    }
+}
+
+impl SparseVector {
+    /// Build from parallel index/value lists in any order. Zero values are
+    /// dropped; errors on differing lengths, repeated indices, NaN or infinity
+    pub fn new(indices: Vec<u32>, values: Vec<f32>) -> Result<Self> {
+        if indices.len() != values.len() {
+            return Err(anyhow::anyhow!(
+                "Got {} indices but {} values",
+                indices.len(), values.len()
+            ));
+        }
+        check_finite(&values).map_err(|e| anyhow::anyhow!("Sparse vector {}", e))?;
+
+        let mut entries: Vec<(u32, f32)> = indices.into_iter().zip(values).filter(|&(_, v)| v != 0.0).collect();
+        entries.sort_unstable_by_key(|&(idx, _)| idx);
+        if let Some(pair) = entries.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+            return Err(anyhow::anyhow!("Index {} appears more than once", pair[0].0));
+        }
+
+        let (indices, values) = entries.into_iter().unzip();
+        Ok(Self { indices, values })
+    }
+
+    /// Keep the non-zero entries of a dense vector
+    pub fn from_dense(dense: &[f32]) -> Result<Self> {
+        check_finite(dense).map_err(|e| anyhow::anyhow!("Sparse vector {}", e))?;
+        let (indices, values) = dense
+            .iter()
+            .enumerate()
+            .filter(|&(_, &v)| v != 0.0)
+            .map(|(idx, &v)| (idx as u32, v))
+            .unzip();
+        Ok(Self { indices, values })
+    }
+
+    pub fn indices(&self) -> &[u32] {
+        &self.indices
+    }
+
+    pub fn values(&self) -> &[f32] {
+        &self.values
+    }
+
+    /// Number of non-zero entries
+    pub fn nnz(&self) -> usize {
+        self.indices.len()
+    }
+
+    fn norm(&self) -> f32 {
+        self.values.iter().map(|v| v * v).sum::<f32>().sqrt()
+    }
+}
+
+/// Dot product over the indices both vectors share, found by merging the
+/// sorted index lists
+pub fn sparse_dot(a: &SparseVector, b: &SparseVector) -> f32 {
+    let (mut i, mut j) = (0, 0);
+    let mut dot = 0.0;
+    while i < a.indices.len() && j < b.indices.len() {
+        match a.indices[i].cmp(&b.indices[j]) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => {
+                dot += a.values[i] * b.values[j];
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    dot
+}
+
+/// Cosine similarity of two sparse vectors, in time linear in their non-zero
+/// entries. Like `VectorStore::cosine_similarity`, an empty vector scores 0.0
+pub fn sparse_cosine(a: &SparseVector, b: &SparseVector) -> f32 {
+    let norm = a.norm() * b.norm();
+    if norm == 0.0 {
+        return 0.0;
+    }
+    sparse_dot(a, b) / norm
+}
+
+/// Brute-force cosine search over sparse vectors, e.g. learned sparse or
+/// TF-IDF representations alongside BM25's term space
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SparseVectorStore {
+    pub vectors: Vec<SparseVector>,
+}
+
+impl SparseVectorStore {
+    pub fn new(vectors: Vec<SparseVector>) -> Self {
+        Self { vectors }
+    }
+
+    /// Append a vector and return its index
+    pub fn add(&mut self, vector: SparseVector) -> usize {
+        self.vectors.push(vector);
+        self.vectors.len() - 1
+    }
+
+    pub fn len(&self) -> usize {
+        self.vectors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vectors.is_empty()
+    }
+
+    /// The `limit` most cosine-similar vectors as `(index, similarity)`, best first
+    pub fn search(&self, query: &SparseVector, limit: usize) -> Vec<(usize, f32)> {
+        let scores = self
+            .vectors
+            .iter()
+            .enumerate()
+            .map(|(idx, vector)| (idx, sparse_cosine(query, vector)));
+        top_k(scores, limit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vector::VectorStore;
+
+    #[test]
+    fn test_sparse_matches_dense_cosine() {
+        let a = vec![0.0, 1.5, 0.0, 0.0, -2.0, 0.0, 3.0];
+        let b = vec![0.5, 1.0, 0.0, 4.0, 0.0, 0.0, -1.0];
+        let (sa, sb) = (SparseVector::from_dense(&a).unwrap(), SparseVector::from_dense(&b).unwrap());
+        assert_eq!(sa.nnz(), 3);
+        assert!((sparse_cosine(&sa, &sb) - VectorStore::cosine_similarity(&a, &b)).abs() < 1e-6);
+        assert!((sparse_dot(&sa, &sb) - VectorStore::dot_product(&a, &b)).abs() < 1e-6);
+        assert_eq!(sparse_cosine(&sa, &SparseVector::default()), 0.0);
+    }
+
+    #[test]
+    fn test_new_sorts_and_validates() {
+        let v = SparseVector::new(vec![5, 1, 3], vec![1.0, 2.0, 0.0]).unwrap();
+        assert_eq!((v.indices(), v.values()), (&[1, 5][..], &[2.0, 1.0][..]));
+
+        assert!(SparseVector::new(vec![1, 2], vec![1.0]).is_err());
+        assert!(SparseVector::new(vec![1, 1], vec![1.0, 2.0]).is_err());
+        assert!(SparseVector::new(vec![1], vec![f32::NAN]).is_err());
+        assert!(SparseVector::from_dense(&[f32::INFINITY]).is_err());
+    }
+
+    #[test]
+    fn test_store_search() {
+        let mut store = SparseVectorStore::default();
+        store.add(SparseVector::new(vec![0, 10], vec![1.0, 1.0]).unwrap());
+        store.add(SparseVector::new(vec![10], vec![1.0]).unwrap());
+        store.add(SparseVector::new(vec![99], vec![1.0]).unwrap());
+
+        let query = SparseVector::new(vec![10], vec![2.0]).unwrap();
+        let results = store.search(&query, 2);
+        assert_eq!(results.iter().map(|&(idx, _)| idx).collect::<Vec<_>>(), vec![1, 0]);
+        assert!((results[0].1 - 1.0).abs() < 1e-6);
+        assert_eq!(store.search(&query, 0).len(), 3);
+        assert!(SparseVectorStore::new(Vec::new()).search(&query, 5).is_empty());
+    }
+
+    #[test]
+    fn test_deserialize_validates() {
+        let v = SparseVector::new(vec![3, 1], vec![1.0, 2.0]).unwrap();
+        let json = serde_json::to_string(&v).unwrap();
+        assert_eq!(serde_json::from_str::<SparseVector>(&json).unwrap(), v);
+
+        let unsorted: SparseVector = serde_json::from_str(r#"{"indices":[3,1],"values":[1.0,0.0]}"#).unwrap();
+        assert_eq!((unsorted.indices(), unsorted.values()), (&[3][..], &[1.0][..]));
+        assert!(serde_json::from_str::<SparseVector>(r#"{"indices":[1,2],"values":[1.0]}"#).is_err());
+        assert!(serde_json::from_str::<SparseVector>(r#"{"indices":[1,1],"values":[1.0,2.0]}"#).is_err());
+    }
+}
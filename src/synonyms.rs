@@ -0,0 +1,131 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+use crate::bm25::{parse_boost, split_quoted};
+
+/// Query-time synonym expansion for `RagEngine` keyword search, so "car"
+/// also matches documents that only say "automobile".
+///
+/// Only queries are expanded; documents are indexed as written. Each
+/// synonym is added to the query as an extra term boosted by `weight`
+/// (times the original word's own `^` boost), so exact matches still rank
+/// first. Words inside quoted phrases are not expanded.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SynonymConfig {
+    /// Lowercase word -> its synonyms. A synonym may be several words, which
+    /// share its weight
+    pub synonyms: HashMap<String, Vec<String>>,
+    /// Boost of expanded terms, in (0, 1] to rank them below the original
+    pub weight: f32,
+    /// Most synonyms added to one query, taken in query order
+    pub max_expansions: usize,
+}
+
+impl SynonymConfig {
+    pub fn new(synonyms: HashMap<String, Vec<String>>) -> Self {
+        Self {
+            synonyms,
+            weight: 0.5,
+            max_expansions: 10,
+        }
+    }
+
+    pub fn with_weight(mut self, weight: f32) -> Self {
+        self.weight = weight;
+        self
+    }
+
+    pub fn with_max_expansions(mut self, max_expansions: usize) -> Self {
+        self.max_expansions = max_expansions;
+        self
+    }
+
+    /// Errors unless `weight` is in (0, 1]
+    pub(crate) fn validate(&self) -> Result<()> {
+        if !(self.weight > 0.0 && self.weight <= 1.0) {
+            return Err(anyhow::anyhow!("Synonym weight must be in (0, 1], got {}", self.weight));
+        }
+        Ok(())
+    }
+
+    /// `query` with boosted synonyms appended, e.g. `fast car` becomes
+    /// `fast car automobile^0.5`. Synonyms already in the query are skipped.
+    /// Quotes and `^` boosts are read as `Bm25Index::search` reads them
+    pub(crate) fn expand(&self, query: &str) -> String {
+        let words: Vec<(String, f32)> = split_quoted(query)
+            .filter(|&(_, quoted)| !quoted)
+            .flat_map(|(segment, _)| segment.split_whitespace())
+            .map(|word| {
+                let (text, boost) = parse_boost(word);
+                (text.trim_matches(|c: char| !c.is_alphanumeric()).to_lowercase(), boost)
+            })
+            .collect();
+        let mut seen: HashSet<String> = words.iter().map(|(word, _)| word.clone()).collect();
+
+        let mut expanded = query.to_string();
+        let mut added = 0;
+        for (word, boost) in &words {
+            for synonym in self.synonyms.get(word).into_iter().flatten() {
+                if added == self.max_expansions {
+                    return expanded;
+                }
+                if !seen.insert(synonym.to_lowercase()) {
+                    continue;
+                }
+                let parts: Vec<&str> = synonym.split_whitespace().collect();
+                for part in &parts {
+                    expanded.push_str(&format!(" {}^{}", part, self.weight * boost / parts.len() as f32));
+                }
+                added += 1;
+            }
+        }
+        expanded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> SynonymConfig {
+        SynonymConfig::new(HashMap::from([
+            ("car".to_string(), vec!["automobile".to_string(), "motor vehicle".to_string()]),
+            ("fast".to_string(), vec!["quick".to_string()]),
+        ]))
+    }
+
+    #[test]
+    fn test_expand() {
+        assert_eq!(config().expand("fast car"), "fast car quick^0.5 automobile^0.5 motor^0.25 vehicle^0.25");
+        assert_eq!(config().expand("boat"), "boat");
+        // Case and punctuation don't block a match, the query is kept as written
+        assert_eq!(config().expand("Car?"), "Car? automobile^0.5 motor^0.25 vehicle^0.25");
+    }
+
+    #[test]
+    fn test_expand_boosts_and_skips() {
+        let c = config().with_weight(0.4);
+        assert_eq!(c.expand("fast^3"), "fast^3 quick^1.2");
+        // Synonyms already in the query aren't added again
+        assert_eq!(c.expand("fast quick"), "fast quick");
+        // Nor are words inside quoted phrases expanded
+        assert_eq!(c.expand("\"fast car\" today"), "\"fast car\" today");
+        assert_eq!(c.expand("\"fast car"), "\"fast car quick^0.4 automobile^0.4 motor^0.2 vehicle^0.2");
+    }
+
+    #[test]
+    fn test_max_expansions() {
+        assert_eq!(config().with_max_expansions(2).expand("fast car"), "fast car quick^0.5 automobile^0.5");
+        assert_eq!(config().with_max_expansions(0).expand("fast car"), "fast car");
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(config().validate().is_ok());
+        assert!(config().with_weight(1.0).validate().is_ok());
+        for weight in [0.0, -1.0, 1.5, f32::NAN, f32::INFINITY] {
+            assert!(config().with_weight(weight).validate().is_err());
+        }
+    }
+}
@@ -0,0 +1,424 @@
+use rust_stemmers::{Algorithm, Stemmer};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
+#[cfg(feature = "unicode-normalization")]
+use unicode_normalization::UnicodeNormalization;
+
+/// Splits text into the terms that get indexed and queried.
+///
+/// The same tokenizer must be used for indexing and searching. Tokenizers are
+/// not serialized with an index, so a custom one has to be set again after
+/// loading.
+pub trait Tokenizer: Send + Sync {
+    fn tokenize(&self, text: &str) -> Vec<String>;
+}
+
+impl fmt::Debug for dyn Tokenizer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Tokenizer")
+    }
+}
+
+/// Unicode normalization applied before tokenizing, so composed and
+/// decomposed spellings of the same text produce the same tokens
+#[cfg(feature = "unicode-normalization")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnicodeForm {
+    /// Canonical composition: "e" + combining acute becomes "é"
+    #[default]
+    Nfc,
+    /// Compatibility composition: also folds variants like "ﬁ" to "fi"
+    /// and full-width letters to ASCII
+    Nfkc,
+}
+
+/// Lowercases and splits on non-alphanumeric characters, keeping tokens of
+/// at least two characters (see `with_min_token_length`). With the
+/// `unicode-normalization` feature, text is NFC-normalized first
+/// (see `with_unicode_form`).
+#[derive(Debug, Clone)]
+pub struct DefaultTokenizer {
+    #[cfg(feature = "unicode-normalization")]
+    form: UnicodeForm,
+    keep_numeric_separators: bool,
+    min_token_length: usize,
+}
+
+impl Default for DefaultTokenizer {
+    fn default() -> Self {
+        Self {
+            #[cfg(feature = "unicode-normalization")]
+            form: UnicodeForm::default(),
+            keep_numeric_separators: false,
+            min_token_length: 2,
+        }
+    }
+}
+
+impl DefaultTokenizer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Drop tokens shorter than `min` characters (not bytes). Defaults to 2;
+    /// use 1 to index single-letter terms like "c" or "r".
+    pub fn with_min_token_length(mut self, min: usize) -> Self {
+        self.min_token_length = min;
+        self
+    }
+
+    /// Choose the normalization form. Use the same tokenizer for indexing
+    /// and querying, or composed and decomposed text won't match.
+    #[cfg(feature = "unicode-normalization")]
+    pub fn with_unicode_form(mut self, form: UnicodeForm) -> Self {
+        self.form = form;
+        self
+    }
+
+    /// Keep `.`, `,` and `-` between alphanumeric characters when the token
+    /// contains a digit, so "v1.2.3", "gpt-4", "3.14", "10,000" and
+    /// "2024-01-01" stay whole. Words without digits ("state-of-the-art")
+    /// are still split. Off by default.
+    pub fn with_keep_numeric_separators(mut self, keep: bool) -> Self {
+        self.keep_numeric_separators = keep;
+        self
+    }
+
+    #[cfg(feature = "unicode-normalization")]
+    fn normalize(&self, text: &str) -> String {
+        match self.form {
+            UnicodeForm::Nfc => text.nfc().collect(),
+            UnicodeForm::Nfkc => text.nfkc().collect(),
+        }
+    }
+}
+
+impl Tokenizer for DefaultTokenizer {
+    fn tokenize(&self, text: &str) -> Vec<String> {
+        #[cfg(feature = "unicode-normalization")]
+        let lowered = self.normalize(text).to_lowercase();
+        #[cfg(not(feature = "unicode-normalization"))]
+        let lowered = text.to_lowercase();
+
+        let pieces: Vec<&str> = if self.keep_numeric_separators {
+            split_keeping_numeric_separators(&lowered)
+        } else {
+            lowered.split(|c: char| !c.is_alphanumeric()).collect()
+        };
+
+        pieces
+            .into_iter()
+            .filter(|s| !s.is_empty() && s.chars().count() >= self.min_token_length)
+            .map(|s| s.to_string())
+            .collect()
+    }
+}
+
+/// Emits the character n-grams of every word, for partial and
+/// autocomplete-style matching: "search" shares all its 3-grams with
+/// "searching". Words shorter than `min_n` are emitted whole.
+///
+/// Much larger index than `DefaultTokenizer`, in exchange for recall.
+/// Phrase queries match n-gram sequences rather than words.
+#[derive(Debug, Clone)]
+pub struct NgramTokenizer {
+    pub min_n: usize,
+    pub max_n: usize,
+}
+
+impl Default for NgramTokenizer {
+    fn default() -> Self {
+        Self { min_n: 3, max_n: 4 }
+    }
+}
+
+impl NgramTokenizer {
+    pub fn new(min_n: usize, max_n: usize) -> Self {
+        Self { min_n, max_n }
+    }
+}
+
+impl Tokenizer for NgramTokenizer {
+    fn tokenize(&self, text: &str) -> Vec<String> {
+        let min_n = self.min_n.max(1);
+        let max_n = self.max_n.max(min_n);
+        let words = DefaultTokenizer::new().with_min_token_length(1).tokenize(text);
+
+        let mut grams = Vec::new();
+        for word in words {
+            let chars: Vec<char> = word.chars().collect();
+            if chars.len() < min_n {
+                grams.push(word);
+                continue;
+            }
+            for n in min_n..=max_n.min(chars.len()) {
+                grams.extend(chars.windows(n).map(|w| w.iter().collect::<String>()));
+            }
+        }
+        grams
+    }
+}
+
+fn is_numeric_separator(c: char) -> bool {
+    matches!(c, '.' | ',' | '-')
+}
+
+/// Split on non-alphanumerics, except single separators inside a run that
+/// contains a digit
+fn split_keeping_numeric_separators(text: &str) -> Vec<&str> {
+    let mut pieces = Vec::new();
+    let mut start: Option<usize> = None;
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        let next_is_alphanumeric = chars.peek().is_some_and(|&(_, n)| n.is_alphanumeric());
+        if c.is_alphanumeric() {
+            start.get_or_insert(i);
+        } else if is_numeric_separator(c) && start.is_some() && next_is_alphanumeric {
+            // Interior separator, part of the current run
+        } else if let Some(s) = start.take() {
+            pieces.push(&text[s..i]);
+        }
+    }
+    if let Some(s) = start {
+        pieces.push(&text[s..]);
+    }
+
+    pieces
+        .into_iter()
+        .flat_map(|run| {
+            if run.chars().any(|c| c.is_numeric()) {
+                vec![run]
+            } else {
+                run.split(is_numeric_separator).collect()
+            }
+        })
+        .collect()
+}
+
+/// Common English words that carry little meaning for keyword search
+pub const ENGLISH_STOPWORDS: &[&str] = &[
+    "a", "about", "above", "after", "again", "against", "all", "am", "an", "and", "any", "are",
+    "as", "at", "be", "because", "been", "before", "being", "below", "between", "both", "but",
+    "by", "can", "could", "did", "do", "does", "doing", "down", "during", "each", "few", "for",
+    "from", "further", "had", "has", "have", "having", "he", "her", "here", "hers", "herself",
+    "him", "himself", "his", "how", "if", "in", "into", "is", "it", "its", "itself", "just", "me",
+    "more", "most", "my", "myself", "no", "nor", "not", "now", "of", "off", "on", "once", "only",
+    "or", "other", "our", "ours", "ourselves", "out", "over", "own", "same", "she", "should", "so",
+    "some", "such", "than", "that", "the", "their", "theirs", "them", "themselves", "then",
+    "there", "these", "they", "this", "those", "through", "to", "too", "under", "until", "up",
+    "very", "was", "we", "were", "what", "when", "where", "which", "while", "who", "whom", "why",
+    "will", "with", "would", "you", "your", "yours", "yourself", "yourselves",
+];
+
+/// Common German words that carry little meaning for keyword search
+pub const GERMAN_STOPWORDS: &[&str] = &[
+    "aber", "alle", "als", "also", "am", "an", "auch", "auf", "aus", "bei", "bin", "bis", "bist",
+    "da", "damit", "dann", "das", "dass", "dein", "dem", "den", "der", "des", "dich", "die", "dir",
+    "doch", "du", "durch", "ein", "eine", "einem", "einen", "einer", "eines", "er", "es", "euch",
+    "euer", "für", "hat", "hatte", "hier", "ich", "ihm", "ihn", "ihr", "ihre", "im", "in", "ist",
+    "ja", "jede", "jeder", "kann", "kein", "keine", "man", "mein", "mich", "mir", "mit", "muss",
+    "nach", "nicht", "noch", "nur", "ob", "oder", "ohne", "sehr", "sein", "seine", "sich", "sie",
+    "sind", "so", "über", "um", "und", "uns", "unser", "unter", "vom", "von", "vor", "war",
+    "waren", "was", "weil", "wenn", "wer", "wie", "wir", "wird", "wo", "zu", "zum", "zur",
+];
+
+/// Common French words that carry little meaning for keyword search
+pub const FRENCH_STOPWORDS: &[&str] = &[
+    "au", "aux", "avec", "ce", "ces", "cette", "dans", "de", "des", "du", "elle", "elles", "en",
+    "est", "et", "être", "eu", "il", "ils", "je", "la", "le", "les", "leur", "leurs", "lui", "ma",
+    "mais", "me", "même", "mes", "moi", "mon", "ne", "nos", "notre", "nous", "on", "ou", "où",
+    "par", "pas", "pour", "qu", "que", "qui", "sa", "se", "ses", "son", "sont", "sur", "ta", "te",
+    "tes", "toi", "ton", "tu", "un", "une", "vos", "votre", "vous",
+];
+
+/// Common Spanish words that carry little meaning for keyword search
+pub const SPANISH_STOPWORDS: &[&str] = &[
+    "al", "algo", "como", "con", "cual", "cuando", "de", "del", "desde", "donde", "el", "él",
+    "ella", "ellos", "en", "entre", "era", "es", "esa", "ese", "eso", "esta", "este", "esto",
+    "fue", "ha", "hay", "la", "las", "le", "les", "lo", "los", "me", "mi", "muy", "más", "nada",
+    "ni", "no", "nos", "nosotros", "o", "para", "pero", "por", "porque", "que", "qué", "se",
+    "ser", "si", "sí", "sin", "sobre", "su", "sus", "también", "te", "tu", "un", "una", "uno",
+    "unos", "y", "ya", "yo",
+];
+
+/// Language of a built-in stopword list. Stopwords are opt-in: a new
+/// `Bm25Index` drops none until `Bm25Index::set_stopword_languages` is called
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Language {
+    #[default]
+    English,
+    German,
+    French,
+    Spanish,
+}
+
+impl Language {
+    /// The built-in stopword list for this language
+    pub fn stopwords(&self) -> &'static [&'static str] {
+        match self {
+            Language::English => ENGLISH_STOPWORDS,
+            Language::German => GERMAN_STOPWORDS,
+            Language::French => FRENCH_STOPWORDS,
+            Language::Spanish => SPANISH_STOPWORDS,
+        }
+    }
+}
+
+/// Set of words dropped from both indexed documents and queries
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct StopwordFilter {
+    words: HashSet<String>,
+}
+
+impl StopwordFilter {
+    /// Words are lowercased to match the tokenizer output
+    pub fn new(words: HashSet<String>) -> Self {
+        Self {
+            words: words.into_iter().map(|w| w.to_lowercase()).collect(),
+        }
+    }
+
+    /// Filter using the built-in English list
+    pub fn english() -> Self {
+        Self::for_languages(&[Language::English])
+    }
+
+    /// Filter using the union of the built-in lists for `languages`, for
+    /// corpora that mix languages
+    pub fn for_languages(languages: &[Language]) -> Self {
+        Self::new(languages.iter().flat_map(|l| l.stopwords()).map(|w| w.to_string()).collect())
+    }
+
+    pub fn is_stopword(&self, token: &str) -> bool {
+        self.words.contains(token)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    pub fn words(&self) -> &HashSet<String> {
+        &self.words
+    }
+}
+
+/// Tokenize with the default `DefaultTokenizer` settings
+pub(crate) fn tokenize(text: &str) -> Vec<String> {
+    DefaultTokenizer::default().tokenize(text)
+}
+
+/// Reduce tokens to their Snowball English stem ("running" -> "run").
+/// Tokens that would stem to nothing are kept as-is.
+pub(crate) fn stem_tokens(tokens: Vec<String>) -> Vec<String> {
+    let stemmer = Stemmer::create(Algorithm::English);
+    tokens
+        .into_iter()
+        .map(|token| {
+            let stemmed = stemmer.stem(&token);
+            if stemmed.is_empty() {
+                token
+            } else {
+                stemmed.into_owned()
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(tokenize("Hello, World! It's a test-case."), vec!["hello", "world", "it", "test", "case"]);
+        assert!(tokenize("  ..  ").is_empty());
+    }
+
+    #[test]
+    fn test_stopword_filter() {
+        let filter = StopwordFilter::new(HashSet::from(["The".to_string()]));
+        assert!(filter.is_stopword("the"));
+        assert!(!filter.is_stopword("rust"));
+        assert!(StopwordFilter::default().is_empty());
+        assert!(StopwordFilter::english().is_stopword("and"));
+    }
+
+    #[test]
+    fn test_default_tokenizer() {
+        let tokens = DefaultTokenizer::new().tokenize("Hello, World! It's a test-case.");
+        assert_eq!(tokens, vec!["hello", "world", "it", "test", "case"]);
+        assert!(DefaultTokenizer::new().tokenize("  ..  ").is_empty());
+    }
+
+    #[cfg(feature = "unicode-normalization")]
+    #[test]
+    fn test_unicode_normalization() {
+        let composed = "caf\u{e9}";
+        let decomposed = "cafe\u{301}";
+        let tokenizer = DefaultTokenizer::new();
+        assert_eq!(tokenizer.tokenize(composed), tokenizer.tokenize(decomposed));
+
+        let nfkc = DefaultTokenizer::new().with_unicode_form(UnicodeForm::Nfkc);
+        assert_eq!(nfkc.tokenize("\u{fb01}nd ＲＵＳＴ"), vec!["find", "rust"]);
+        assert_eq!(tokenizer.tokenize("\u{fb01}nd"), vec!["\u{fb01}nd"]);
+    }
+
+    #[test]
+    fn test_stopword_languages() {
+        let german = StopwordFilter::for_languages(&[Language::German]);
+        assert!(german.is_stopword("und"));
+        assert!(!german.is_stopword("and"));
+
+        let mixed = StopwordFilter::for_languages(&[Language::English, Language::German]);
+        assert!(mixed.is_stopword("und") && mixed.is_stopword("and"));
+        assert_eq!(Language::default(), Language::English);
+
+        // Built-in lists are lowercase so they match tokenizer output
+        for language in [Language::English, Language::German, Language::French, Language::Spanish] {
+            assert!(language.stopwords().iter().all(|w| w.to_lowercase() == *w));
+        }
+    }
+
+    #[test]
+    fn test_stem_tokens() {
+        let stems = stem_tokens(vec!["running".to_string(), "runs".to_string(), "cats".to_string()]);
+        assert_eq!(stems, vec!["run", "run", "cat"]);
+        assert!(stem_tokens(vec!["a".to_string(), "is".to_string()]).iter().all(|t| !t.is_empty()));
+    }
+
+    #[test]
+    fn test_keep_numeric_separators() {
+        let tokenizer = DefaultTokenizer::new().with_keep_numeric_separators(true);
+        assert_eq!(tokenizer.tokenize("release v1.2.3 today"), vec!["release", "v1.2.3", "today"]);
+        assert_eq!(tokenizer.tokenize("on 2024-01-01, pi is 3.14"), vec!["on", "2024-01-01", "pi", "is", "3.14"]);
+        assert_eq!(tokenizer.tokenize("10,000 users of gpt-4."), vec!["10,000", "users", "of", "gpt-4"]);
+        assert_eq!(tokenizer.tokenize("state-of-the-art"), vec!["state", "of", "the", "art"]);
+
+        // Off by default
+        assert_eq!(DefaultTokenizer::new().tokenize("v1.2.3"), vec!["v1"]);
+    }
+
+    #[test]
+    fn test_min_token_length() {
+        assert_eq!(DefaultTokenizer::new().tokenize("c and r"), vec!["and"]);
+        assert_eq!(DefaultTokenizer::new().with_min_token_length(1).tokenize("c and r"), vec!["c", "and", "r"]);
+        // Two characters but four bytes each
+        assert_eq!(DefaultTokenizer::new().tokenize("日本 é"), vec!["日本"]);
+        assert_eq!(DefaultTokenizer::new().with_min_token_length(3).tokenize("go rust"), vec!["rust"]);
+    }
+
+    #[test]
+    fn test_ngram_tokenizer() {
+        let tokenizer = NgramTokenizer::new(3, 4);
+        assert_eq!(tokenizer.tokenize("rust"), vec!["rus", "ust", "rust"]);
+        assert_eq!(tokenizer.tokenize("Go"), vec!["go"]);
+
+        let word: HashSet<String> = tokenizer.tokenize("searching").into_iter().collect();
+        assert!(tokenizer.tokenize("search").iter().all(|gram| word.contains(gram)));
+
+        // min_n above max_n is treated as a single size
+        assert_eq!(NgramTokenizer::new(4, 2).tokenize("abcde"), vec!["abcd", "bcde"]);
+    }
+}
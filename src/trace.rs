@@ -0,0 +1,166 @@
+//! Search-phase spans for the `tracing` feature. Without the feature `span!`
+//! yields a zero-sized `NoSpan` whose methods do nothing, so instrumented
+//! code compiles to the same thing as uninstrumented code.
+
+/// Enter a debug-level span named `$name`, with each listed field declared
+/// empty so it can be filled in with `record` once the value is known
+macro_rules! span {
+    ($name:literal $(, $field:ident)* $(,)?) => {{
+        #[cfg(feature = "tracing")]
+        let span = tracing::debug_span!($name, $($field = tracing::field::Empty),*).entered();
+        #[cfg(not(feature = "tracing"))]
+        let span = $crate::trace::NoSpan;
+        span
+    }};
+}
+
+pub(crate) use span;
+
+/// Stand-in for an entered span when the `tracing` feature is off
+#[cfg(not(feature = "tracing"))]
+pub(crate) struct NoSpan;
+
+#[cfg(not(feature = "tracing"))]
+impl NoSpan {
+    #[inline(always)]
+    pub(crate) fn record<V>(&self, _field: &str, _value: V) -> &Self {
+        self
+    }
+}
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+    use parking_lot::Mutex;
+    use std::collections::{HashMap, HashSet};
+    use std::fmt;
+    use std::sync::Arc;
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    use crate::bm25::Bm25Index;
+    use crate::vector::VectorStore;
+
+    #[derive(Debug, Clone)]
+    struct CapturedSpan {
+        name: &'static str,
+        parent: Option<&'static str>,
+        fields: HashMap<&'static str, String>,
+    }
+
+    /// Subscriber that keeps every span with its recorded fields and the span
+    /// it was opened in
+    #[derive(Default, Clone)]
+    struct Capture {
+        spans: Arc<Mutex<Vec<CapturedSpan>>>,
+        entered: Arc<Mutex<Vec<usize>>>,
+    }
+
+    struct Fields<'a>(&'a mut HashMap<&'static str, String>);
+
+    impl Visit for Fields<'_> {
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            self.0.insert(field.name(), format!("{:?}", value));
+        }
+    }
+
+    impl Subscriber for Capture {
+        fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, attrs: &Attributes<'_>) -> Id {
+            let mut spans = self.spans.lock();
+            let parent = self.entered.lock().last().map(|&i| spans[i].name);
+            let mut fields = HashMap::new();
+            attrs.record(&mut Fields(&mut fields));
+            spans.push(CapturedSpan { name: attrs.metadata().name(), parent, fields });
+            Id::from_u64(spans.len() as u64)
+        }
+
+        fn record(&self, span: &Id, values: &Record<'_>) {
+            let mut spans = self.spans.lock();
+            values.record(&mut Fields(&mut spans[span.into_u64() as usize - 1].fields));
+        }
+
+        fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+        fn event(&self, _event: &Event<'_>) {}
+
+        fn enter(&self, span: &Id) {
+            self.entered.lock().push(span.into_u64() as usize - 1);
+        }
+
+        fn exit(&self, _span: &Id) {
+            self.entered.lock().pop();
+        }
+    }
+
+    fn capture(f: impl FnOnce()) -> Vec<CapturedSpan> {
+        let subscriber = Capture::default();
+        tracing::subscriber::with_default(subscriber.clone(), f);
+        subscriber.spans.lock().clone()
+    }
+
+    #[test]
+    fn test_bm25_search_spans() {
+        let mut index = Bm25Index::new();
+        index.add_document(0, "rust search engine");
+        index.add_document(1, "rust compiler");
+        index.add_document(2, "python");
+
+        let spans = capture(|| {
+            index.search("rust", 1);
+        });
+        let names: Vec<&str> = spans.iter().map(|s| s.name).collect();
+        assert_eq!(names, vec!["bm25_search", "bm25_parse", "bm25_candidates", "bm25_score", "bm25_rank"]);
+        assert!(spans[1..].iter().all(|s| s.parent == Some("bm25_search")));
+
+        let fields = |name| &spans.iter().find(|s| s.name == name).unwrap().fields;
+        assert_eq!(fields("bm25_candidates")["candidates"], "2");
+        assert_eq!(fields("bm25_search")["matches"], "2");
+        assert_eq!(fields("bm25_search")["results"], "1");
+    }
+
+    #[test]
+    fn test_filtered_candidates_span() {
+        let mut index = Bm25Index::new();
+        index.add_document(0, "rust search engine");
+        index.add_document(1, "rust compiler");
+        index.add_document(2, "rust book");
+
+        // Disallowed documents are dropped before scoring
+        let spans = capture(|| {
+            index.search_filtered("rust", 10, &HashSet::from([1]));
+        });
+        let candidates = spans.iter().find(|s| s.name == "bm25_candidates").unwrap();
+        assert_eq!(candidates.fields["candidates"], "1");
+    }
+
+    #[test]
+    fn test_boolean_candidates_span() {
+        let mut index = Bm25Index::new();
+        index.add_document(0, "rust async runtime");
+        index.add_document(1, "rust compiler");
+        index.add_document(2, "rust async javascript");
+
+        // Documents failing AND/NOT are pruned before scoring
+        let spans = capture(|| {
+            index.search_boolean("rust AND async NOT javascript", 10).unwrap();
+        });
+        let candidates = spans.iter().find(|s| s.name == "bm25_candidates").unwrap();
+        assert_eq!(candidates.fields["candidates"], "1");
+    }
+
+    #[test]
+    fn test_vector_search_span() {
+        let store = VectorStore::new(vec![vec![1.0, 0.0], vec![0.0, 1.0], vec![0.7, 0.7]]).unwrap();
+        let spans = capture(|| {
+            store.search(&[1.0, 0.0], 2).unwrap();
+        });
+        assert_eq!(spans.len(), 1);
+        assert_eq!(spans[0].name, "vector_search");
+        assert_eq!(spans[0].fields["candidates"], "3");
+        assert_eq!(spans[0].fields["results"], "2");
+    }
+}
@@ -1,3 +1,4 @@
+use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -29,6 +30,175 @@ impl Chunk {
     }
 }
 
+/// Split `text` into windows of at most `max_tokens` whitespace-separated
+/// tokens, each starting `max_tokens - overlap` tokens after the previous one.
+///
+/// Chunks get ids `"{source_file}-{n}"`, have `source_file` set and are linked
+/// through `preceding_chunk_id`/`following_chunk_id`. Text shorter than
+/// `max_tokens` gives a single chunk, empty text gives none. Errors if
+/// `max_tokens` is 0 or `overlap >= max_tokens`.
+pub fn chunk_text(text: &str, source_file: &str, max_tokens: usize, overlap: usize) -> Result<Vec<Chunk>> {
+    if max_tokens == 0 {
+        return Err(anyhow::anyhow!("max_tokens must be greater than 0"));
+    }
+    if overlap >= max_tokens {
+        return Err(anyhow::anyhow!(
+            "overlap ({}) must be smaller than max_tokens ({})",
+            overlap, max_tokens
+        ));
+    }
+
+    let tokens: Vec<&str> = text.split_whitespace().collect();
+    let step = max_tokens - overlap;
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    while start < tokens.len() {
+        let end = (start + max_tokens).min(tokens.len());
+        pieces.push(tokens[start..end].join(" "));
+        if end == tokens.len() {
+            break;
+        }
+        start += step;
+    }
+
+    Ok(link_chunks(source_file, pieces.into_iter().map(|text| (text, HashMap::new())).collect()))
+}
+
+/// Words ending in a period that don't end a sentence
+const ABBREVIATIONS: &[&str] = &[
+    "mr.", "mrs.", "ms.", "dr.", "prof.", "sr.", "jr.", "st.", "vs.", "etc.", "inc.", "ltd.", "co.",
+    "no.", "fig.", "approx.", "cf.", "jan.", "feb.", "mar.", "apr.", "jun.", "jul.", "aug.", "sep.",
+    "sept.", "oct.", "nov.", "dec.",
+];
+
+/// Group whole sentences into chunks of at most `max_tokens` whitespace-separated
+/// tokens, never splitting a sentence.
+///
+/// Sentences end at `.`, `!` or `?` (optionally followed by closing quotes or
+/// brackets), except after common abbreviations, initialisms like "e.g." and
+/// single-letter initials. A sentence longer than `max_tokens` becomes its own
+/// chunk with `"oversized": true` in `custom_metadata`. Ids and links are set
+/// as in `chunk_text`. Errors if `max_tokens` is 0.
+pub fn chunk_by_sentences(text: &str, source_file: &str, max_tokens: usize) -> Result<Vec<Chunk>> {
+    if max_tokens == 0 {
+        return Err(anyhow::anyhow!("max_tokens must be greater than 0"));
+    }
+
+    let mut pieces = Vec::new();
+    let mut current: Vec<&str> = Vec::new();
+
+    for sentence in split_sentences(text) {
+        if sentence.len() > max_tokens {
+            if !current.is_empty() {
+                pieces.push((current.join(" "), HashMap::new()));
+                current.clear();
+            }
+            let flag = HashMap::from([("oversized".to_string(), serde_json::Value::Bool(true))]);
+            pieces.push((sentence.join(" "), flag));
+            continue;
+        }
+        if current.len() + sentence.len() > max_tokens {
+            pieces.push((current.join(" "), HashMap::new()));
+            current.clear();
+        }
+        current.extend(sentence);
+    }
+    if !current.is_empty() {
+        pieces.push((current.join(" "), HashMap::new()));
+    }
+
+    Ok(link_chunks(source_file, pieces))
+}
+
+/// Split text into sentences, each a list of whitespace-separated tokens
+fn split_sentences(text: &str) -> Vec<Vec<&str>> {
+    let mut sentences = Vec::new();
+    let mut current = Vec::new();
+
+    for token in text.split_whitespace() {
+        current.push(token);
+        if ends_sentence(token) {
+            sentences.push(std::mem::take(&mut current));
+        }
+    }
+    if !current.is_empty() {
+        sentences.push(current);
+    }
+    sentences
+}
+
+fn ends_sentence(token: &str) -> bool {
+    let word = token
+        .trim_end_matches(['"', '\'', ')', ']', '\u{201d}', '\u{2019}'])
+        .trim_start_matches(['"', '\'', '(', '[', '\u{201c}', '\u{2018}']);
+    if word.ends_with(['!', '?']) {
+        return true;
+    }
+    let Some(stem) = word.strip_suffix('.') else {
+        return false;
+    };
+
+    let is_abbreviation = ABBREVIATIONS.contains(&word.to_lowercase().as_str())
+        // Initialisms such as "e.g." or "U.S."
+        || stem.contains('.')
+        // Single-letter initials such as "J."
+        || (stem.chars().count() == 1 && stem.chars().all(char::is_alphabetic));
+    !is_abbreviation
+}
+
+/// Collapse chunks with the same text, ignoring case and whitespace
+/// differences. Keeps the first of each group in order and returns, for every
+/// input chunk, the index of the chunk it was kept as in the output.
+pub fn dedup_chunks(chunks: &[Chunk]) -> (Vec<Chunk>, Vec<usize>) {
+    let mut kept: Vec<Chunk> = Vec::new();
+    let mut remap = Vec::with_capacity(chunks.len());
+    let mut seen: HashMap<String, usize> = HashMap::new();
+
+    for chunk in chunks {
+        let idx = *seen.entry(normalized_text(chunk)).or_insert_with(|| {
+            kept.push(chunk.clone());
+            kept.len() - 1
+        });
+        remap.push(idx);
+    }
+    (kept, remap)
+}
+
+/// Chunk text lowercased with whitespace runs collapsed, for duplicate checks
+pub(crate) fn normalized_text(chunk: &Chunk) -> String {
+    chunk.as_text().to_lowercase().split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Build text chunks in order, with ids, source file and neighbour links filled in
+fn link_chunks(source_file: &str, pieces: Vec<(String, HashMap<String, serde_json::Value>)>) -> Vec<Chunk> {
+    let id = |n: usize| format!("{}-{}", source_file, n);
+    let count = pieces.len();
+
+    pieces
+        .into_iter()
+        .enumerate()
+        .map(|(n, (text, custom_metadata))| Chunk {
+            id: id(n),
+            content: ChunkContent::Text {
+                text: TextChunk {
+                    id: id(n),
+                    html: None,
+                    markdown: None,
+                    text,
+                },
+            },
+            metadata: ChunkMetadata {
+                source_file: Some(source_file.to_string()),
+                preceding_chunk_id: n.checked_sub(1).map(id),
+                following_chunk_id: (n + 1 < count).then(|| id(n + 1)),
+                custom_metadata,
+                ..Default::default()
+            },
+            enrichment: None,
+        })
+        .collect()
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "lowercase")]
 pub enum ChunkContent {
@@ -123,12 +293,248 @@ pub struct SearchResult {
     pub score: f32,
     pub chunk: Chunk,
     pub search_type: SearchType,
+    /// 1-based position in the result list
+    #[serde(default)]
+    pub rank: usize,
+}
+
+impl SearchResult {
+    /// Serialize as a `VersionedSearchResult` tagged with the current schema
+    /// version, for results persisted outside the process
+    pub fn to_value(&self) -> Result<serde_json::Value> {
+        let versioned = VersionedSearchResult {
+            schema_version: SEARCH_RESULT_SCHEMA_VERSION,
+            result: self.clone(),
+        };
+        Ok(serde_json::to_value(versioned)?)
+    }
+
+    /// Read a result written by `to_value` or plain serde, by this or an
+    /// older version of the crate. Missing fields added since take their
+    /// defaults and unknown fields are ignored; a schema version newer than
+    /// this crate understands is an error.
+    pub fn from_value(value: serde_json::Value) -> Result<Self> {
+        let versioned: VersionedSearchResult =
+            serde_json::from_value(value).map_err(|e| anyhow::anyhow!("Invalid search result: {}", e))?;
+        if versioned.schema_version > SEARCH_RESULT_SCHEMA_VERSION {
+            return Err(anyhow::anyhow!(
+                "Search result schema version {} is newer than the supported version {}",
+                versioned.schema_version, SEARCH_RESULT_SCHEMA_VERSION
+            ));
+        }
+        Ok(versioned.result)
+    }
 }
 
+/// Schema version written by `SearchResult::to_value`. Bumped when the
+/// serialized shape of `SearchResult` changes; 0 means unversioned
+pub const SEARCH_RESULT_SCHEMA_VERSION: u32 = 1;
+
+/// A `SearchResult` with its fields inline next to `schema_version`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VersionedSearchResult {
+    #[serde(default)]
+    pub schema_version: u32,
+    #[serde(flatten)]
+    pub result: SearchResult,
+}
+
+/// Serialized as the lowercase strings `keyword`, `semantic` and `hybrid`
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum SearchType {
     Keyword,
     Semantic,
     Hybrid,
-}
\ No newline at end of file
+}
+
+impl SearchType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SearchType::Keyword => "keyword",
+            SearchType::Semantic => "semantic",
+            SearchType::Hybrid => "hybrid",
+        }
+    }
+}
+
+impl std::fmt::Display for SearchType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for SearchType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "keyword" => Ok(SearchType::Keyword),
+            "semantic" => Ok(SearchType::Semantic),
+            "hybrid" => Ok(SearchType::Hybrid),
+            _ => Err(anyhow::anyhow!(
+                "Unknown search type {:?}, expected keyword, semantic or hybrid",
+                s
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(chunks: &[Chunk]) -> Vec<String> {
+        chunks.iter().map(Chunk::as_text).collect()
+    }
+
+    #[test]
+    fn test_chunk_text_windows() {
+        let text = (0..10).map(|i| format!("w{}", i)).collect::<Vec<_>>().join(" ");
+        let chunks = chunk_text(&text, "doc.txt", 4, 1).unwrap();
+        assert_eq!(texts(&chunks), vec!["w0 w1 w2 w3", "w3 w4 w5 w6", "w6 w7 w8 w9"]);
+
+        let no_overlap = chunk_text(&text, "doc.txt", 4, 0).unwrap();
+        assert_eq!(texts(&no_overlap), vec!["w0 w1 w2 w3", "w4 w5 w6 w7", "w8 w9"]);
+
+        assert_eq!(chunk_text(&text, "doc.txt", 20, 5).unwrap().len(), 1);
+        assert!(chunk_text("  \n ", "doc.txt", 4, 1).unwrap().is_empty());
+        assert!(chunk_text(&text, "doc.txt", 0, 0).is_err());
+        assert!(chunk_text(&text, "doc.txt", 4, 4).is_err());
+    }
+
+    #[test]
+    fn test_as_text_for_images() {
+        let mut chunk = chunk_text("unused", "doc", 4, 0).unwrap().remove(0);
+        chunk.content = ChunkContent::Image {
+            image: ImageChunk {
+                id: "img".to_string(),
+                bounding_box: [0.0; 4],
+                image_path: None,
+                caption: Some("A cat".to_string()),
+                ocr_text: Some("MEOW".to_string()),
+            },
+        };
+        assert_eq!(chunk.as_text(), "A cat\n\nMEOW");
+    }
+
+    #[test]
+    fn test_chunks_are_linked() {
+        let chunks = chunk_text("a b c d e f", "notes.md", 2, 0).unwrap();
+        let ids: Vec<&str> = chunks.iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(ids, vec!["notes.md-0", "notes.md-1", "notes.md-2"]);
+
+        let middle = &chunks[1].metadata;
+        assert_eq!(middle.source_file.as_deref(), Some("notes.md"));
+        assert_eq!(middle.preceding_chunk_id.as_deref(), Some("notes.md-0"));
+        assert_eq!(middle.following_chunk_id.as_deref(), Some("notes.md-2"));
+        assert_eq!(chunks[0].metadata.preceding_chunk_id, None);
+        assert_eq!(chunks[2].metadata.following_chunk_id, None);
+    }
+
+    #[test]
+    fn test_chunk_by_sentences_keeps_sentences_whole() {
+        let text = "One two three. Four five! Six seven eight nine? Ten.";
+        let chunks = chunk_by_sentences(text, "doc", 5).unwrap();
+        assert_eq!(texts(&chunks), vec!["One two three. Four five!", "Six seven eight nine? Ten."]);
+        assert!(chunks.iter().all(|c| c.metadata.custom_metadata.is_empty()));
+        assert!(chunk_by_sentences(text, "doc", 0).is_err());
+    }
+
+    #[test]
+    fn test_chunk_by_sentences_abbreviations() {
+        let text = "Dr. Smith met J. Doe in the U.S. today. Bring tools, e.g. a hammer. \
+                    He said \"stop.\" (Really.) Done etc. here.";
+        let sentences: Vec<String> = split_sentences(text).into_iter().map(|s| s.join(" ")).collect();
+        assert_eq!(
+            sentences,
+            vec![
+                "Dr. Smith met J. Doe in the U.S. today.",
+                "Bring tools, e.g. a hammer.",
+                "He said \"stop.\"",
+                "(Really.)",
+                "Done etc. here.",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_chunk_by_sentences_oversized() {
+        let text = "Short one. This sentence is far too long to fit. End.";
+        let chunks = chunk_by_sentences(text, "doc", 3).unwrap();
+        assert_eq!(texts(&chunks), vec!["Short one.", "This sentence is far too long to fit.", "End."]);
+        let oversized: Vec<bool> = chunks
+            .iter()
+            .map(|c| c.metadata.custom_metadata.get("oversized") == Some(&serde_json::Value::Bool(true)))
+            .collect();
+        assert_eq!(oversized, vec![false, true, false]);
+        assert_eq!(chunks[2].metadata.preceding_chunk_id.as_deref(), Some("doc-1"));
+    }
+
+    #[test]
+    fn test_dedup_chunks() {
+        let mut chunks = chunk_text("a b c d", "doc", 2, 0).unwrap();
+        chunks.extend(chunk_text("A  B   e", "other", 2, 0).unwrap());
+        // "a b", "c d", "A B", "e"
+        let (kept, remap) = dedup_chunks(&chunks);
+        assert_eq!(texts(&kept), vec!["a b", "c d", "e"]);
+        assert_eq!(remap, vec![0, 1, 0, 2]);
+        assert_eq!(dedup_chunks(&[]).0.len(), 0);
+    }
+
+    #[test]
+    fn test_rank_defaults_to_zero() {
+        let result = SearchResult {
+            score: 0.5,
+            chunk: chunk_text("hello world", "doc", 4, 0).unwrap().remove(0),
+            search_type: SearchType::Hybrid,
+            rank: 3,
+        };
+        let mut old = serde_json::to_value(&result).unwrap();
+        assert_eq!(old["rank"], 3);
+        // Results serialized before ranks existed
+        old.as_object_mut().unwrap().remove("rank");
+        let back: SearchResult = serde_json::from_value(old).unwrap();
+        assert_eq!((back.rank, back.score), (0, 0.5));
+    }
+
+    fn result() -> SearchResult {
+        SearchResult {
+            score: 0.5,
+            chunk: chunk_text("hello world", "doc", 4, 0).unwrap().remove(0),
+            search_type: SearchType::Hybrid,
+            rank: 3,
+        }
+    }
+
+    #[test]
+    fn test_search_result_versioning() {
+        let value = result().to_value().unwrap();
+        assert_eq!(value["schema_version"], SEARCH_RESULT_SCHEMA_VERSION);
+        assert_eq!(value["search_type"], "hybrid");
+        let back = SearchResult::from_value(value).unwrap();
+        assert_eq!((back.score, back.rank, back.chunk.id.as_str()), (0.5, 3, "doc-0"));
+
+        // A result serialized before versioning and ranks existed
+        let mut old = serde_json::to_value(result()).unwrap();
+        old.as_object_mut().unwrap().remove("rank");
+        old["unknown_field"] = serde_json::json!(1);
+        let back = SearchResult::from_value(old).unwrap();
+        assert_eq!((back.rank, back.search_type), (0, SearchType::Hybrid));
+
+        let mut newer = result().to_value().unwrap();
+        newer["schema_version"] = serde_json::json!(SEARCH_RESULT_SCHEMA_VERSION + 1);
+        assert!(SearchResult::from_value(newer).unwrap_err().to_string().contains("newer"));
+        assert!(SearchResult::from_value(serde_json::json!({"score": 1.0})).is_err());
+    }
+
+    #[test]
+    fn test_search_type_strings() {
+        for mode in [SearchType::Keyword, SearchType::Semantic, SearchType::Hybrid] {
+            assert_eq!(mode.to_string().parse::<SearchType>().unwrap(), mode);
+            assert_eq!(serde_json::to_value(&mode).unwrap(), mode.as_str());
+        }
+        assert!("Keyword".parse::<SearchType>().is_err());
+        assert!(serde_json::from_str::<SearchType>("\"fuzzy\"").is_err());
+    }
+}
@@ -1,68 +1,1237 @@
 use serde::{Deserialize, Serialize};
-use anyhow::Result;
+use anyhow::{Context, Result};
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::path::Path;
 
+#[cfg(feature = "f16")]
+use crate::half_store::HalfStore;
+use crate::quantized::QuantizedStore;
+use crate::ranking::{bottom_k, bottom_k_in_place, top_k, top_k_in_place};
+use crate::trace::span;
+
+const FILE_MAGIC: &[u8; 4] = b"EVS1";
+
+/// Bumped whenever the binary layout written by `save_binary` changes
+const FILE_FORMAT_VERSION: u32 = 1;
+
+/// magic, version, dimension, row count, removed count, metric, normalized
+const HEADER_LEN: usize = 4 + 4 + 8 + 8 + 8 + 1 + 1;
+
+/// How `VectorStore::search` compares a query with stored embeddings.
+/// Results carry the metric's own value and are ordered best first, see
+/// `higher_is_better`
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum DistanceMetric {
+    /// Cosine similarity in [-1, 1]; higher is better
+    #[default]
+    Cosine,
+    /// L2 distance; lower is better, results are sorted ascending
+    Euclidean,
+    /// Raw dot product; higher is better
+    DotProduct,
+}
+
+/// Function comparing two equal-length vectors
+type Scorer = fn(&[f32], &[f32]) -> f32;
+
+impl DistanceMetric {
+    /// Compare two vectors with this metric
+    pub fn score(&self, a: &[f32], b: &[f32]) -> f32 {
+        self.scorer()(a, b)
+    }
+
+    fn code(&self) -> u8 {
+        match self {
+            DistanceMetric::Cosine => 0,
+            DistanceMetric::Euclidean => 1,
+            DistanceMetric::DotProduct => 2,
+        }
+    }
+
+    fn from_code(code: u8) -> Option<Self> {
+        match code {
+            0 => Some(DistanceMetric::Cosine),
+            1 => Some(DistanceMetric::Euclidean),
+            2 => Some(DistanceMetric::DotProduct),
+            _ => None,
+        }
+    }
+
+    /// Whether larger scores are better. Similarities (`Cosine`, `DotProduct`)
+    /// are ranked descending, distances (`Euclidean`) ascending, and every
+    /// search ranks by this so the best match always comes first
+    pub fn higher_is_better(&self) -> bool {
+        match self {
+            DistanceMetric::Euclidean => false,
+            DistanceMetric::Cosine | DistanceMetric::DotProduct => true,
+        }
+    }
+
+    /// Whether `score` is at least as good as `threshold` under this metric
+    pub(crate) fn reaches(&self, score: f32, threshold: f32) -> bool {
+        if self.higher_is_better() { score >= threshold } else { score <= threshold }
+    }
+
+    fn scorer(&self) -> Scorer {
+        match self {
+            DistanceMetric::Cosine => VectorStore::cosine_similarity,
+            DistanceMetric::Euclidean => VectorStore::euclidean_distance,
+            DistanceMetric::DotProduct => VectorStore::dot_product,
+        }
+    }
+}
+
+/// Brute-force store of embeddings.
+///
+/// `Send + Sync`: searches take `&self`, so one store can serve many threads.
+/// Wrap it in `SharedIndex` to also allow writes.
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct VectorStore {
     pub embeddings: Vec<Vec<f32>>,
     pub dimension: usize,
+    #[serde(default)]
+    pub metric: DistanceMetric,
+    /// Every stored embedding has unit L2 norm (or is all zeros), so cosine
+    /// similarity reduces to a dot product
+    #[serde(default)]
+    pub normalized: bool,
+    /// Indices removed with `remove`; their slots are kept so other
+    /// indices stay valid, but they never appear in search results
+    #[serde(default)]
+    tombstones: HashSet<usize>,
 }
 
 impl VectorStore {
+    /// Errors if the embeddings differ in dimension or contain NaN or
+    /// infinity, naming the first offending embedding. Validation runs in
+    /// parallel with the `rayon` feature.
     pub fn new(embeddings: Vec<Vec<f32>>) -> Result<Self> {
+        Self::with_metric(embeddings, DistanceMetric::Cosine)
+    }
+
+    pub fn with_metric(embeddings: Vec<Vec<f32>>, metric: DistanceMetric) -> Result<Self> {
         if embeddings.is_empty() {
             return Ok(Self {
                 embeddings: vec![],
                 dimension: 0,
+                metric,
+                normalized: false,
+                tombstones: HashSet::new(),
             });
         }
 
         let dimension = embeddings[0].len();
+        let is_valid = |emb: &Vec<f32>| emb.len() == dimension && emb.iter().all(|x| x.is_finite());
 
-        for (i, emb) in embeddings.iter().enumerate() {
+        // Checked in parallel with rayon, but always reporting the lowest bad index
+        #[cfg(feature = "rayon")]
+        let first_invalid = {
+            use rayon::prelude::*;
+            embeddings.par_iter().position_first(|emb| !is_valid(emb))
+        };
+        #[cfg(not(feature = "rayon"))]
+        let first_invalid = embeddings.iter().position(|emb| !is_valid(emb));
+
+        if let Some(i) = first_invalid {
+            let emb = &embeddings[i];
             if emb.len() != dimension {
                 return Err(anyhow::anyhow!(
                     "Embedding {} has dimension {} but expected {}",
                     i, emb.len(), dimension
                 ));
             }
+            check_finite(emb).map_err(|e| anyhow::anyhow!("Embedding {} {}", i, e))?;
         }
 
-        Ok(Self { embeddings, dimension })
+        Ok(Self {
+            embeddings,
+            dimension,
+            metric,
+            normalized: false,
+            tombstones: HashSet::new(),
+        })
     }
 
+    /// Build a cosine store whose embeddings are L2-normalized up front,
+    /// so `search` only needs a dot product per stored vector.
+    /// Zero vectors are left as zeros and always score 0.0.
+    pub fn new_normalized(mut embeddings: Vec<Vec<f32>>) -> Result<Self> {
+        for emb in embeddings.iter_mut() {
+            Self::l2_normalize(emb);
+        }
+        let mut store = Self::new(embeddings)?;
+        store.normalized = true;
+        Ok(store)
+    }
+
+    /// Append an embedding and return its index. The first embedding added
+    /// to an empty store sets its dimension. NaN and infinite values are rejected.
+    pub fn add(&mut self, mut embedding: Vec<f32>) -> Result<usize> {
+        if self.embeddings.is_empty() && self.dimension == 0 {
+            self.dimension = embedding.len();
+        } else if embedding.len() != self.dimension {
+            return Err(anyhow::anyhow!(
+                "Embedding has dimension {} but expected {}",
+                embedding.len(), self.dimension
+            ));
+        }
+        check_finite(&embedding).map_err(|e| anyhow::anyhow!("Embedding {}", e))?;
+
+        if self.normalized {
+            Self::l2_normalize(&mut embedding);
+        }
+        self.embeddings.push(embedding);
+        Ok(self.embeddings.len() - 1)
+    }
+
+    /// Remove the embedding at `idx`. The slot is tombstoned rather than
+    /// compacted, so the indices of other embeddings stay valid.
+    pub fn remove(&mut self, idx: usize) -> Result<()> {
+        if !self.contains(idx) {
+            return Err(anyhow::anyhow!("No embedding at index {}", idx));
+        }
+        self.tombstones.insert(idx);
+        Ok(())
+    }
+
+    /// Whether `idx` holds a live (not removed) embedding
+    pub fn contains(&self, idx: usize) -> bool {
+        idx < self.embeddings.len() && !self.tombstones.contains(&idx)
+    }
+
+    /// Number of live embeddings
+    pub fn len(&self) -> usize {
+        self.embeddings.len() - self.tombstones.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Live embeddings with their indices, skipping removed slots
+    fn live_embeddings(&self) -> impl Iterator<Item = (usize, &Vec<f32>)> {
+        self.embeddings
+            .iter()
+            .enumerate()
+            .filter(|(idx, _)| !self.tombstones.contains(idx))
+    }
+
+    /// Scale `v` to unit length in place; a zero vector is left unchanged
+    pub fn l2_normalize(v: &mut [f32]) {
+        let norm = v.iter().map(|x| x * x).sum::<f32>().sqrt();
+        if norm > 0.0 {
+            for x in v.iter_mut() {
+                *x /= norm;
+            }
+        }
+    }
+
+    /// Cosine similarity for hot paths. Returns 0.0 when the lengths differ,
+    /// which can hide mixed-up embedding models; use `try_cosine_similarity`
+    /// when the inputs aren't already validated.
     pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
         if a.len() != b.len() {
             return 0.0;
         }
 
-        let mut dot_product = 0.0;
-        let mut norm_a = 0.0;
-        let mut norm_b = 0.0;
+        let (dot_product, norm_a, norm_b) = dot_and_norms(a, b);
+
+        let denominator = (norm_a.sqrt() * norm_b.sqrt()).max(1e-9);
+        dot_product / denominator
+    }
 
-        for i in 0..a.len() {
-            dot_product += a[i] * b[i];
-            norm_a += a[i] * a[i];
-            norm_b += b[i] * b[i];
+    /// `cosine_similarity` of a query `a` whose L2 norm is already known, so
+    /// scoring one query against many vectors computes its norm only once.
+    /// Returns 0.0 when the lengths differ.
+    pub fn cosine_similarity_with_query_norm(a: &[f32], b: &[f32], query_norm: f32) -> f32 {
+        if a.len() != b.len() {
+            return 0.0;
         }
 
-        let denominator = (norm_a.sqrt() * norm_b.sqrt()).max(1e-9);
+        let (dot_product, norm_b) = dot_and_norm_b(a, b);
+
+        let denominator = (query_norm * norm_b.sqrt()).max(1e-9);
         dot_product / denominator
     }
 
-    pub fn search(&self, query_embedding: &[f32], limit: usize) -> Vec<(usize, f32)> {
-        if self.embeddings.is_empty() || query_embedding.len() != self.dimension {
-            return vec![];
+    /// Cosine similarity that errors on a dimension mismatch
+    pub fn try_cosine_similarity(a: &[f32], b: &[f32]) -> Result<f32> {
+        if a.len() != b.len() {
+            return Err(anyhow::anyhow!(
+                "Cannot compare vectors of dimension {} and {}",
+                a.len(), b.len()
+            ));
+        }
+        Ok(Self::cosine_similarity(a, b))
+    }
+
+    pub fn dot_product(a: &[f32], b: &[f32]) -> f32 {
+        a.iter().zip(b).map(|(x, y)| x * y).sum()
+    }
+
+    pub fn euclidean_distance(a: &[f32], b: &[f32]) -> f32 {
+        a.iter()
+            .zip(b)
+            .map(|(x, y)| (x - y) * (x - y))
+            .sum::<f32>()
+            .sqrt()
+    }
+
+    /// Find the `limit` nearest embeddings (all of them if `limit` is 0).
+    /// The returned f32 is a similarity for `Cosine` and `DotProduct` (best first, descending) and a distance
+    /// for `Euclidean` (nearest first, ascending).
+    /// An empty store (dimension 0) returns no results for any query; otherwise
+    /// errors if the query dimension doesn't match the store or the query
+    /// contains NaN or infinity.
+    pub fn search(&self, query_embedding: &[f32], limit: usize) -> Result<Vec<(usize, f32)>> {
+        self.search_with_min_score(query_embedding, limit, None)
+    }
+
+    /// Like `search`, but drops results that don't reach `min_score` before
+    /// taking the top `limit`, so fewer than `limit` may come back. The
+    /// threshold is a similarity floor for `Cosine` and `DotProduct` and a
+    /// maximum distance for `Euclidean`. `None` behaves like `search`.
+    pub fn search_with_min_score(
+        &self,
+        query_embedding: &[f32],
+        limit: usize,
+        min_score: Option<f32>,
+    ) -> Result<Vec<(usize, f32)>> {
+        if self.embeddings.is_empty() {
+            return Ok(vec![]);
         }
+        self.check_query(query_embedding)?;
+
+        // Scoring is fused with ranking, so one span times both
+        let span = span!("vector_search", candidates, results);
+        span.record("candidates", self.len());
+        let similarities = self
+            .score_indices(query_embedding, self.live_embeddings().map(|(idx, _)| idx))
+            .filter(|&(_, score)| min_score.is_none_or(|threshold| self.metric.reaches(score, threshold)));
+        let results = rank_results(self.metric, similarities, limit);
+        span.record("results", results.len());
+        Ok(results)
+    }
+
+    /// `search` writing its results into `out` instead of a new `Vec`, so a
+    /// buffer reused across searches stops allocating once it has grown to
+    /// the number of live embeddings. `out` is cleared first, and is left
+    /// empty on error; it is borrowed mutably, so it can't alias the store.
+    pub fn search_into(&self, query_embedding: &[f32], limit: usize, out: &mut Vec<(usize, f32)>) -> Result<()> {
+        out.clear();
+        if self.embeddings.is_empty() {
+            return Ok(());
+        }
+        self.check_query(query_embedding)?;
+
+        out.extend(self.score_indices(query_embedding, self.live_embeddings().map(|(idx, _)| idx)));
+        if self.metric.higher_is_better() {
+            top_k_in_place(out, limit);
+        } else {
+            bottom_k_in_place(out, limit);
+        }
+        Ok(())
+    }
+
+    /// Cosine search for unit-length embeddings and query (as returned by
+    /// most embedding APIs), scoring by dot product alone whatever the
+    /// store's metric. Results are only meaningful if everything really is
+    /// normalized; debug builds assert the query is.
+    pub fn search_dot(&self, query_embedding: &[f32], limit: usize) -> Result<Vec<(usize, f32)>> {
+        if self.embeddings.is_empty() {
+            return Ok(vec![]);
+        }
+        self.check_query(query_embedding)?;
+        debug_assert!(
+            (Self::dot_product(query_embedding, query_embedding).sqrt() - 1.0).abs() < 1e-3,
+            "search_dot query is not unit length"
+        );
+
+        let similarities = self
+            .live_embeddings()
+            .map(|(idx, emb)| (idx, dot_lanes(query_embedding, emb)));
+        Ok(top_k(similarities, limit))
+    }
+
+    /// Like `search_with_min_score`, but also returns how many embeddings
+    /// reached `min_score` before truncating to `limit` (every live one if `None`)
+    pub fn search_with_count(
+        &self,
+        query_embedding: &[f32],
+        limit: usize,
+        min_score: Option<f32>,
+    ) -> Result<(Vec<(usize, f32)>, usize)> {
+        if self.embeddings.is_empty() {
+            return Ok((vec![], 0));
+        }
+        self.check_query(query_embedding)?;
+
+        let similarities: Vec<(usize, f32)> = self
+            .score_indices(query_embedding, self.live_embeddings().map(|(idx, _)| idx))
+            .filter(|&(_, score)| min_score.is_none_or(|threshold| self.metric.reaches(score, threshold)))
+            .collect();
+        let total = similarities.len();
+        Ok((rank_results(self.metric, similarities, limit), total))
+    }
+
+    /// Like `search`, but only scores embeddings whose index is in `allowed`,
+    /// e.g. chunks precomputed to match a `ChunkMetadata` condition.
+    /// `limit` applies after filtering.
+    pub fn search_filtered(
+        &self,
+        query_embedding: &[f32],
+        limit: usize,
+        allowed: &HashSet<usize>,
+    ) -> Result<Vec<(usize, f32)>> {
+        if self.embeddings.is_empty() {
+            return Ok(vec![]);
+        }
+        self.check_query(query_embedding)?;
+        if allowed.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let indices = allowed.iter().copied().filter(|&idx| self.contains(idx));
+        let similarities = self.score_indices(query_embedding, indices);
+        Ok(rank_results(self.metric, similarities, limit))
+    }
+
+    /// Lazily score the query against the embeddings at `indices`, which must be live
+    fn score_indices<'a>(
+        &'a self,
+        query_embedding: &'a [f32],
+        indices: impl Iterator<Item = usize> + 'a,
+    ) -> impl Iterator<Item = (usize, f32)> + 'a {
+        // Cosine needs the query norm only once: either fold it into the
+        // query for a normalized store, or pass it along for every row
+        let (query, query_norm, scorer): (Cow<'a, [f32]>, Option<f32>, Scorer) = match self.metric {
+            DistanceMetric::Cosine if self.normalized => {
+                let mut query = query_embedding.to_vec();
+                Self::l2_normalize(&mut query);
+                (Cow::Owned(query), None, Self::dot_product)
+            }
+            DistanceMetric::Cosine => {
+                let norm = query_embedding.iter().map(|x| x * x).sum::<f32>().sqrt();
+                (Cow::Borrowed(query_embedding), Some(norm), self.metric.scorer())
+            }
+            _ => (Cow::Borrowed(query_embedding), None, self.metric.scorer()),
+        };
+
+        indices.map(move |idx| {
+            let embedding = &self.embeddings[idx];
+            let score = match query_norm {
+                Some(norm) => Self::cosine_similarity_with_query_norm(&query, embedding, norm),
+                None => scorer(&query, embedding),
+            };
+            (idx, score)
+        })
+    }
+
+    /// Run `search` for every query, returning one result list per query in
+    /// the same order. All query dimensions are checked before any scoring.
+    /// Queries run in parallel with the `rayon` feature.
+    pub fn search_batch(&self, queries: &[Vec<f32>], limit: usize) -> Result<Vec<Vec<(usize, f32)>>> {
+        if self.embeddings.is_empty() {
+            return Ok(vec![Vec::new(); queries.len()]);
+        }
+        for (i, query) in queries.iter().enumerate() {
+            self.check_query(query)
+                .map_err(|e| anyhow::anyhow!("Query {}: {}", i, e))?;
+        }
+
+        #[cfg(feature = "rayon")]
+        {
+            use rayon::prelude::*;
+            queries.par_iter().map(|query| self.search(query, limit)).collect()
+        }
+        #[cfg(not(feature = "rayon"))]
+        {
+            queries.iter().map(|query| self.search(query, limit)).collect()
+        }
+    }
+
+    /// Pairwise cosine similarity between all stored embeddings, indexed like
+    /// `embeddings`. Rows and columns of removed embeddings are all 0.0, and
+    /// the diagonal is 1.0 except for zero vectors.
+    ///
+    /// Costs O(N² · d) time and O(N²) memory, so this errors if the store holds
+    /// more than `max_embeddings` slots. Rows are computed in parallel with the
+    /// `rayon` feature.
+    pub fn similarity_matrix(&self, max_embeddings: usize) -> Result<Vec<Vec<f32>>> {
+        let n = self.embeddings.len();
+        if n > max_embeddings {
+            return Err(anyhow::anyhow!(
+                "Similarity matrix for {} embeddings exceeds the limit of {}",
+                n, max_embeddings
+            ));
+        }
+
+        // Upper triangle only, row i holds columns i..n
+        let upper_row = |i: usize| -> Vec<f32> {
+            if !self.contains(i) {
+                return vec![0.0; n - i];
+            }
+            (i..n)
+                .map(|j| {
+                    if j == i {
+                        // Exact, rather than whatever rounding gives
+                        let nonzero = self.embeddings[i].iter().any(|&x| x != 0.0);
+                        if nonzero { 1.0 } else { 0.0 }
+                    } else if self.contains(j) {
+                        Self::cosine_similarity(&self.embeddings[i], &self.embeddings[j])
+                    } else {
+                        0.0
+                    }
+                })
+                .collect()
+        };
+
+        #[cfg(feature = "rayon")]
+        let upper: Vec<Vec<f32>> = {
+            use rayon::prelude::*;
+            (0..n).into_par_iter().map(upper_row).collect()
+        };
+        #[cfg(not(feature = "rayon"))]
+        let upper: Vec<Vec<f32>> = (0..n).map(upper_row).collect();
+
+        Ok((0..n)
+            .map(|i| (0..n).map(|j| if j >= i { upper[i][j - i] } else { upper[j][i - j] }).collect())
+            .collect())
+    }
+
+    /// Pairs `(i, j)` with `i < j` of live embeddings whose cosine similarity
+    /// is at least `threshold` (e.g. 0.98), ordered by `i` then `j`. Use it
+    /// to prune near-duplicate chunks before or after ingestion.
+    ///
+    /// Brute force over all pairs, but each comparison stops early once the
+    /// remaining dimensions can no longer lift it to `threshold`.
+    pub fn find_near_duplicates(&self, threshold: f32) -> Vec<(usize, usize)> {
+        const BLOCK: usize = 32;
+
+        // Unit vectors, plus the norm of each one's tail from every block start
+        let live: Vec<(usize, Vec<f32>)> = self
+            .live_embeddings()
+            .map(|(idx, emb)| {
+                let mut unit = emb.clone();
+                Self::l2_normalize(&mut unit);
+                (idx, unit)
+            })
+            .filter(|(_, unit)| unit.iter().any(|&x| x != 0.0))
+            .collect();
+        let tails: Vec<Vec<f32>> = live
+            .iter()
+            .map(|(_, unit)| {
+                let mut tail = vec![0.0f32; unit.len().div_ceil(BLOCK) + 1];
+                for block in (0..unit.len().div_ceil(BLOCK)).rev() {
+                    let start = block * BLOCK;
+                    let end = (start + BLOCK).min(unit.len());
+                    let sq: f32 = unit[start..end].iter().map(|x| x * x).sum();
+                    tail[block] = (tail[block + 1].powi(2) + sq).sqrt();
+                }
+                tail
+            })
+            .collect();
+
+        let mut pairs = Vec::new();
+        for a in 0..live.len() {
+            for b in a + 1..live.len() {
+                let (x, y) = (&live[a].1, &live[b].1);
+                let mut dot = 0.0;
+                let mut similar = true;
+                for (block, start) in (0..x.len()).step_by(BLOCK).enumerate() {
+                    // Cauchy-Schwarz bound on what the remaining blocks can add
+                    if dot + tails[a][block] * tails[b][block] < threshold {
+                        similar = false;
+                        break;
+                    }
+                    let end = (start + BLOCK).min(x.len());
+                    dot += Self::dot_product(&x[start..end], &y[start..end]);
+                }
+                if similar && dot >= threshold {
+                    pairs.push((live[a].0, live[b].0));
+                }
+            }
+        }
+        pairs
+    }
+
+    /// Write the store in a compact binary format, much smaller than JSON.
+    ///
+    /// Layout (all little-endian): magic `EVS1`, u32 format version,
+    /// u64 dimension, u64 row count, u64 removed count, u8 metric,
+    /// u8 normalized flag, then every row as `dimension` f32 values (removed
+    /// rows included, so indices survive), then the removed indices as u64.
+    pub fn save_binary<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        if self.dimension == 0 && !self.embeddings.is_empty() {
+            return Err(anyhow::anyhow!("Cannot save {} embeddings of dimension 0", self.embeddings.len()));
+        }
+        let rows = self.embeddings.len() * self.dimension * 4;
+        let mut bytes = Vec::with_capacity(HEADER_LEN + rows + self.tombstones.len() * 8);
+
+        bytes.extend_from_slice(FILE_MAGIC);
+        bytes.extend_from_slice(&FILE_FORMAT_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&(self.dimension as u64).to_le_bytes());
+        bytes.extend_from_slice(&(self.embeddings.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(&(self.tombstones.len() as u64).to_le_bytes());
+        bytes.push(self.metric.code());
+        bytes.push(self.normalized as u8);
+
+        for value in self.embeddings.iter().flatten() {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        let mut removed: Vec<usize> = self.tombstones.iter().copied().collect();
+        removed.sort_unstable();
+        for idx in removed {
+            bytes.extend_from_slice(&(idx as u64).to_le_bytes());
+        }
+
+        std::fs::write(path, bytes)
+            .with_context(|| format!("Failed to write vector store to {}", path.display()))
+    }
+
+    /// Read a store written by `save_binary`, checking the file size
+    /// matches its header
+    pub fn load_binary<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path)
+            .with_context(|| format!("Failed to read vector store from {}", path.display()))?;
+
+        if bytes.len() < HEADER_LEN || &bytes[..4] != FILE_MAGIC {
+            return Err(anyhow::anyhow!("{} is not a vector store file", path.display()));
+        }
+        let u32_at = |at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+        let u64_at = |at: usize| u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap());
+
+        let version = u32_at(4);
+        if version != FILE_FORMAT_VERSION {
+            return Err(anyhow::anyhow!(
+                "Vector store file version {} is not supported (expected {})",
+                version, FILE_FORMAT_VERSION
+            ));
+        }
+
+        let dimension = u64_at(8) as usize;
+        let count = u64_at(16) as usize;
+        let removed = u64_at(24) as usize;
+        let metric = DistanceMetric::from_code(bytes[32])
+            .ok_or_else(|| anyhow::anyhow!("Unknown distance metric code {}", bytes[32]))?;
+        let normalized = bytes[33] != 0;
+
+        // Zero-dimension rows take no bytes, so the size check below can't
+        // bound their count
+        if dimension == 0 && count > 0 {
+            return Err(anyhow::anyhow!(
+                "{} describes {} rows of dimension 0",
+                path.display(), count
+            ));
+        }
+        let expected = count
+            .checked_mul(dimension)
+            .and_then(|n| n.checked_mul(4))
+            .and_then(|n| n.checked_add(removed.checked_mul(8)?))
+            .and_then(|n| n.checked_add(HEADER_LEN));
+        if expected != Some(bytes.len()) {
+            return Err(anyhow::anyhow!(
+                "{} is {} bytes but its header describes {} rows of dimension {}",
+                path.display(), bytes.len(), count, dimension
+            ));
+        }
+
+        let rows_end = HEADER_LEN + count * dimension * 4;
+        let embeddings: Vec<Vec<f32>> = if dimension == 0 {
+            Vec::new()
+        } else {
+            bytes[HEADER_LEN..rows_end]
+                .chunks_exact(dimension * 4)
+                .map(|row| {
+                    row.chunks_exact(4)
+                        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                        .collect()
+                })
+                .collect()
+        };
+        for (i, emb) in embeddings.iter().enumerate() {
+            check_finite(emb).map_err(|e| anyhow::anyhow!("Embedding {} {}", i, e))?;
+        }
+
+        let mut tombstones = HashSet::with_capacity(removed);
+        for at in (rows_end..bytes.len()).step_by(8) {
+            let idx = u64_at(at) as usize;
+            if idx >= count {
+                return Err(anyhow::anyhow!("Removed index {} is out of range", idx));
+            }
+            tombstones.insert(idx);
+        }
+
+        Ok(Self {
+            embeddings,
+            dimension,
+            metric,
+            normalized,
+            tombstones,
+        })
+    }
 
-        let mut similarities: Vec<(usize, f32)> = self
+    /// Compress embeddings to int8 for roughly 4x less memory, see `QuantizedStore`
+    pub fn quantize(&self) -> QuantizedStore {
+        QuantizedStore::from_store(self)
+    }
+
+    /// Convert embeddings to f16 for half the memory, see `HalfStore`
+    #[cfg(feature = "f16")]
+    pub fn to_f16(&self) -> HalfStore {
+        HalfStore::from_store(self)
+    }
+
+    /// Check a query can be scored: right dimension and no NaN or infinity
+    pub(crate) fn check_query(&self, query_embedding: &[f32]) -> Result<()> {
+        if query_embedding.len() != self.dimension {
+            return Err(anyhow::anyhow!(
+                "Query has dimension {} but the store expects {}",
+                query_embedding.len(), self.dimension
+            ));
+        }
+        check_finite(query_embedding).map_err(|e| anyhow::anyhow!("Query {}", e))
+    }
+}
+
+/// NaN or infinite components would poison every score computed from the vector
+pub(crate) fn check_finite(embedding: &[f32]) -> Result<()> {
+    match embedding.iter().position(|x| !x.is_finite()) {
+        Some(pos) => Err(anyhow::anyhow!(
+            "has non-finite value {} at position {}",
+            embedding[pos], pos
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Keep the `limit` best results for `metric`, best first, using a bounded
+/// heap rather than sorting every score. Ties go to the lower index.
+pub(crate) fn rank_results(
+    metric: DistanceMetric,
+    results: impl IntoIterator<Item = (usize, f32)>,
+    limit: usize,
+) -> Vec<(usize, f32)> {
+    if metric.higher_is_better() {
+        top_k(results, limit)
+    } else {
+        bottom_k(results, limit)
+    }
+}
+
+/// Dot product of two equal-length vectors with eight independent partial
+/// sums, which the compiler can vectorize without the `simd` feature
+fn dot_lanes(a: &[f32], b: &[f32]) -> f32 {
+    let mut lanes = [0.0f32; 8];
+    let a_chunks = a.chunks_exact(8);
+    let b_chunks = b.chunks_exact(8);
+    let (a_rest, b_rest) = (a_chunks.remainder(), b_chunks.remainder());
+
+    for (ca, cb) in a_chunks.zip(b_chunks) {
+        for i in 0..8 {
+            lanes[i] += ca[i] * cb[i];
+        }
+    }
+
+    let rest: f32 = a_rest.iter().zip(b_rest).map(|(x, y)| x * y).sum();
+    lanes.iter().sum::<f32>() + rest
+}
+
+/// Dot product of two equal-length vectors and the squared norm of `b`
+#[cfg(not(feature = "simd"))]
+fn dot_and_norm_b(a: &[f32], b: &[f32]) -> (f32, f32) {
+    let mut dot_product = 0.0;
+    let mut norm_b = 0.0;
+
+    for i in 0..a.len() {
+        dot_product += a[i] * b[i];
+        norm_b += b[i] * b[i];
+    }
+
+    (dot_product, norm_b)
+}
+
+/// Dot product of two equal-length vectors and the squared norm of `b`,
+/// eight lanes at a time with a scalar loop for the remainder
+#[cfg(feature = "simd")]
+fn dot_and_norm_b(a: &[f32], b: &[f32]) -> (f32, f32) {
+    use wide::f32x8;
+
+    let mut dot_acc = f32x8::ZERO;
+    let mut norm_b_acc = f32x8::ZERO;
+
+    let a_chunks = a.chunks_exact(8);
+    let b_chunks = b.chunks_exact(8);
+    let (a_rest, b_rest) = (a_chunks.remainder(), b_chunks.remainder());
+
+    for (ca, cb) in a_chunks.zip(b_chunks) {
+        let va = f32x8::from(<[f32; 8]>::try_from(ca).unwrap());
+        let vb = f32x8::from(<[f32; 8]>::try_from(cb).unwrap());
+        dot_acc += va * vb;
+        norm_b_acc += vb * vb;
+    }
+
+    let mut dot_product = dot_acc.reduce_add();
+    let mut norm_b = norm_b_acc.reduce_add();
+
+    for (x, y) in a_rest.iter().zip(b_rest) {
+        dot_product += x * y;
+        norm_b += y * y;
+    }
+
+    (dot_product, norm_b)
+}
+
+/// Dot product and squared norms of two equal-length vectors
+#[cfg(not(feature = "simd"))]
+fn dot_and_norms(a: &[f32], b: &[f32]) -> (f32, f32, f32) {
+    let mut dot_product = 0.0;
+    let mut norm_a = 0.0;
+    let mut norm_b = 0.0;
+
+    for i in 0..a.len() {
+        dot_product += a[i] * b[i];
+        norm_a += a[i] * a[i];
+        norm_b += b[i] * b[i];
+    }
+
+    (dot_product, norm_a, norm_b)
+}
+
+/// Dot product and squared norms of two equal-length vectors, eight lanes
+/// at a time with a scalar loop for the remainder
+#[cfg(feature = "simd")]
+fn dot_and_norms(a: &[f32], b: &[f32]) -> (f32, f32, f32) {
+    use wide::f32x8;
+
+    let mut dot_acc = f32x8::ZERO;
+    let mut norm_a_acc = f32x8::ZERO;
+    let mut norm_b_acc = f32x8::ZERO;
+
+    let a_chunks = a.chunks_exact(8);
+    let b_chunks = b.chunks_exact(8);
+    let (a_rest, b_rest) = (a_chunks.remainder(), b_chunks.remainder());
+
+    for (ca, cb) in a_chunks.zip(b_chunks) {
+        let va = f32x8::from(<[f32; 8]>::try_from(ca).unwrap());
+        let vb = f32x8::from(<[f32; 8]>::try_from(cb).unwrap());
+        dot_acc += va * vb;
+        norm_a_acc += va * va;
+        norm_b_acc += vb * vb;
+    }
+
+    let mut dot_product = dot_acc.reduce_add();
+    let mut norm_a = norm_a_acc.reduce_add();
+    let mut norm_b = norm_b_acc.reduce_add();
+
+    for (x, y) in a_rest.iter().zip(b_rest) {
+        dot_product += x * y;
+        norm_a += x * x;
+        norm_b += y * y;
+    }
+
+    (dot_product, norm_a, norm_b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embedder::mock_embed;
+    use std::path::PathBuf;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("edgerag-vector-{}-{}", std::process::id(), name))
+    }
+
+    fn random_embeddings(count: usize, dim: usize) -> Vec<Vec<f32>> {
+        (0..count).map(|i| mock_embed(&format!("doc {}", i), dim)).collect()
+    }
+
+    fn ids(results: &[(usize, f32)]) -> Vec<usize> {
+        results.iter().map(|&(idx, _)| idx).collect()
+    }
+
+    fn store_2d() -> VectorStore {
+        VectorStore::new(vec![vec![1.0, 0.0], vec![0.0, 1.0], vec![0.7, 0.7], vec![-1.0, 0.0]]).unwrap()
+    }
+
+    #[test]
+    fn test_new_validates_embeddings() {
+        let err = VectorStore::new(vec![vec![1.0, 0.0], vec![1.0], vec![1.0, 2.0, 3.0]]).unwrap_err();
+        assert_eq!(err.to_string(), "Embedding 1 has dimension 1 but expected 2");
+
+        let err = VectorStore::new(vec![vec![1.0, 0.0], vec![1.0, 0.0], vec![f32::NAN, 0.0]]).unwrap_err();
+        assert!(err.to_string().starts_with("Embedding 2 has non-finite value NaN"));
+        assert!(VectorStore::new(vec![vec![f32::INFINITY]]).is_err());
+
+        // The lowest bad index is reported, however validation is split up
+        let mut many = random_embeddings(1000, 4);
+        many[900] = vec![1.0];
+        many[500][2] = f32::NAN;
+        assert!(VectorStore::new(many).unwrap_err().to_string().starts_with("Embedding 500 "));
+        assert_eq!(VectorStore::new(vec![vec![0.5]]).unwrap().dimension, 1);
+    }
+
+    #[test]
+    fn test_cosine_search() {
+        let results = store_2d().search(&[1.0, 0.1], 2).unwrap();
+        assert_eq!(ids(&results), vec![0, 2]);
+        assert!(results[0].1 > results[1].1);
+
+        let all = store_2d().search(&[1.0, 0.1], 0).unwrap();
+        assert_eq!(ids(&all), vec![0, 2, 1, 3]);
+        let expected = VectorStore::cosine_similarity(&[1.0, 0.1], &[0.0, 1.0]);
+        assert_eq!(all[2].1, expected);
+        assert_eq!(store_2d().search(&[1.0, 0.1], usize::MAX).unwrap(), all);
+    }
+
+    #[test]
+    fn test_metrics() {
+        let embeddings = vec![vec![1.0, 0.0], vec![3.0, 0.0], vec![0.0, 2.0]];
+        let query = [2.9, 0.1];
+
+        let cosine = VectorStore::with_metric(embeddings.clone(), DistanceMetric::Cosine).unwrap();
+        let default = VectorStore::new(embeddings.clone()).unwrap();
+        assert_eq!(cosine.search(&query, 0).unwrap(), default.search(&query, 0).unwrap());
+
+        let euclidean = VectorStore::with_metric(embeddings.clone(), DistanceMetric::Euclidean).unwrap();
+        let results = euclidean.search(&query, 10).unwrap();
+        assert_eq!(ids(&results), vec![1, 0, 2]);
+        assert!(results[0].1 < results[1].1 && results[1].1 < results[2].1);
+        assert!((results[0].1 - VectorStore::euclidean_distance(&query, &[3.0, 0.0])).abs() < 1e-6);
+
+        let dot = VectorStore::with_metric(embeddings, DistanceMetric::DotProduct).unwrap();
+        let results = dot.search(&query, 10).unwrap();
+        assert_eq!(ids(&results), vec![1, 0, 2]);
+        assert!((results[0].1 - 8.7).abs() < 1e-5);
+        assert_eq!(VectorStore::dot_product(&[1.0, 2.0], &[3.0, 4.0]), 11.0);
+
+        assert!(DistanceMetric::Cosine.higher_is_better());
+        assert!(DistanceMetric::DotProduct.higher_is_better());
+        assert!(!DistanceMetric::Euclidean.higher_is_better());
+    }
+
+    #[test]
+    fn test_metric_serde() {
+        assert_eq!(serde_json::to_string(&DistanceMetric::DotProduct).unwrap(), "\"dotproduct\"");
+        let store: VectorStore = serde_json::from_str(r#"{"embeddings":[[1.0]],"dimension":1}"#).unwrap();
+        assert_eq!(store.metric, DistanceMetric::Cosine);
+    }
+
+    #[test]
+    fn test_new_normalized() {
+        let store = VectorStore::new_normalized(vec![vec![3.0, 4.0], vec![0.0, 0.0], vec![0.0, 5.0]]).unwrap();
+        assert!(store.normalized);
+        assert_eq!(store.embeddings[0], vec![0.6, 0.8]);
+        assert_eq!(store.embeddings[1], vec![0.0, 0.0]);
+
+        let plain = VectorStore::new(vec![vec![3.0, 4.0], vec![0.0, 0.0], vec![0.0, 5.0]]).unwrap();
+        let (fast, slow) = (store.search(&[2.0, 1.0], 10).unwrap(), plain.search(&[2.0, 1.0], 10).unwrap());
+        assert_eq!(ids(&fast), ids(&slow));
+        for ((_, a), (_, b)) in fast.iter().zip(&slow) {
+            assert!((a - b).abs() < 1e-6);
+        }
+        assert_eq!(fast[2], (1, 0.0));
+    }
+
+    #[test]
+    fn test_add_and_remove() {
+        let mut store = VectorStore::default();
+        assert_eq!(store.add(vec![1.0, 0.0]).unwrap(), 0);
+        assert_eq!(store.add(vec![0.0, 1.0]).unwrap(), 1);
+        assert_eq!(store.dimension, 2);
+
+        let err = store.add(vec![1.0, 2.0, 3.0]).unwrap_err();
+        assert_eq!(err.to_string(), "Embedding has dimension 3 but expected 2");
+        assert!(store.add(vec![f32::NAN, 0.0]).is_err());
+        assert_eq!(store.len(), 2);
+
+        store.remove(0).unwrap();
+        assert!(store.remove(0).is_err());
+        assert!(store.remove(9).is_err());
+        assert!(!store.contains(0) && store.contains(1));
+        assert_eq!(ids(&store.search(&[1.0, 0.0], 10).unwrap()), vec![1]);
+        assert_eq!(store.add(vec![1.0, 0.0]).unwrap(), 2);
+        assert_eq!(ids(&store.search(&[1.0, 0.0], 10).unwrap()), vec![2, 1]);
+        assert_eq!(store.len(), 2);
+
+        let mut normalized = VectorStore::new_normalized(vec![vec![1.0, 0.0]]).unwrap();
+        normalized.add(vec![0.0, 2.0]).unwrap();
+        assert_eq!(normalized.embeddings[1], vec![0.0, 1.0]);
+    }
+
+    #[test]
+    fn test_cosine_similarity() {
+        assert!((VectorStore::cosine_similarity(&[1.0, 0.0], &[1.0, 0.0]) - 1.0).abs() < 1e-6);
+        assert!(VectorStore::cosine_similarity(&[1.0, 0.0], &[0.0, 1.0]).abs() < 1e-6);
+        assert_eq!(VectorStore::cosine_similarity(&[0.0, 0.0], &[1.0, 0.0]), 0.0);
+        assert_eq!(VectorStore::cosine_similarity(&[1.0], &[1.0, 0.0]), 0.0);
+    }
+
+    /// Plain loop the other implementations are checked against
+    fn reference_cosine(a: &[f32], b: &[f32]) -> f32 {
+        let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+        let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+        let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+        dot / (norm_a * norm_b)
+    }
+
+    #[test]
+    fn test_cosine_matches_reference() {
+        // Lengths that exercise full 8-lane chunks and every remainder
+        for dim in [1, 7, 8, 9, 31, 768, 1536] {
+            let a = mock_embed(&format!("a{}", dim), dim);
+            let b: Vec<f32> = mock_embed(&format!("b{}", dim), dim).iter().map(|x| x * 3.0 + 0.01).collect();
+            let expected = reference_cosine(&a, &b);
+            assert!((VectorStore::cosine_similarity(&a, &b) - expected).abs() < 1e-5, "dim {}", dim);
+
+            let norm = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+            let with_norm = VectorStore::cosine_similarity_with_query_norm(&a, &b, norm);
+            assert!((with_norm - VectorStore::cosine_similarity(&a, &b)).abs() < 1e-6, "dim {}", dim);
+            assert!((dot_lanes(&a, &b) - VectorStore::dot_product(&a, &b)).abs() < 1e-4, "dim {}", dim);
+        }
+        assert_eq!(VectorStore::cosine_similarity_with_query_norm(&[1.0], &[1.0, 0.0], 1.0), 0.0);
+    }
+
+    #[test]
+    fn test_search_checks_query() {
+        let store = VectorStore::new(vec![vec![1.0, 0.0], vec![0.0, 1.0]]).unwrap();
+        let err = store.search(&[1.0, 0.0, 0.0], 1).unwrap_err();
+        assert_eq!(err.to_string(), "Query has dimension 3 but the store expects 2");
+        assert!(store.search(&[f32::NAN, 0.0], 1).is_err());
+        assert!(store.search(&[f32::INFINITY, 0.0], 1).is_err());
+        assert!(VectorStore::default().search(&[1.0], 1).unwrap().is_empty());
+
+        let err = VectorStore::try_cosine_similarity(&[1.0], &[1.0, 0.0]).unwrap_err();
+        assert_eq!(err.to_string(), "Cannot compare vectors of dimension 1 and 2");
+        assert_eq!(VectorStore::try_cosine_similarity(&[2.0, 0.0], &[1.0, 0.0]).unwrap(), 1.0);
+    }
+
+    #[test]
+    fn test_search_batch_matches_search() {
+        let store = VectorStore::new(random_embeddings(50, 8)).unwrap();
+        let queries = random_embeddings(5, 8);
+        let batch = store.search_batch(&queries, 5).unwrap();
+        for (query, results) in queries.iter().zip(&batch) {
+            assert_eq!(results, &store.search(query, 5).unwrap());
+        }
+
+        assert!(store.search_batch(&[], 5).unwrap().is_empty());
+        let err = store.search_batch(&[queries[0].clone(), vec![1.0]], 5).unwrap_err();
+        assert!(err.to_string().starts_with("Query 1:"));
+        assert_eq!(VectorStore::default().search_batch(&queries, 5).unwrap(), vec![Vec::new(); 5]);
+    }
+
+    #[test]
+    fn test_search_filtered() {
+        let store = VectorStore::new(random_embeddings(20, 8)).unwrap();
+        let query = mock_embed("query", 8);
+        let allowed: HashSet<usize> = (0..20).step_by(3).collect();
+
+        let all = store.search(&query, 20).unwrap();
+        let filtered = store.search_filtered(&query, 20, &allowed).unwrap();
+        assert_eq!(filtered.len(), allowed.len());
+        assert!(filtered.iter().all(|result| all.contains(result)));
+        let expected: Vec<(usize, f32)> = all.iter().filter(|(idx, _)| allowed.contains(idx)).copied().collect();
+        assert_eq!(filtered, expected);
+
+        assert_eq!(store.search_filtered(&query, 2, &allowed).unwrap(), expected[..2].to_vec());
+        assert!(store.search_filtered(&query, 5, &HashSet::new()).unwrap().is_empty());
+        assert!(store.search_filtered(&query, 5, &HashSet::from([99])).unwrap().is_empty());
+        // The query is still checked when nothing is allowed
+        assert!(store.search_filtered(&[1.0], 5, &HashSet::new()).is_err());
+    }
+
+    #[test]
+    fn test_heap_selection_matches_full_sort() {
+        let store = VectorStore::new(random_embeddings(500, 16)).unwrap();
+        let query = mock_embed("query", 16);
+        let mut sorted: Vec<(usize, f32)> = store
             .embeddings
             .iter()
             .enumerate()
-            .map(|(idx, emb)| (idx, Self::cosine_similarity(query_embedding, emb)))
+            .map(|(idx, emb)| (idx, VectorStore::cosine_similarity(&query, emb)))
             .collect();
+        sorted.sort_by(cmp_desc);
+        let results = store.search(&query, 10).unwrap();
+        assert_eq!(ids(&results), ids(&sorted[..10]));
+    }
+
+    fn cmp_desc(a: &(usize, f32), b: &(usize, f32)) -> std::cmp::Ordering {
+        b.1.total_cmp(&a.1).then(a.0.cmp(&b.0))
+    }
+
+    #[test]
+    fn test_ties_break_by_index() {
+        let store = VectorStore::new(vec![vec![0.0, 1.0], vec![1.0, 0.0], vec![0.0, 1.0], vec![1.0, 0.0]]).unwrap();
+        assert_eq!(ids(&store.search(&[1.0, 0.0], 4).unwrap()), vec![1, 3, 0, 2]);
+        assert_eq!(ids(&store.search(&[1.0, 0.0], 1).unwrap()), vec![1]);
+
+        let euclidean = VectorStore::with_metric(store.embeddings.clone(), DistanceMetric::Euclidean).unwrap();
+        assert_eq!(ids(&euclidean.search(&[1.0, 0.0], 4).unwrap()), vec![1, 3, 0, 2]);
+    }
 
-        similarities.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
-        similarities.truncate(limit);
-        similarities
+    #[test]
+    fn test_min_score() {
+        let store = store_2d();
+        let all = store.search(&[1.0, 0.0], 10).unwrap();
+        assert_eq!(store.search_with_min_score(&[1.0, 0.0], 10, None).unwrap(), all);
+
+        let close = store.search_with_min_score(&[1.0, 0.0], 10, Some(0.5)).unwrap();
+        assert_eq!(ids(&close), vec![0, 2]);
+
+        let euclidean = VectorStore::with_metric(store.embeddings.clone(), DistanceMetric::Euclidean).unwrap();
+        let near = euclidean.search_with_min_score(&[1.0, 0.0], 10, Some(1.0)).unwrap();
+        assert_eq!(ids(&near), vec![0, 2]);
+
+        let (results, total) = store.search_with_count(&[1.0, 0.0], 1, Some(0.0)).unwrap();
+        assert_eq!((ids(&results), total), (vec![0], 3));
+        assert_eq!(store.search_with_count(&[1.0, 0.0], 1, None).unwrap().1, 4);
+        assert_eq!(VectorStore::default().search_with_count(&[1.0], 10, None).unwrap(), (vec![], 0));
+    }
+
+    #[test]
+    fn test_binary_round_trip() {
+        let mut store = VectorStore::with_metric(random_embeddings(10, 6), DistanceMetric::Euclidean).unwrap();
+        store.remove(3).unwrap();
+        let path = temp_path("roundtrip");
+        store.save_binary(&path).unwrap();
+        let size = std::fs::metadata(&path).unwrap().len() as usize;
+        let loaded = VectorStore::load_binary(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(size, HEADER_LEN + 10 * 6 * 4 + 8);
+        assert_eq!(loaded.embeddings, store.embeddings);
+        assert_eq!((loaded.metric, loaded.len()), (DistanceMetric::Euclidean, 9));
+        let query = mock_embed("query", 6);
+        assert_eq!(loaded.search(&query, 10).unwrap(), store.search(&query, 10).unwrap());
+    }
+
+    #[test]
+    fn test_binary_empty_store() {
+        let path = temp_path("empty");
+        VectorStore::default().save_binary(&path).unwrap();
+        let loaded = VectorStore::load_binary(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(loaded.is_empty());
+
+        let zero_dim = VectorStore::new(vec![vec![], vec![]]).unwrap();
+        assert!(zero_dim.save_binary(temp_path("zero")).is_err());
+    }
+
+    #[test]
+    fn test_load_binary_rejects_malformed_files() {
+        let path = temp_path("malformed");
+        VectorStore::new(random_embeddings(3, 4)).unwrap().save_binary(&path).unwrap();
+        let good = std::fs::read(&path).unwrap();
+        let load = |bytes: &[u8]| {
+            std::fs::write(&path, bytes).unwrap();
+            VectorStore::load_binary(&path)
+        };
+
+        assert!(load(&good[..good.len() - 1]).is_err());
+        assert!(load(&[good.clone(), vec![0]].concat()).is_err());
+        assert!(load(b"EVS1").is_err());
+        assert!(load(&[b"XXXX", &good[4..]].concat()).is_err());
+
+        let mut bad_version = good.clone();
+        bad_version[4] = 9;
+        assert!(load(&bad_version).unwrap_err().to_string().contains("version"));
+
+        let mut bad_metric = good.clone();
+        bad_metric[32] = 7;
+        assert!(load(&bad_metric).unwrap_err().to_string().contains("metric"));
+
+        let mut nan = good.clone();
+        nan[HEADER_LEN..HEADER_LEN + 4].copy_from_slice(&f32::NAN.to_le_bytes());
+        assert!(load(&nan).is_err());
+
+        // A huge row count with dimension 0 would otherwise pass the size check
+        let mut zero_dim = good[..HEADER_LEN].to_vec();
+        zero_dim[8..16].copy_from_slice(&0u64.to_le_bytes());
+        zero_dim[16..24].copy_from_slice(&(1u64 << 60).to_le_bytes());
+        assert!(load(&zero_dim).unwrap_err().to_string().contains("dimension 0"));
+
+        let mut huge = good[..HEADER_LEN].to_vec();
+        huge[16..24].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(load(&huge).is_err());
+
+        let mut bad_removed = good.clone();
+        bad_removed[24..32].copy_from_slice(&1u64.to_le_bytes());
+        bad_removed.extend_from_slice(&5u64.to_le_bytes());
+        assert!(load(&bad_removed).unwrap_err().to_string().contains("out of range"));
+        std::fs::remove_file(&path).unwrap();
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_similarity_matrix() {
+        let embeddings = vec![vec![1.0, 0.0], vec![0.6, 0.8], vec![0.0, 0.0], vec![0.0, 1.0]];
+        let mut store = VectorStore::new(embeddings).unwrap();
+        let matrix = store.similarity_matrix(10).unwrap();
+        for (i, row) in matrix.iter().enumerate() {
+            for (j, &value) in row.iter().enumerate() {
+                assert_eq!(value, matrix[j][i]);
+            }
+        }
+        assert_eq!([matrix[0][0], matrix[1][1], matrix[2][2], matrix[3][3]], [1.0, 1.0, 0.0, 1.0]);
+        assert!((matrix[0][1] - 0.6).abs() < 1e-6);
+
+        store.remove(1).unwrap();
+        let matrix = store.similarity_matrix(10).unwrap();
+        assert!(matrix[1].iter().all(|&x| x == 0.0));
+        assert!(store.similarity_matrix(3).is_err());
+    }
+
+    #[test]
+    fn test_find_near_duplicates() {
+        let base = mock_embed("base", 64);
+        let near: Vec<f32> = base.iter().enumerate().map(|(i, x)| if i == 0 { x + 0.01 } else { *x }).collect();
+        let far: Vec<f32> = base.iter().enumerate().map(|(i, x)| if i < 16 { -x } else { *x }).collect();
+        let mut store = VectorStore::new(vec![base, mock_embed("other", 64), near, far, vec![0.0; 64]]).unwrap();
+
+        assert_eq!(store.find_near_duplicates(0.98), vec![(0, 2)]);
+        let similarity = VectorStore::cosine_similarity(&store.embeddings[0], &store.embeddings[3]);
+        assert!(similarity < 0.98);
+        assert!(store.find_near_duplicates(similarity - 0.01).contains(&(0, 3)));
+
+        store.remove(2).unwrap();
+        assert!(store.find_near_duplicates(0.98).is_empty());
+    }
+
+    #[test]
+    fn test_search_dot_matches_cosine() {
+        let store = VectorStore::new(random_embeddings(40, 12)).unwrap();
+        let query = mock_embed("query", 12);
+        let dot = store.search_dot(&query, 10).unwrap();
+        let cosine = store.search(&query, 10).unwrap();
+        assert_eq!(ids(&dot), ids(&cosine));
+        for ((_, a), (_, b)) in dot.iter().zip(&cosine) {
+            assert!((a - b).abs() < 1e-5);
+        }
+        assert!(VectorStore::default().search_dot(&[1.0], 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_search_into_reuses_buffer() {
+        let store = VectorStore::new(random_embeddings(30, 8)).unwrap();
+        let mut buffer = Vec::new();
+        for (i, query) in random_embeddings(4, 8).iter().enumerate() {
+            store.search_into(query, 5, &mut buffer).unwrap();
+            assert_eq!(buffer, store.search(query, 5).unwrap());
+            if i == 0 {
+                store.search_into(query, 0, &mut buffer).unwrap();
+                assert_eq!(buffer, store.search(query, 0).unwrap());
+            }
+        }
+        let capacity = buffer.capacity();
+        store.search_into(&mock_embed("again", 8), 5, &mut buffer).unwrap();
+        assert_eq!(buffer.capacity(), capacity);
+
+        assert!(store.search_into(&[1.0], 5, &mut buffer).is_err());
+        assert!(buffer.is_empty());
+    }
+}